@@ -1,9 +1,25 @@
 mod otpauth_url;
 
+use serde::Deserialize;
 use zeroize::Zeroizing;
 
-pub(crate) fn process(data: Zeroizing<Vec<u8>>) -> miette::Result<Zeroizing<Vec<u8>>> {
-    if otpauth_url::OtpauthUrl::applies(&data)? {
+/// The automatic transformations that can be enabled, by the name used in the store config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Kind {
+    /// Replaces an `otpauth://` URL with the current TOTP code
+    OtpauthUrl,
+}
+
+impl Kind {
+    pub(crate) const ALL: &[Self] = &[Self::OtpauthUrl];
+}
+
+pub(crate) fn process(
+    data: Zeroizing<Vec<u8>>,
+    enabled: &[Kind],
+) -> miette::Result<Zeroizing<Vec<u8>>> {
+    if enabled.contains(&Kind::OtpauthUrl) && otpauth_url::OtpauthUrl::applies(&data)? {
         otpauth_url::OtpauthUrl::apply(data)
     } else {
         Ok(data)
@@ -25,14 +41,23 @@ mod tests {
         let data = Zeroizing::new(
             b"otpauth://totp/Example:alice@google.com?secret=JBSWY3DPEHPK3PXP".to_vec(),
         );
-        let result = process(data.clone()).unwrap();
+        let result = process(data.clone(), Kind::ALL).unwrap();
         assert_eq!(result.len(), 6);
     }
 
+    #[test]
+    fn test_process_disabled() {
+        let data = Zeroizing::new(
+            b"otpauth://totp/Example:alice@google.com?secret=JBSWY3DPEHPK3PXP".to_vec(),
+        );
+        let result = process(data.clone(), &[]).unwrap();
+        assert_eq!(*result, *data);
+    }
+
     #[test]
     fn test_process_non_otpauth_url() {
         let data = Zeroizing::new(b"not an otpauth url".to_vec());
-        let result = process(data.clone()).unwrap();
+        let result = process(data.clone(), Kind::ALL).unwrap();
         assert_eq!(*result, *data);
     }
 }
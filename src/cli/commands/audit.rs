@@ -0,0 +1,342 @@
+use crate::{
+    cli::{Context, Run, print_json},
+    secret_store::Store,
+    utils::{document::Value, password::estimate_entropy},
+};
+use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use miette::miette;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Audit the secrets in the store.
+///
+/// Every record is decrypted and checked for secrets that are reused across records, passwords
+/// with a low estimated entropy and records that have a password but no OTP.
+///
+/// Only the password attribute, the attributes given with `--reuse-attribute` and records that
+/// aren't YAML or JSON are checked for reuse, so that e.g. usernames shared between records aren't
+/// reported. Values are only compared by their hashes, which are held in memory and never printed.
+/// Records holding binary data that isn't text are skipped.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Name of the attribute that holds a password
+    #[arg(long, default_value = "password")]
+    password_attribute: String,
+
+    /// Name of the attribute that holds an OTP secret
+    #[arg(long, default_value = "otp")]
+    otp_attribute: String,
+
+    /// Name of another attribute that holds a secret that shouldn't be reused, can be given
+    /// multiple times
+    #[arg(long = "reuse-attribute", value_name = "NAME", default_values = ["secret", "token"])]
+    reuse_attributes: Vec<String>,
+
+    /// Passwords with fewer bits of estimated entropy than this are reported as weak
+    #[arg(long, default_value_t = 60.0)]
+    min_entropy: f64,
+
+    /// Number of records to decrypt in parallel (defaults to the number of CPUs)
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Path under which to audit
+    #[arg(add = ArgValueCompleter::new(super::complete_location))]
+    path: Option<PathBuf>,
+}
+
+/// The parts of a record that are needed for the audit, with all plaintext discarded.
+struct RecordSummary {
+    /// Location (`path:attribute`) and hash of each secret that is checked for reuse
+    hashes: Vec<(String, [u8; 32])>,
+    /// Location and estimated entropy of each weak password
+    weak: Vec<(String, f64)>,
+    has_password: bool,
+    has_otp: bool,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
+
+        let records = store.list_records(self.path.as_deref())?;
+
+        let results =
+            crate::utils::parallel::map(&records, self.jobs, |path| self.summarise(&store, path));
+
+        let mut hashes = Vec::new();
+        let mut weak = Vec::new();
+        let mut missing_otp = Vec::new();
+        let mut skipped = Vec::new();
+        let mut errors = Vec::new();
+
+        for (path, result) in records.iter().zip(results) {
+            match result {
+                Ok(None) => skipped.push(path.display().to_string()),
+                Ok(Some(summary)) => {
+                    hashes.extend(
+                        summary
+                            .hashes
+                            .into_iter()
+                            .map(|(location, hash)| (path.as_path(), location, hash)),
+                    );
+
+                    weak.extend(summary.weak);
+
+                    if summary.has_password && !summary.has_otp {
+                        missing_otp.push(path.display().to_string());
+                    }
+                }
+                Err(e) => errors.push((path, e)),
+            }
+        }
+
+        let reused = find_reused(hashes);
+
+        if ctx.json() {
+            let report = serde_json::json!({
+                "reused": reused,
+                "weak": weak
+                    .iter()
+                    .map(|(location, bits)| serde_json::json!({
+                        "location": location,
+                        "entropy": bits.round(),
+                    }))
+                    .collect::<Vec<_>>(),
+                "missing_otp": missing_otp,
+                "skipped": skipped,
+                "errors": errors
+                    .iter()
+                    .map(|(path, e)| serde_json::json!({
+                        "record": path.display().to_string(),
+                        "error": e.to_string(),
+                    }))
+                    .collect::<Vec<_>>(),
+            });
+
+            print_json(&report)?;
+        } else {
+            for path in &skipped {
+                eprintln!("Skipped `{path}`, which is binary data");
+            }
+
+            for (path, e) in &errors {
+                eprintln!("Failed to audit `{}`: {e:?}", path.display());
+            }
+
+            if reused.is_empty() && weak.is_empty() && missing_otp.is_empty() {
+                println!("No issues found.");
+            }
+
+            if !reused.is_empty() {
+                println!("Reused values:");
+                for locations in &reused {
+                    println!("  {}", locations.join(", "));
+                }
+            }
+
+            if !weak.is_empty() {
+                println!("Weak passwords:");
+                for (location, bits) in &weak {
+                    println!("  {location} (~{bits:.0} bits)");
+                }
+            }
+
+            if !missing_otp.is_empty() {
+                println!("Missing OTP:");
+                for path in &missing_otp {
+                    println!("  {path}");
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(miette!("Failed to audit {} record(s)", errors.len()))
+        }
+    }
+}
+
+impl Command {
+    /// Decrypts a single record and reduces it to what is needed for the audit, `None` if the
+    /// record is binary data.
+    fn summarise(&self, store: &Store, path: &Path) -> miette::Result<Option<RecordSummary>> {
+        let Some(values) = store.get_record(path)?.decrypt_text_values(None)? else {
+            return Ok(None);
+        };
+
+        let mut summary = RecordSummary {
+            hashes: Vec::new(),
+            weak: Vec::new(),
+            has_password: false,
+            has_otp: false,
+        };
+
+        for (attribute, value) in values.iter() {
+            let Value::String(value) = &**value else {
+                continue;
+            };
+
+            let location = match attribute {
+                Some(attribute) => format!("{}:{attribute}", path.display()),
+                None => path.display().to_string(),
+            };
+            let name = attribute.as_deref().and_then(|a| a.rsplit('/').next());
+
+            if name == Some(self.otp_attribute.as_str()) {
+                summary.has_otp = true;
+            }
+
+            if name == Some(self.password_attribute.as_str()) {
+                summary.has_password = true;
+
+                let bits = estimate_entropy(value);
+                if bits < self.min_entropy {
+                    summary.weak.push((location.clone(), bits));
+                }
+            }
+
+            let is_secret = match name {
+                Some(name) => {
+                    name == self.password_attribute
+                        || self.reuse_attributes.iter().any(|a| a == name)
+                }
+                None => true,
+            };
+
+            if is_secret && !value.is_empty() {
+                summary
+                    .hashes
+                    .push((location, Sha256::digest(value.as_bytes()).into()));
+            }
+        }
+
+        Ok(Some(summary))
+    }
+}
+
+/// Groups the locations of values with the same hash, keeping only the groups that span more than
+/// one record.
+fn find_reused(hashes: Vec<(&Path, String, [u8; 32])>) -> Vec<Vec<String>> {
+    let mut locations_by_hash: HashMap<[u8; 32], Vec<(&Path, String)>> = HashMap::new();
+
+    for (path, location, hash) in hashes {
+        locations_by_hash
+            .entry(hash)
+            .or_default()
+            .push((path, location));
+    }
+
+    let mut reused: Vec<Vec<String>> = locations_by_hash
+        .into_values()
+        .filter(|locations| locations.iter().any(|(p, _)| *p != locations[0].0))
+        .map(|locations| locations.into_iter().map(|(_, l)| l).collect())
+        .collect();
+    reused.sort();
+
+    reused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempfile::tempdir;
+
+    fn hash(value: &str) -> [u8; 32] {
+        Sha256::digest(value.as_bytes()).into()
+    }
+
+    #[test]
+    fn summarise_record() {
+        let dir = tempdir().unwrap();
+        let store = crate::utils::test::init_store(dir.path());
+
+        for (path, contents) in [
+            (
+                "web.yaml",
+                "username: alice\npassword: hunter2\napi:\n  token: abc\n  url: https://example.com\n",
+            ),
+            ("note", "hunter2"),
+        ] {
+            store
+                .create_record(Path::new(path))
+                .unwrap()
+                .encrypt_entire_file(contents.as_bytes().to_vec().into())
+                .unwrap();
+        }
+
+        let command = Command::parse_from(["audit"]);
+
+        let summary = command
+            .summarise(&store, Path::new("web.yaml"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            summary.hashes,
+            vec![
+                ("web.yaml:password".to_owned(), hash("hunter2")),
+                ("web.yaml:api/token".to_owned(), hash("abc")),
+            ]
+        );
+        assert_eq!(summary.weak.len(), 1);
+        assert_eq!(summary.weak[0].0, "web.yaml:password");
+        assert!(summary.has_password);
+        assert!(!summary.has_otp);
+
+        let summary = command
+            .summarise(&store, Path::new("note"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(summary.hashes, vec![("note".to_owned(), hash("hunter2"))]);
+        assert!(!summary.has_password);
+
+        let command = Command::parse_from(["audit", "--reuse-attribute", "username"]);
+        let summary = command
+            .summarise(&store, Path::new("web.yaml"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            summary.hashes,
+            vec![
+                ("web.yaml:username".to_owned(), hash("alice")),
+                ("web.yaml:password".to_owned(), hash("hunter2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn reuse_spans_records() {
+        let (a, b, c) = (Path::new("a.yaml"), Path::new("b.yaml"), Path::new("c"));
+
+        let reused = find_reused(vec![
+            (a, "a.yaml:password".into(), hash("hunter2")),
+            (a, "a.yaml:old/password".into(), hash("hunter2")),
+            (b, "b.yaml:password".into(), hash("unique")),
+            (b, "b.yaml:token".into(), hash("abc")),
+            (c, "c".into(), hash("abc")),
+            (c, "c".into(), hash("hunter2")),
+        ]);
+        assert_eq!(
+            reused,
+            vec![
+                vec!["a.yaml:password", "a.yaml:old/password", "c"],
+                vec!["b.yaml:token", "c"],
+            ]
+        );
+
+        // Reuse within a single record isn't reported
+        assert!(
+            find_reused(vec![
+                (a, "a.yaml:password".into(), hash("hunter2")),
+                (a, "a.yaml:old/password".into(), hash("hunter2")),
+            ])
+            .is_empty()
+        );
+    }
+}
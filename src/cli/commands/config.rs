@@ -1,14 +1,13 @@
-use crate::{cli::Run, secret_store::Store};
+use crate::cli::{Context, Run};
 use clap::Parser;
-use std::path::Path;
 
 /// Edit the SOPS configuration.
 #[derive(Debug, Parser)]
 pub(super) struct Command {}
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        let store = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
 
         if !store.edit_config_interactive()? {
             eprintln!("No changes.");
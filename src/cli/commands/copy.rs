@@ -0,0 +1,35 @@
+use crate::cli::{Context, Run};
+use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use std::path::PathBuf;
+
+/// Copies a directory or record.
+///
+/// Each record is decrypted in memory and re-encrypted at its new path, so that it uses the
+/// creation rules that apply there. All of the copies are made in a single commit.
+/// Either path may be given as `@store:path` to refer to a path in another of the stores in the
+/// user config.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Path to the source directory/record
+    #[arg(add = ArgValueCompleter::new(super::complete_location))]
+    source: PathBuf,
+
+    /// Path to copy the directory/record to
+    #[arg(add = ArgValueCompleter::new(super::complete_location))]
+    destination: PathBuf,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let (source_store, source) = ctx.open_store_for(&self.source)?;
+        let (destination_store, destination) = ctx.open_store_for(&self.destination)?;
+
+        let source = source_store.location(source);
+        let destination = destination_store.location(destination);
+
+        source.copy_to(&destination)?;
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
-use crate::{cli::Run, secret_store::Store};
+use crate::cli::{Context, Run};
 use clap::Parser;
 use clap_complete::ArgValueCompleter;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 /// Delete a directory or record from the store.
 #[derive(Debug, Parser)]
@@ -12,8 +12,8 @@ pub(super) struct Command {
 }
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        let store = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
 
         let location = store.location(&self.path);
 
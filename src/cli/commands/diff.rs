@@ -0,0 +1,110 @@
+use crate::{
+    cli::{Context, Run},
+    secret_store::{Record, Values},
+    utils::document::Value,
+};
+use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use miette::miette;
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+/// Show the attributes that differ between two versions of a record.
+///
+/// With no revisions the last committed version is compared with the working tree, with one
+/// revision that version is compared with the working tree, and with two revisions they are
+/// compared with each other.
+/// Added attributes are prefixed with `+`, removed with `-` and changed with `~`.
+/// Values are masked unless `--show` is given.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Show values instead of masking them
+    #[arg(long)]
+    show: bool,
+
+    /// Path to a record
+    #[arg(add = ArgValueCompleter::new(super::complete_record))]
+    path: PathBuf,
+
+    /// Git revision of the old version (defaults to `HEAD`)
+    rev_a: Option<String>,
+
+    /// Git revision of the new version (defaults to the working tree)
+    rev_b: Option<String>,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
+
+        // The record may not exist in one of the versions
+        let record = store.get_record_unchecked(&self.path)?;
+
+        let rev_a = self.rev_a.as_deref().unwrap_or("HEAD");
+        let rev_b = self.rev_b.as_deref();
+
+        let (old, new) = match (
+            Self::values(&record, Some(rev_a))?,
+            Self::values(&record, rev_b)?,
+        ) {
+            (None, None) => {
+                return Err(miette!("No secret found at `{}`", self.path.display()));
+            }
+            (old, new) => (old.unwrap_or_default(), new.unwrap_or_default()),
+        };
+
+        let find = |values: &'_ Values, attribute: &Option<String>| {
+            values
+                .iter()
+                .find(|(a, _)| a == attribute)
+                .map(|(_, v)| v.clone())
+        };
+
+        for (attribute, value) in &new {
+            match find(&old, attribute) {
+                None => println!("+ {}: {}", self.label(attribute), self.mask(value).as_str()),
+                Some(old_value) if old_value != *value => println!(
+                    "~ {}: {} -> {}",
+                    self.label(attribute),
+                    self.mask(&old_value).as_str(),
+                    self.mask(value).as_str()
+                ),
+                Some(_) => {}
+            }
+        }
+
+        for (attribute, value) in &old {
+            if find(&new, attribute).is_none() {
+                println!("- {}: {}", self.label(attribute), self.mask(value).as_str());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Command {
+    /// Decrypts the values of a version of the record, `None` if it does not exist in that version.
+    fn values(record: &Record<'_>, rev: Option<&str>) -> miette::Result<Option<Values>> {
+        if record.exists_at(rev)? {
+            Ok(Some(record.decrypt_values(rev)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn label(&self, attribute: &Option<String>) -> String {
+        match attribute {
+            Some(attribute) => attribute.clone(),
+            None => self.path.display().to_string(),
+        }
+    }
+
+    fn mask(&self, value: &Value) -> Zeroizing<String> {
+        if self.show {
+            Zeroizing::new(value.scalar_to_string().unwrap_or_default())
+        } else {
+            Zeroizing::new("********".into())
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{
+    cli::{Context, Run},
+    secret_store::Store,
+    utils::document::{Format, Value},
+};
+use clap::{Parser, ValueEnum};
+use miette::{IntoDiagnostic, WrapErr, miette};
+use serde::Deserialize;
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+use zeroize::Zeroizing;
+
+/// Message that tells Docker there are no credentials for a registry.
+const NOT_FOUND: &str = "credentials not found in native keychain";
+
+/// Act as a Docker credential helper, keeping registry credentials in the store.
+///
+/// Link koishi to `docker-credential-koishi` somewhere on the `PATH` and set `"credsStore": "koishi"`
+/// in `~/.docker/config.json` for Docker to use it.
+///
+/// Credentials are kept in `<prefix>/<registry>.yaml`, where the registry is the server URL
+/// without its scheme, e.g. `registries/ghcr.io.yaml`.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Directory in the store that credentials are kept in
+    #[arg(long, env = "KOISHI_REGISTRIES_PREFIX", default_value = "registries")]
+    prefix: PathBuf,
+
+    /// Operation requested by Docker
+    #[arg(value_enum)]
+    operation: Operation,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Operation {
+    /// Print the credentials for a registry
+    Get,
+    /// Save the credentials for a registry
+    Store,
+    /// Remove the credentials for a registry
+    Erase,
+    /// Print the username for each registry
+    List,
+}
+
+/// Credentials as sent by Docker to be stored.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Credentials {
+    #[serde(rename = "ServerURL")]
+    server_url: String,
+    username: String,
+    secret: Zeroizing<String>,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let mut input = Zeroizing::new(String::new());
+        let _ = std::io::stdin()
+            .read_to_string(&mut input)
+            .into_diagnostic()?;
+
+        let store = ctx.open_store()?;
+
+        let output = match self.operation {
+            Operation::Get => match self.get(&store, input.trim())? {
+                Some(output) => output,
+                None => {
+                    // Docker looks for this exact message to tell that there are no credentials
+                    println!("{NOT_FOUND}");
+                    std::process::exit(1);
+                }
+            },
+            Operation::Store => self.store(&store, &input)?,
+            Operation::Erase => self.erase(&store, input.trim())?,
+            Operation::List => self.list(&store)?,
+        };
+
+        std::io::stdout()
+            .write_all(output.as_bytes())
+            .into_diagnostic()
+    }
+}
+
+impl Command {
+    /// Credentials for a registry as JSON, `None` if there are none in the store.
+    fn get(&self, store: &Store, server_url: &str) -> miette::Result<Option<Zeroizing<String>>> {
+        let Ok(record) = store.get_record(&self.record_path(server_url)?) else {
+            return Ok(None);
+        };
+
+        let username = record.decrypt_and_extract(Some("username"))?;
+        let secret = record.decrypt_and_extract(Some("secret"))?;
+
+        Ok(Some(Zeroizing::new(
+            serde_json::to_string(&serde_json::json!({
+                "ServerURL": server_url,
+                "Username": *crate::utils::bytes_to_string(username)?,
+                "Secret": *crate::utils::bytes_to_string(secret)?,
+            }))
+            .into_diagnostic()?,
+        )))
+    }
+
+    fn store(&self, store: &Store, input: &str) -> miette::Result<Zeroizing<String>> {
+        let credentials: Credentials = serde_json::from_str(input)
+            .into_diagnostic()
+            .wrap_err("Invalid credentials from Docker")?;
+        let path = self.record_path(&credentials.server_url)?;
+
+        let document = Zeroizing::new(Value::Mapping(vec![
+            (
+                "server_url".into(),
+                Value::String(credentials.server_url.clone()),
+            ),
+            (
+                "username".into(),
+                Value::String(credentials.username.clone()),
+            ),
+            (
+                "secret".into(),
+                Value::String(credentials.secret.to_string()),
+            ),
+        ]));
+
+        let record = match store.get_record(&path) {
+            Ok(record) => {
+                // Docker stores credentials on every login, which shouldn't cause a commit
+                if record.decrypt_document(None, None).ok().as_ref() == Some(&document) {
+                    return Ok(Zeroizing::default());
+                }
+                record
+            }
+            Err(_) => store.create_record(&path)?,
+        };
+
+        let contents = Zeroizing::new(document.emit(Format::Yaml)?);
+        record.encrypt_entire_file(contents.as_bytes().to_vec().into())?;
+
+        Ok(Zeroizing::default())
+    }
+
+    fn erase(&self, store: &Store, server_url: &str) -> miette::Result<Zeroizing<String>> {
+        let path = self.record_path(server_url)?;
+
+        if store.get_record(&path).is_ok() {
+            store.location(&path).delete()?;
+        }
+
+        Ok(Zeroizing::default())
+    }
+
+    /// Lists the username for each registry.
+    ///
+    /// Records that aren't credentials stored by Docker are skipped, so that one of them doesn't
+    /// stop Docker from seeing the rest.
+    fn list(&self, store: &Store) -> miette::Result<Zeroizing<String>> {
+        let mut registries = serde_json::Map::new();
+
+        for path in store.list_records(Some(&self.prefix))? {
+            match registry_username(store, &path) {
+                Ok((server_url, username)) => {
+                    let _ = registries.insert(server_url, username.into());
+                }
+                Err(e) => eprintln!("{:?}", e.wrap_err(format!("Skipping `{}`", path.display()))),
+            }
+        }
+
+        Ok(Zeroizing::new(
+            serde_json::to_string(&registries).into_diagnostic()?,
+        ))
+    }
+
+    /// Path in the store of the record holding the credentials for a registry.
+    fn record_path(&self, server_url: &str) -> miette::Result<PathBuf> {
+        let registry = server_url
+            .split_once("://")
+            .map_or(server_url, |(_, rest)| rest)
+            .trim_end_matches('/');
+
+        if registry.is_empty() {
+            return Err(miette!("No registry given by Docker"));
+        }
+
+        let mut path = self.prefix.clone();
+        for component in registry.split('/') {
+            path.push(super::git_credential::path_component(component)?);
+        }
+
+        Ok(add_yaml_extension(&path))
+    }
+}
+
+/// Reads the server URL and username from a record of registry credentials.
+fn registry_username(store: &Store, path: &Path) -> miette::Result<(String, String)> {
+    let record = store.get_record(path)?;
+    let server_url = record.decrypt_and_extract(Some("server_url"))?;
+    let username = record.decrypt_and_extract(Some("username"))?;
+
+    Ok((
+        crate::utils::bytes_to_string(server_url)?.to_string(),
+        crate::utils::bytes_to_string(username)?.to_string(),
+    ))
+}
+
+/// Adds the YAML extension to a path, without replacing what looks like an extension in a registry
+/// name (e.g. `.io`).
+fn add_yaml_extension(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".yaml");
+    path.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn command(operation: &str) -> Command {
+        Command::parse_from(["docker-credential", operation])
+    }
+
+    fn store_credentials(store: &Store, server_url: &str, username: &str, secret: &str) {
+        let input = serde_json::json!({
+            "ServerURL": server_url,
+            "Username": username,
+            "Secret": secret,
+        });
+        let _ = command("store").store(store, &input.to_string()).unwrap();
+    }
+
+    #[test]
+    fn record_paths() {
+        let command = command("get");
+
+        for (server_url, path) in [
+            ("ghcr.io", "registries/ghcr.io.yaml"),
+            ("https://ghcr.io", "registries/ghcr.io.yaml"),
+            ("https://ghcr.io/", "registries/ghcr.io.yaml"),
+            ("localhost:5000", "registries/localhost:5000.yaml"),
+            (
+                "https://index.docker.io/v1/",
+                "registries/index.docker.io/v1.yaml",
+            ),
+        ] {
+            assert_eq!(command.record_path(server_url).unwrap(), Path::new(path));
+        }
+
+        for server_url in ["", "https://", "https://../etc", "https://ghcr.io//x"] {
+            assert!(command.record_path(server_url).is_err(), "{server_url}");
+        }
+    }
+
+    #[test]
+    fn get_store_and_erase() {
+        let dir = tempdir().unwrap();
+        let store = crate::utils::test::init_store(dir.path());
+
+        assert!(command("get").get(&store, "ghcr.io").unwrap().is_none());
+
+        store_credentials(&store, "https://ghcr.io", "alice", "hunter2");
+        let filename = dir.path().join("registries/ghcr.io.yaml");
+        let contents = std::fs::read(&filename).unwrap();
+
+        let output = command("get")
+            .get(&store, "https://ghcr.io")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+            serde_json::json!({
+                "ServerURL": "https://ghcr.io",
+                "Username": "alice",
+                "Secret": "hunter2",
+            })
+        );
+
+        // Logging in again with the same credentials leaves the record as it is
+        store_credentials(&store, "https://ghcr.io", "alice", "hunter2");
+        assert_eq!(std::fs::read(&filename).unwrap(), contents);
+
+        let _ = command("erase").erase(&store, "https://ghcr.io").unwrap();
+        assert!(!filename.exists());
+        assert!(
+            command("get")
+                .get(&store, "https://ghcr.io")
+                .unwrap()
+                .is_none()
+        );
+
+        // Erasing credentials that don't exist isn't an error
+        let _ = command("erase").erase(&store, "https://ghcr.io").unwrap();
+    }
+
+    #[test]
+    fn list_skips_other_records() {
+        let dir = tempdir().unwrap();
+        let store = crate::utils::test::init_store(dir.path());
+
+        store_credentials(&store, "https://ghcr.io", "alice", "hunter2");
+        store_credentials(&store, "https://index.docker.io/v1/", "bob", "abc");
+        for (path, contents) in [
+            ("registries/notes", "not credentials"),
+            ("registries/partial.yaml", "username: carol\n"),
+        ] {
+            store
+                .create_record(Path::new(path))
+                .unwrap()
+                .encrypt_entire_file(contents.as_bytes().to_vec().into())
+                .unwrap();
+        }
+
+        let output = command("list").list(&store).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+            serde_json::json!({
+                "https://ghcr.io": "alice",
+                "https://index.docker.io/v1/": "bob",
+            })
+        );
+    }
+}
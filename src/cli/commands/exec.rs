@@ -0,0 +1,161 @@
+use crate::{
+    cli::{Context, Run},
+    secret_store::Store,
+    utils::document::Format,
+};
+use clap::Parser;
+use miette::{IntoDiagnostic, WrapErr, miette};
+use std::{ffi::OsString, path::PathBuf, process::Stdio};
+use zeroize::Zeroizing;
+
+/// Run a command with secrets from the store in its environment.
+///
+/// Each `--map` exports a single value as the given environment variable, e.g.
+/// `--map DB_PASS=db/prod:password`. The selector after the colon works the same as for `get`. It
+/// can only be left out for records that hold a single value, such as binary records.
+///
+/// Each `--map-record` exports every value in a YAML or JSON record, named after the path to the
+/// value in upper case, e.g. `api/key` becomes `API_KEY`.
+///
+/// The secrets are only given to the command, they are not written anywhere.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Export a value as an environment variable
+    #[arg(long = "map", value_name = "VAR=PATH[:SELECTOR]", value_parser = parse_map)]
+    maps: Vec<Map>,
+
+    /// Export every value in a record as environment variables
+    #[arg(long = "map-record", value_name = "PATH", add = clap_complete::ArgValueCompleter::new(super::complete_record))]
+    records: Vec<PathBuf>,
+
+    /// Command to run, followed by its arguments
+    #[arg(required = true, last = true)]
+    command: Vec<OsString>,
+}
+
+#[derive(Debug, Clone)]
+struct Map {
+    var: String,
+    path: PathBuf,
+    selector: Option<String>,
+}
+
+fn parse_map(arg: &str) -> Result<Map, String> {
+    let (var, location) = arg.split_once('=').ok_or("expected VAR=PATH[:SELECTOR]")?;
+
+    check_var_name(var)?;
+
+    let (path, selector) = match location.split_once(':') {
+        Some((path, selector)) => (path, Some(selector.to_owned())),
+        None => (location, None),
+    };
+
+    if path.is_empty() {
+        return Err("the path must not be empty".into());
+    }
+
+    Ok(Map {
+        var: var.to_owned(),
+        path: path.into(),
+        selector,
+    })
+}
+
+fn check_var_name(var: &str) -> Result<(), String> {
+    if var.is_empty() || var.contains(['=', '\0']) {
+        Err(format!("`{var}` is not a valid environment variable name"))
+    } else {
+        Ok(())
+    }
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
+
+        let mut env = Vec::new();
+
+        for path in &self.records {
+            env.extend(record_vars(&store, path)?);
+        }
+
+        // Single values are added last so that they can override part of a record
+        for map in &self.maps {
+            let value = store
+                .get_record(&map.path)?
+                .decrypt_document(None, map.selector.as_deref())?;
+
+            let value = value.scalar_to_string().ok_or_else(|| {
+                miette!(
+                    "`{}` is not a single value and can't be exported as `{}`",
+                    map.path.display(),
+                    map.var
+                )
+            })?;
+
+            env.push((map.var.clone(), Zeroizing::new(value)));
+        }
+
+        let res = std::process::Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .envs(env.iter().map(|(var, value)| (var, value.as_str())))
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .into_diagnostic()
+            .wrap_err(format!(
+                "Failed to run `{}`",
+                self.command[0].to_string_lossy()
+            ))?;
+
+        // Pass the exit code of the command on, once the secrets have been zeroized
+        drop(env);
+
+        if res.success() {
+            Ok(())
+        } else {
+            std::process::exit(res.code().unwrap_or(1))
+        }
+    }
+}
+
+/// Decrypts a record into environment variables, one for each value in it.
+fn record_vars(
+    store: &Store,
+    path: &std::path::Path,
+) -> miette::Result<Vec<(String, Zeroizing<String>)>> {
+    let record = store.get_record(path)?;
+
+    if !matches!(
+        Format::from_path(&record.filename()),
+        Format::Yaml | Format::Json
+    ) {
+        return Err(miette!(
+            "`{}` must be a YAML or JSON record to export all of its values",
+            path.display()
+        ));
+    }
+
+    let document = record.decrypt_document(None, None)?;
+
+    // Leaves are always scalars, so every one of them has a string value
+    Ok(document
+        .leaves()
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let var = name
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_uppercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+
+            Some((var, Zeroizing::new(value.scalar_to_string()?)))
+        })
+        .collect())
+}
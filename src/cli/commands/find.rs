@@ -0,0 +1,170 @@
+use crate::cli::{Context, Run, print_json};
+use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use globset::{GlobBuilder, GlobMatcher};
+use miette::{IntoDiagnostic, WrapErr};
+use regex::{Regex, RegexBuilder};
+use std::path::PathBuf;
+
+/// Find records by their path or the names of their attributes.
+///
+/// Only metadata that SOPS leaves in plaintext is searched, nothing is decrypted.
+/// Records with a matching path are printed as `path`, matching attributes are printed as
+/// `path:attribute`.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Treat the pattern as a regular expression instead of a glob
+    #[arg(short = 'e', long)]
+    regex: bool,
+
+    /// Match case insensitively
+    #[arg(short, long)]
+    ignore_case: bool,
+
+    /// Pattern to match record paths and attribute names against
+    pattern: String,
+
+    /// Path under which to search
+    #[arg(add = ArgValueCompleter::new(super::complete_location))]
+    path: Option<PathBuf>,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
+
+        let matcher = Matcher::new(&self.pattern, self.regex, self.ignore_case)?;
+
+        let mut matches = Vec::new();
+
+        for path in store.list_records(self.path.as_deref())? {
+            if matcher.is_match_path(&path.display().to_string()) {
+                matches.push((path.clone(), None));
+            }
+
+            // Records that are not YAML or JSON do not have attributes that can be listed
+            let attributes = store
+                .get_record(&path)?
+                .list_attributes()
+                .unwrap_or_default();
+
+            for attribute in attributes {
+                if matcher.is_match_path(&attribute) {
+                    matches.push((path.clone(), Some(attribute)));
+                }
+            }
+        }
+
+        if ctx.json() {
+            return print_json(&serde_json::json!(
+                matches
+                    .iter()
+                    .map(|(path, attribute)| serde_json::json!({
+                        "record": path.display().to_string(),
+                        "attribute": attribute,
+                    }))
+                    .collect::<Vec<_>>()
+            ));
+        }
+
+        for (path, attribute) in matches {
+            match attribute {
+                Some(attribute) => println!("{}:{attribute}", path.display()),
+                None => println!("{}", path.display()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+enum Matcher {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn new(pattern: &str, regex: bool, ignore_case: bool) -> miette::Result<Self> {
+        if regex {
+            Ok(Self::Regex(
+                RegexBuilder::new(pattern)
+                    .case_insensitive(ignore_case)
+                    .build()
+                    .into_diagnostic()
+                    .wrap_err("Invalid regex pattern")?,
+            ))
+        } else {
+            Ok(Self::Glob(
+                GlobBuilder::new(pattern)
+                    .case_insensitive(ignore_case)
+                    .literal_separator(true)
+                    .build()
+                    .into_diagnostic()
+                    .wrap_err("Invalid glob pattern")?
+                    .compile_matcher(),
+            ))
+        }
+    }
+
+    /// Matches a slash delimited path (of a record or an attribute).
+    ///
+    /// Globs must match either the entire path or its final component, regexes may match any part
+    /// of it.
+    fn is_match_path(&self, path: &str) -> bool {
+        match self {
+            Self::Glob(glob) => {
+                glob.is_match(path)
+                    || path
+                        .rsplit('/')
+                        .next()
+                        .is_some_and(|name| glob.is_match(name))
+            }
+            Self::Regex(regex) => regex.is_match(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_path_or_name() {
+        let matcher = Matcher::new("*.yaml", false, false).unwrap();
+        assert!(matcher.is_match_path("web/site.yaml"));
+        assert!(matcher.is_match_path("site.yaml"));
+        assert!(!matcher.is_match_path("web/site.json"));
+
+        // `*` doesn't cross separators, so a glob over directories must match the whole path
+        let matcher = Matcher::new("web/*", false, false).unwrap();
+        assert!(matcher.is_match_path("web/site.yaml"));
+        assert!(!matcher.is_match_path("old/web/site.yaml"));
+
+        let matcher = Matcher::new("web", false, false).unwrap();
+        assert!(!matcher.is_match_path("web/site.yaml"));
+        assert!(matcher.is_match_path("hosts/web"));
+
+        let matcher = Matcher::new("SITE.*", false, true).unwrap();
+        assert!(matcher.is_match_path("web/site.yaml"));
+    }
+
+    #[test]
+    fn regex_matches_any_part_of_path() {
+        let matcher = Matcher::new("eb/si", true, false).unwrap();
+        assert!(matcher.is_match_path("web/site.yaml"));
+        assert!(!matcher.is_match_path("web/Site.yaml"));
+
+        let matcher = Matcher::new("^web/.*\\.yaml$", true, false).unwrap();
+        assert!(matcher.is_match_path("web/site.yaml"));
+        assert!(!matcher.is_match_path("old/web/site.yaml"));
+
+        let matcher = Matcher::new("SITE", true, true).unwrap();
+        assert!(matcher.is_match_path("web/site.yaml"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert!(Matcher::new("[", true, false).is_err());
+        assert!(Matcher::new("[", false, false).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    cli::{Context, Run},
+    utils::password::CharacterClasses,
+};
+use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use miette::IntoDiagnostic;
+use std::{io::Write, path::PathBuf, time::Duration};
+use zeroize::Zeroizing;
+
+/// Generate a random secret and store it in a record.
+///
+/// The generated secret is never printed to the terminal, it is only written to the record and
+/// optionally made available via the clipboard or as a QR code.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Length of the generated password
+    #[arg(short, long, default_value_t = 24, conflicts_with = "passphrase")]
+    length: usize,
+
+    /// Do not include lowercase letters
+    #[arg(long, conflicts_with = "passphrase")]
+    no_lowercase: bool,
+
+    /// Do not include uppercase letters
+    #[arg(long, conflicts_with = "passphrase")]
+    no_uppercase: bool,
+
+    /// Do not include digits
+    #[arg(long, conflicts_with = "passphrase")]
+    no_digits: bool,
+
+    /// Do not include symbols
+    #[arg(long, conflicts_with = "passphrase")]
+    no_symbols: bool,
+
+    /// Exclude characters that are easily confused (e.g. `l`, `1`, `O` and `0`)
+    #[arg(long, conflicts_with = "passphrase")]
+    exclude_ambiguous: bool,
+
+    /// Generate a diceware style passphrase instead of a password
+    #[arg(short, long)]
+    passphrase: bool,
+
+    /// Number of words in the generated passphrase
+    #[arg(long, default_value_t = 6, requires = "passphrase")]
+    words: usize,
+
+    /// Separator placed between words in the generated passphrase
+    #[arg(long, default_value = "-", requires = "passphrase")]
+    separator: String,
+
+    /// Copy the secret to the clipboard
+    #[arg(short, long, conflicts_with_all = &["qr", "qr_ascii", "qr_unicode"])]
+    copy: bool,
+
+    /// Output the secret as a QR code in a PNG image
+    #[arg(long, conflicts_with_all = &["copy", "qr_ascii", "qr_unicode"])]
+    qr: bool,
+
+    /// Output the secret as a QR code as text using ASCII characters
+    #[arg(long, conflicts_with_all = &["copy", "qr", "qr_unicode"])]
+    qr_ascii: bool,
+
+    /// Output the secret as a QR code as nicer text using unicode characters
+    #[arg(long, conflicts_with_all = &["copy", "qr", "qr_ascii"])]
+    qr_unicode: bool,
+
+    /// Path to a record
+    #[arg(add = ArgValueCompleter::new(super::complete_record))]
+    path: PathBuf,
+
+    /// Part of the record to set
+    selector: Option<String>,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
+
+        let record = if self.selector.is_some() {
+            // Need an existing secret when using a selector
+            store.get_record(&self.path)?
+        } else {
+            // If setting the entire contents, try to get a record, otherwise create a new one
+            match store.get_record(&self.path) {
+                Ok(r) => r,
+                Err(_) => store.create_record(&self.path)?,
+            }
+        };
+
+        let secret = if self.passphrase {
+            crate::utils::password::generate_passphrase(self.words, &self.separator)?
+        } else {
+            let classes = CharacterClasses {
+                lowercase: !self.no_lowercase,
+                uppercase: !self.no_uppercase,
+                digits: !self.no_digits,
+                symbols: !self.no_symbols,
+            };
+            crate::utils::password::generate_password(
+                self.length,
+                &classes,
+                self.exclude_ambiguous,
+            )?
+        };
+        let secret: Zeroizing<Vec<u8>> = secret.as_bytes().to_vec().into();
+
+        match &self.selector {
+            Some(selector) => record.encrypt_set(selector, secret.clone())?,
+            None => record.encrypt_entire_file(secret.clone())?,
+        }
+
+        if self.copy {
+            let clipboard = &store.config().clipboard;
+            crate::utils::clipboard::copy(
+                secret,
+                clipboard.timeout.map(Duration::from_secs),
+                clipboard,
+            )?;
+        } else if self.qr {
+            let png = crate::utils::qr::encode_png(secret)?;
+            std::io::stdout().write_all(&png).into_diagnostic()?;
+        } else if self.qr_ascii {
+            let qr = crate::utils::qr::encode_ascii(secret)?;
+            println!("{}", *qr);
+        } else if self.qr_unicode {
+            let qr = crate::utils::qr::encode_unicode(secret)?;
+            println!("{}", *qr);
+        }
+
+        Ok(())
+    }
+}
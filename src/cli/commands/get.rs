@@ -1,11 +1,13 @@
-use crate::{cli::Run, secret_store::Store};
+use crate::{
+    cli::{Context, Run, print_json},
+    secret_store::Store,
+    utils::document::Value,
+};
 use clap::Parser;
 use clap_complete::ArgValueCompleter;
 use miette::IntoDiagnostic;
-use std::{
-    io::Write,
-    path::{Path, PathBuf},
-};
+use std::{io::Write, path::PathBuf, time::Duration};
+use zeroize::Zeroizing;
 
 /// Gets part or all of a record.
 ///
@@ -13,12 +15,20 @@ use std::{
 /// If a simple string is given then the corresponding attribute under the top level is returned.
 /// If a path of string keys delimited by forward slashes (/) then a path selector is built from this.
 /// Otherwise the selector is assumed to be a path specifier, as per `sops decrypt --extract`
+///
+/// With `--output json` the selected part of the record is output as JSON, keeping the structure
+/// of YAML and JSON records.
 #[derive(Debug, Parser)]
 pub(super) struct Command {
     /// Copy the secret to the clipboard
     #[arg(short, long, conflicts_with_all = &["qr", "qr_ascii", "qr_unicode"])]
     copy: bool,
 
+    /// Withdraw the secret from the clipboard after this many seconds (defaults to the clipboard
+    /// timeout in the store config)
+    #[arg(long, value_name = "SECS", requires = "copy")]
+    timeout: Option<u64>,
+
     /// Output the secret as a QR code in a PNG image
     #[arg(long, conflicts_with_all = &["copy", "qr_ascii", "qr_unicode"])]
     qr: bool,
@@ -35,6 +45,10 @@ pub(super) struct Command {
     #[arg(long)]
     raw: bool,
 
+    /// Get the record as it was at a Git revision (e.g. a commit ID shown by `koishi log`)
+    #[arg(long)]
+    rev: Option<String>,
+
     /// Path to a record
     #[arg(add = ArgValueCompleter::new(super::complete_record))]
     path: PathBuf,
@@ -44,20 +58,36 @@ pub(super) struct Command {
 }
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        let store = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
 
-        let record = store.get_record(&self.path)?;
+        // Selected values are output as a JSON document rather than as raw bytes
+        if ctx.json() && !(self.copy || self.qr || self.qr_ascii || self.qr_unicode) {
+            return self.print_document(&store);
+        }
 
-        let mut secret = record.decrypt_and_extract(self.selector.as_deref())?;
+        let mut secret = match &self.rev {
+            // The record may no longer exist
+            Some(rev) => store
+                .get_record_unchecked(&self.path)?
+                .decrypt_and_extract_at(rev, self.selector.as_deref())?,
+            None => store
+                .get_record(&self.path)?
+                .decrypt_and_extract(self.selector.as_deref())?,
+        };
 
         // Apply auto transforms unless --raw flag is set
         if !self.raw {
-            secret = crate::auto_transforms::process(secret)?;
+            secret = crate::auto_transforms::process(secret, &store.config().auto_transforms)?;
         }
 
         if self.copy {
-            crate::utils::clipboard::copy(secret)?;
+            let clipboard = &store.config().clipboard;
+            crate::utils::clipboard::copy(
+                secret,
+                self.timeout.or(clipboard.timeout).map(Duration::from_secs),
+                clipboard,
+            )?;
         } else if self.qr {
             let png = crate::utils::qr::encode_png(secret)?;
             std::io::stdout().write_all(&png).into_diagnostic()?;
@@ -76,3 +106,29 @@ impl Run for Command {
         Ok(())
     }
 }
+
+impl Command {
+    fn print_document(&self, store: &Store) -> miette::Result<()> {
+        let record = match &self.rev {
+            // The record may no longer exist
+            Some(_) => store.get_record_unchecked(&self.path)?,
+            None => store.get_record(&self.path)?,
+        };
+
+        let mut value = record.decrypt_document(self.rev.as_deref(), self.selector.as_deref())?;
+
+        // Auto transforms only apply to single values
+        if let Value::String(secret) = &*value {
+            if !self.raw {
+                let secret = crate::auto_transforms::process(
+                    secret.as_bytes().to_vec().into(),
+                    &store.config().auto_transforms,
+                )?;
+                value =
+                    Zeroizing::new(Value::String(String::from_utf8_lossy(&secret).into_owned()));
+            }
+        }
+
+        print_json(&value.to_json())
+    }
+}
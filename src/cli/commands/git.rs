@@ -1,7 +1,10 @@
-use crate::{cli::Run, secret_store::Store};
+use crate::{
+    cli::{Context, Run},
+    secret_store::Store,
+};
 use clap::Parser;
 use miette::{IntoDiagnostic, WrapErr, miette};
-use std::{path::Path, process::Stdio};
+use std::process::Stdio;
 
 /// Run Git commands inside the secret store.
 #[derive(Debug, Parser)]
@@ -10,14 +13,15 @@ pub(super) struct Command {
 }
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        // Try to open the store, this is just to provide validation
-        let _ = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        // Try to open the store, this is just to provide validation. The remote is left alone as
+        // the Git command may well be one that talks to it.
+        let _ = Store::open(ctx.store_path(), true)?;
 
         // Run the Git command with the provided arguments
         let res = std::process::Command::new("git")
             .arg("-C")
-            .arg(store_path)
+            .arg(ctx.store_path())
             .args(&self.args)
             .stdin(Stdio::inherit())
             .stdout(Stdio::inherit())
@@ -0,0 +1,345 @@
+use crate::{
+    cli::{Context, Run},
+    secret_store::Store,
+    utils::document::{Format, Value},
+};
+use clap::Parser;
+use miette::{IntoDiagnostic, miette};
+use std::{
+    io::{BufRead, Write},
+    path::PathBuf,
+};
+use zeroize::Zeroizing;
+
+/// Act as a Git credential helper, keeping credentials in the store.
+///
+/// Git can be told to use it with `git config --global credential.helper '!koishi git-credential'`.
+///
+/// Credentials are kept in `<prefix>/<protocol>/<host>/<username>.yaml`, with the password in its
+/// `password` attribute. When Git doesn't know the username, it is taken from the only record for
+/// the host.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Directory in the store that credentials are kept in
+    #[arg(long, default_value = "git")]
+    prefix: PathBuf,
+
+    /// Operation requested by Git: `get`, `store` or `erase`
+    ///
+    /// Other operations are ignored, as Git may add new ones that helpers don't know about.
+    operation: String,
+}
+
+/// The attributes of a credential that are used, any others sent by Git are ignored.
+#[derive(Debug, Default)]
+struct Credential {
+    protocol: Option<String>,
+    host: Option<String>,
+    username: Option<String>,
+    password: Option<Zeroizing<String>>,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let read = || -> miette::Result<_> {
+            Ok((ctx.open_store()?, read_credential(std::io::stdin().lock())?))
+        };
+
+        match self.operation.as_str() {
+            "get" => {
+                let (store, credential) = read()?;
+                self.get(&store, &credential, &mut std::io::stdout())
+            }
+            "store" => {
+                let (store, credential) = read()?;
+                self.store(&store, &credential)
+            }
+            "erase" => {
+                let (store, credential) = read()?;
+                self.erase(&store, &credential)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Command {
+    fn get(
+        &self,
+        store: &Store,
+        credential: &Credential,
+        out: &mut impl Write,
+    ) -> miette::Result<()> {
+        let host_dir = self.host_dir(credential)?;
+
+        let username = match &credential.username {
+            Some(username) => username.clone(),
+            // Without a username the host must only have one
+            None => {
+                let records = store
+                    .list_records(Some(&host_dir))?
+                    .into_iter()
+                    .filter(|path| path.parent() == Some(&host_dir))
+                    .collect::<Vec<_>>();
+
+                match records.as_slice() {
+                    [record] => match record.file_stem().and_then(|s| s.to_str()) {
+                        Some(username) => username.to_owned(),
+                        None => return Ok(()),
+                    },
+                    _ => return Ok(()),
+                }
+            }
+        };
+
+        // Git carries on to other helpers or prompts if nothing is printed
+        let Ok(record) = store.get_record(&record_path(&host_dir, &username)?) else {
+            return Ok(());
+        };
+        let password =
+            crate::utils::bytes_to_string(record.decrypt_and_extract(Some("password"))?)?;
+
+        let output = Zeroizing::new(format!("username={username}\npassword={}\n", *password));
+        out.write_all(output.as_bytes()).into_diagnostic()
+    }
+
+    fn store(&self, store: &Store, credential: &Credential) -> miette::Result<()> {
+        let (Some(username), Some(password)) = (&credential.username, &credential.password) else {
+            return Err(miette!(
+                "Git must give both a username and password to store"
+            ));
+        };
+        let path = record_path(&self.host_dir(credential)?, username)?;
+
+        match store.get_record(&path) {
+            Ok(record) => {
+                // Git stores credentials every time they are used, which shouldn't cause a commit
+                let existing = record.decrypt_and_extract(Some("password")).ok();
+                if existing.as_deref().map(Vec::as_slice) == Some(password.as_bytes()) {
+                    return Ok(());
+                }
+
+                record.encrypt_set("password", password.as_bytes().to_vec().into())
+            }
+            Err(_) => {
+                let contents = Zeroizing::new(
+                    Value::Mapping(vec![(
+                        "password".into(),
+                        Value::String(password.to_string()),
+                    )])
+                    .emit(Format::Yaml)?,
+                );
+
+                store
+                    .create_record(&path)?
+                    .encrypt_entire_file(contents.as_bytes().to_vec().into())
+            }
+        }
+    }
+
+    fn erase(&self, store: &Store, credential: &Credential) -> miette::Result<()> {
+        let Some(username) = &credential.username else {
+            return Ok(());
+        };
+        let path = record_path(&self.host_dir(credential)?, username)?;
+
+        let Ok(record) = store.get_record(&path) else {
+            return Ok(());
+        };
+
+        // Leave the record alone if it has already been changed to something else
+        if let Some(password) = &credential.password {
+            let existing = record.decrypt_and_extract(Some("password"))?;
+            if existing.as_slice() != password.as_bytes() {
+                return Ok(());
+            }
+        }
+
+        store.location(&path).delete()
+    }
+
+    /// Directory in the store that holds the credentials for the host.
+    fn host_dir(&self, credential: &Credential) -> miette::Result<PathBuf> {
+        let (Some(protocol), Some(host)) = (&credential.protocol, &credential.host) else {
+            return Err(miette!("Git must give both a protocol and host"));
+        };
+
+        Ok(self
+            .prefix
+            .join(path_component(protocol)?)
+            .join(path_component(host)?))
+    }
+}
+
+fn record_path(host_dir: &std::path::Path, username: &str) -> miette::Result<PathBuf> {
+    Ok(host_dir.join(format!("{}.yaml", path_component(username)?)))
+}
+
+/// Checks that a value from Git can be used as a single part of a path in the store.
+pub(super) fn path_component(value: &str) -> miette::Result<&str> {
+    if value.is_empty() || value.starts_with('.') || value.contains(['/', '\\']) {
+        Err(miette!(
+            "`{value}` can't be used as part of a path in the store"
+        ))
+    } else {
+        Ok(value)
+    }
+}
+
+/// Reads the attributes of a credential from Git, which are sent as `key=value` lines ending with a
+/// blank line.
+fn read_credential(input: impl BufRead) -> miette::Result<Credential> {
+    let mut credential = Credential::default();
+
+    for line in input.lines() {
+        let line = Zeroizing::new(line.into_diagnostic()?);
+
+        if line.is_empty() {
+            break;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        match key {
+            "protocol" => credential.protocol = Some(value.to_owned()),
+            "host" => credential.host = Some(value.to_owned()),
+            "username" => credential.username = Some(value.to_owned()),
+            "password" => credential.password = Some(Zeroizing::new(value.to_owned())),
+            _ => {}
+        }
+    }
+
+    Ok(credential)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn command(operation: &str) -> Command {
+        Command::parse_from(["git-credential", operation])
+    }
+
+    fn credential(input: &str) -> Credential {
+        read_credential(input.as_bytes()).unwrap()
+    }
+
+    fn get(store: &Store, input: &str) -> String {
+        let mut out = Vec::new();
+        command("get")
+            .get(store, &credential(input), &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn parse_credential() {
+        let credential = credential(
+            "protocol=https\nhost=example.com\nusername=alice\npassword=a=b\nwwwauth[]=Basic\n\nhost=ignored\n",
+        );
+
+        assert_eq!(credential.protocol.as_deref(), Some("https"));
+        assert_eq!(credential.host.as_deref(), Some("example.com"));
+        assert_eq!(credential.username.as_deref(), Some("alice"));
+        assert_eq!(
+            credential.password.as_deref().map(String::as_str),
+            Some("a=b")
+        );
+    }
+
+    #[test]
+    fn get_credentials() {
+        let dir = tempdir().unwrap();
+        let store = crate::utils::test::init_store(dir.path());
+
+        command("store")
+            .store(
+                &store,
+                &credential("protocol=https\nhost=example.com\nusername=alice\npassword=hunter2\n"),
+            )
+            .unwrap();
+
+        assert_eq!(
+            get(&store, "protocol=https\nhost=example.com\nusername=alice\n"),
+            "username=alice\npassword=hunter2\n"
+        );
+        // The username is taken from the only record for the host
+        assert_eq!(
+            get(&store, "protocol=https\nhost=example.com\n"),
+            "username=alice\npassword=hunter2\n"
+        );
+        assert_eq!(
+            get(&store, "protocol=https\nhost=example.com\nusername=bob\n"),
+            ""
+        );
+        assert_eq!(get(&store, "protocol=http\nhost=example.com\n"), "");
+
+        // With more than one username for the host, Git has to say which one it wants
+        command("store")
+            .store(
+                &store,
+                &credential("protocol=https\nhost=example.com\nusername=bob\npassword=abc\n"),
+            )
+            .unwrap();
+        assert_eq!(get(&store, "protocol=https\nhost=example.com\n"), "");
+    }
+
+    #[test]
+    fn store_unchanged_password() {
+        let dir = tempdir().unwrap();
+        let store = crate::utils::test::init_store(dir.path());
+        let input =
+            credential("protocol=https\nhost=example.com\nusername=alice\npassword=hunter2\n");
+        let filename = dir.path().join("git/https/example.com/alice.yaml");
+
+        command("store").store(&store, &input).unwrap();
+        let contents = std::fs::read(&filename).unwrap();
+
+        // The record isn't rewritten, so nothing is committed
+        command("store").store(&store, &input).unwrap();
+        assert_eq!(std::fs::read(&filename).unwrap(), contents);
+
+        command("store")
+            .store(
+                &store,
+                &credential("protocol=https\nhost=example.com\nusername=alice\npassword=hunter3\n"),
+            )
+            .unwrap();
+        assert_ne!(std::fs::read(&filename).unwrap(), contents);
+    }
+
+    #[test]
+    fn erase_matching_password() {
+        let dir = tempdir().unwrap();
+        let store = crate::utils::test::init_store(dir.path());
+        let filename = Path::new("git/https/example.com/alice.yaml");
+
+        command("store")
+            .store(
+                &store,
+                &credential("protocol=https\nhost=example.com\nusername=alice\npassword=hunter2\n"),
+            )
+            .unwrap();
+
+        // The password has been changed since Git used it
+        command("erase")
+            .erase(
+                &store,
+                &credential("protocol=https\nhost=example.com\nusername=alice\npassword=old\n"),
+            )
+            .unwrap();
+        assert!(dir.path().join(filename).exists());
+
+        command("erase")
+            .erase(
+                &store,
+                &credential("protocol=https\nhost=example.com\nusername=alice\npassword=hunter2\n"),
+            )
+            .unwrap();
+        assert!(!dir.path().join(filename).exists());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{
+    cli::{Context, Run, print_json},
+    secret_store::Store,
+};
+use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use miette::{IntoDiagnostic, WrapErr, miette};
+use regex::{Regex, RegexBuilder};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Search the decrypted values of records for a regular expression.
+///
+/// Matching attributes are printed as `path:attribute`, records that are not YAML or JSON are
+/// searched as a whole and printed as `path`. Matched values are masked unless `--show` is given.
+/// Records holding binary data that isn't text are skipped.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Show matching values instead of masking them
+    #[arg(long)]
+    show: bool,
+
+    /// Match case insensitively
+    #[arg(short, long)]
+    ignore_case: bool,
+
+    /// Number of records to decrypt in parallel (defaults to the number of CPUs)
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Regular expression to match values against
+    pattern: String,
+
+    /// Path under which to search
+    #[arg(add = ArgValueCompleter::new(super::complete_location))]
+    path: Option<PathBuf>,
+}
+
+/// A value that matched the pattern.
+struct Match {
+    /// Path of the attribute holding the value, `None` if the record was searched as a whole
+    attribute: Option<String>,
+    value: Zeroizing<String>,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
+
+        let regex = RegexBuilder::new(&self.pattern)
+            .case_insensitive(self.ignore_case)
+            .build()
+            .into_diagnostic()
+            .wrap_err("Invalid regex pattern")?;
+
+        let records = store.list_records(self.path.as_deref())?;
+
+        let results =
+            crate::utils::parallel::map(&records, self.jobs, |path| search(&store, path, &regex));
+
+        let mut failed = 0;
+        let mut json = Vec::new();
+
+        for (path, result) in records.iter().zip(results) {
+            match result {
+                Ok(None) => {
+                    if !ctx.json() {
+                        eprintln!("Skipped `{}`, which is binary data", path.display());
+                    }
+                }
+                Ok(Some(matches)) => {
+                    for m in matches {
+                        if ctx.json() {
+                            json.push(serde_json::json!({
+                                "record": path.display().to_string(),
+                                "attribute": m.attribute,
+                                "value": self.show.then_some(m.value.as_str()),
+                            }));
+                            continue;
+                        }
+
+                        let location = match &m.attribute {
+                            Some(attribute) => format!("{}:{attribute}", path.display()),
+                            None => path.display().to_string(),
+                        };
+
+                        if self.show {
+                            println!("{location}: {}", m.value.as_str());
+                        } else {
+                            println!("{location}: ********");
+                        }
+                    }
+                }
+                Err(e) => {
+                    failed += 1;
+                    eprintln!(
+                        "{:?}",
+                        e.wrap_err(format!("Failed to search `{}`", path.display()))
+                    );
+                }
+            }
+        }
+
+        if ctx.json() {
+            print_json(&serde_json::json!(json))?;
+        }
+
+        if failed > 0 {
+            Err(miette!("Failed to search {failed} record(s)"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Decrypts a single record and returns the values in it that match the pattern, `None` if the
+/// record is binary data.
+fn search(store: &Store, path: &Path, regex: &Regex) -> miette::Result<Option<Vec<Match>>> {
+    let Some(values) = store.get_record(path)?.decrypt_text_values(None)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(
+        values
+            .into_iter()
+            .filter_map(|(attribute, value)| {
+                let value = Zeroizing::new(value.scalar_to_string()?);
+                regex.is_match(&value).then_some(Match { attribute, value })
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn search_values() {
+        let dir = tempdir().unwrap();
+        let store = crate::utils::test::init_store(dir.path());
+
+        for (path, contents) in [
+            ("web.yaml", "user: alice\nkeys:\n  - hunter2\n  - abc\n"),
+            ("note", "hunter2 is my password"),
+        ] {
+            store
+                .create_record(Path::new(path))
+                .unwrap()
+                .encrypt_entire_file(contents.as_bytes().to_vec().into())
+                .unwrap();
+        }
+
+        let regex = Regex::new("hunter").unwrap();
+
+        let matches = search(&store, Path::new("web.yaml"), &regex)
+            .unwrap()
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].attribute.as_deref(), Some("keys/0"));
+        assert_eq!(*matches[0].value, "hunter2");
+
+        let matches = search(&store, Path::new("note"), &regex).unwrap().unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].attribute, None);
+        assert_eq!(*matches[0].value, "hunter2 is my password");
+
+        assert!(search(&store, Path::new("missing.yaml"), &regex).is_err());
+    }
+}
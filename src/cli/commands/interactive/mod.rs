@@ -1,27 +1,29 @@
 use crate::{
-    cli::Run,
-    secret_store::{Record, Store},
+    cli::{Context, Run},
+    secret_store::{Record, StoreConfig},
 };
 use clap::Parser;
 use inquire::{InquireError, Text};
 use miette::IntoDiagnostic;
 use skim::{SkimItem, SkimItemReceiver, SkimItemSender};
 use std::{borrow::Cow, sync::Arc};
-use std::{
-    io::Write,
-    path::{Path, PathBuf},
-};
+use std::{io::Write, path::PathBuf, time::Duration};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use zeroize::Zeroizing;
 
 /// Query the store interactively.
 #[derive(Debug, Parser)]
-pub(super) struct Command {}
+pub(super) struct Command {
+    /// Withdraw secrets from the clipboard after this many seconds (defaults to the clipboard
+    /// timeout in the store config)
+    #[arg(long, value_name = "SECS")]
+    timeout: Option<u64>,
+}
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        let store = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
 
         let records = store.list_records(None)?;
 
@@ -30,9 +32,14 @@ impl Run for Command {
             let attributes = record.list_attributes()?;
 
             'interactive_selection: loop {
-                let attribute = match pick_attribute(attributes.clone())? {
-                    Some(v) => v,
-                    None => break 'interactive_selection,
+                // Records without attributes are used as a whole
+                let attribute = if attributes.is_empty() {
+                    None
+                } else {
+                    match pick_attribute(attributes.clone())? {
+                        Some(v) => Some(v),
+                        None => break 'interactive_selection,
+                    }
                 };
 
                 let lookup = match pick_lookup_mode()? {
@@ -40,7 +47,15 @@ impl Run for Command {
                     None => break 'interactive_selection,
                 };
 
-                do_query(&record, attribute, lookup)?;
+                do_query(
+                    &record,
+                    attribute,
+                    lookup,
+                    self.timeout
+                        .or(store.config().clipboard.timeout)
+                        .map(Duration::from_secs),
+                    store.config(),
+                )?;
             }
         }
 
@@ -48,18 +63,24 @@ impl Run for Command {
     }
 }
 
-fn do_query(record: &Record, attribute: String, lookup: LookupMode) -> miette::Result<()> {
+fn do_query(
+    record: &Record,
+    attribute: Option<String>,
+    lookup: LookupMode,
+    timeout: Option<Duration>,
+    config: &StoreConfig,
+) -> miette::Result<()> {
     // Get the contents of the secret
-    let secret = record.decrypt_and_extract(Some(&attribute))?;
+    let secret = record.decrypt_and_extract(attribute.as_deref())?;
 
     // Apply any automatic transformations
-    let mut secret = crate::auto_transforms::process(secret)?;
+    let mut secret = crate::auto_transforms::process(secret, &config.auto_transforms)?;
 
     // Output it according to the specified mode
     let wait_for_user_ready = match lookup {
         LookupMode::Copy => {
             eprintln!("Waiting for paste...");
-            crate::utils::clipboard::copy(secret)?;
+            crate::utils::clipboard::copy(secret, timeout, &config.clipboard)?;
             false
         }
         LookupMode::QrCodeAscii => {
@@ -1,7 +1,7 @@
-use crate::{cli::Run, secret_store::Store};
+use crate::cli::{Context, Run, print_json};
 use clap::Parser;
 use clap_complete::ArgValueCompleter;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 /// List records in the store.
 #[derive(Debug, Parser)]
@@ -12,10 +12,21 @@ pub(super) struct Command {
 }
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        let store = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
 
-        for r in store.list_records(self.path.as_deref())? {
+        let records = store.list_records(self.path.as_deref())?;
+
+        if ctx.json() {
+            return print_json(&serde_json::json!(
+                records
+                    .iter()
+                    .map(|r| r.display().to_string())
+                    .collect::<Vec<_>>()
+            ));
+        }
+
+        for r in records {
             println!("{}", r.display());
         }
 
@@ -0,0 +1,54 @@
+use crate::cli::{Context, Run, print_json};
+use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use miette::miette;
+use std::path::PathBuf;
+
+/// Show the commits that changed a record, newest first.
+///
+/// The commit IDs can be given to `koishi get --rev` or `koishi restore --rev`.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Path to a record
+    #[arg(add = ArgValueCompleter::new(super::complete_record))]
+    path: PathBuf,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
+
+        // The record may have been deleted since
+        let entries = store.get_record_unchecked(&self.path)?.log()?;
+
+        if entries.is_empty() {
+            return Err(miette!("No history for `{}`", self.path.display()));
+        }
+
+        if ctx.json() {
+            return print_json(&serde_json::json!(
+                entries
+                    .iter()
+                    .map(|entry| serde_json::json!({
+                        "id": entry.id,
+                        "time": entry.time.to_string(),
+                        "summary": entry.summary,
+                    }))
+                    .collect::<Vec<_>>()
+            ));
+        }
+
+        let tz = jiff::tz::TimeZone::system();
+
+        for entry in entries {
+            println!(
+                "{} {} {}",
+                entry.id,
+                entry.time.to_zoned(tz.clone()).strftime("%Y-%m-%d %H:%M"),
+                entry.summary
+            );
+        }
+
+        Ok(())
+    }
+}
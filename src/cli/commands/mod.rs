@@ -1,19 +1,35 @@
+mod audit;
 mod config;
+mod copy;
 mod delete;
+mod diff;
+mod docker_credential;
 mod edit;
+mod exec;
+mod find;
+mod generate;
 mod get;
 mod git;
+mod git_credential;
+mod grep;
 mod init;
 mod interactive;
 mod list;
+mod log;
 mod r#move;
+mod new;
 mod otp;
 mod peek;
+mod render;
+mod restore;
 mod set;
+mod show;
 mod sops;
+mod stale;
+mod sync;
 mod update_keys;
 
-use super::Run;
+use super::{Context, Run};
 use crate::secret_store::Store;
 use clap::Subcommand;
 use clap_complete::CompletionCandidate;
@@ -30,13 +46,25 @@ pub(super) enum Command {
 
     #[clap(name = "ls")]
     List(list::Command),
+    Show(show::Command),
+    Find(find::Command),
+    Grep(grep::Command),
+    Audit(audit::Command),
+    Stale(stale::Command),
+    Log(log::Command),
+    Restore(restore::Command),
+    Diff(diff::Command),
     Peek(peek::Command),
     Edit(edit::Command),
+    New(new::Command),
     Set(set::Command),
+    Generate(generate::Command),
     Get(get::Command),
     Otp(otp::Command),
     #[clap(name = "mv")]
     Move(r#move::Command),
+    #[clap(name = "cp")]
+    Copy(copy::Command),
     #[clap(name = "rm")]
     Delete(delete::Command),
 
@@ -47,33 +75,75 @@ pub(super) enum Command {
     Interactive(interactive::Command),
 
     Git(git::Command),
+    GitCredential(git_credential::Command),
+    DockerCredential(docker_credential::Command),
+    Sync(sync::Command),
     Sops(sops::Command),
+    Exec(exec::Command),
+    Render(render::Command),
 }
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
         match self {
-            Command::Init(cmd) => cmd.run(store_path),
-            Command::Config(cmd) => cmd.run(store_path),
-            Command::List(cmd) => cmd.run(store_path),
-            Command::Peek(cmd) => cmd.run(store_path),
-            Command::Edit(cmd) => cmd.run(store_path),
-            Command::Set(cmd) => cmd.run(store_path),
-            Command::Get(cmd) => cmd.run(store_path),
-            Command::Otp(cmd) => cmd.run(store_path),
-            Command::Move(cmd) => cmd.run(store_path),
-            Command::Delete(cmd) => cmd.run(store_path),
-            Command::UpdateKeys(cmd) => cmd.run(store_path),
-            Command::Interactive(cmd) => cmd.run(store_path),
-            Command::Git(cmd) => cmd.run(store_path),
-            Command::Sops(cmd) => cmd.run(store_path),
+            Command::Init(cmd) => cmd.run(ctx),
+            Command::Config(cmd) => cmd.run(ctx),
+            Command::List(cmd) => cmd.run(ctx),
+            Command::Show(cmd) => cmd.run(ctx),
+            Command::Find(cmd) => cmd.run(ctx),
+            Command::Grep(cmd) => cmd.run(ctx),
+            Command::Audit(cmd) => cmd.run(ctx),
+            Command::Stale(cmd) => cmd.run(ctx),
+            Command::Log(cmd) => cmd.run(ctx),
+            Command::Restore(cmd) => cmd.run(ctx),
+            Command::Diff(cmd) => cmd.run(ctx),
+            Command::Peek(cmd) => cmd.run(ctx),
+            Command::Edit(cmd) => cmd.run(ctx),
+            Command::New(cmd) => cmd.run(ctx),
+            Command::Set(cmd) => cmd.run(ctx),
+            Command::Generate(cmd) => cmd.run(ctx),
+            Command::Get(cmd) => cmd.run(ctx),
+            Command::Otp(cmd) => cmd.run(ctx),
+            Command::Move(cmd) => cmd.run(ctx),
+            Command::Copy(cmd) => cmd.run(ctx),
+            Command::Delete(cmd) => cmd.run(ctx),
+            Command::UpdateKeys(cmd) => cmd.run(ctx),
+            Command::Interactive(cmd) => cmd.run(ctx),
+            Command::Git(cmd) => cmd.run(ctx),
+            Command::GitCredential(cmd) => cmd.run(ctx),
+            Command::DockerCredential(cmd) => cmd.run(ctx),
+            Command::Sync(cmd) => cmd.run(ctx),
+            Command::Sops(cmd) => cmd.run(ctx),
+            Command::Exec(cmd) => cmd.run(ctx),
+            Command::Render(cmd) => cmd.run(ctx),
         }
     }
 }
 
 fn complete_location(current: &OsStr) -> Vec<CompletionCandidate> {
-    let records = match super::get_store_location() {
-        Ok(store_path) => match Store::open(&store_path) {
+    // Paths given as `@store:path` are completed from that store
+    if let (Some(name), path) = super::stores::split_store_path(Path::new(current)) {
+        let records = match super::stores::UserConfig::load()
+            .and_then(|config| config.store_location(Some(name)))
+        {
+            Ok(store_path) => match Store::open(&store_path, true) {
+                Ok(store) => store.list_locations().unwrap_or(Vec::default()),
+                Err(_) => Vec::default(),
+            },
+            Err(_) => Vec::default(),
+        };
+
+        return do_complete(path.as_os_str(), records)
+            .into_iter()
+            .map(|c| {
+                let value = format!("@{name}:{}", c.get_value().to_string_lossy());
+                CompletionCandidate::new(value)
+            })
+            .collect();
+    }
+
+    let records = match super::selected_store_location() {
+        Ok(store_path) => match Store::open(&store_path, true) {
             Ok(store) => store.list_locations().unwrap_or(Vec::default()),
             Err(_) => Vec::default(),
         },
@@ -84,8 +154,8 @@ fn complete_location(current: &OsStr) -> Vec<CompletionCandidate> {
 }
 
 fn complete_record(current: &OsStr) -> Vec<CompletionCandidate> {
-    let records = match super::get_store_location() {
-        Ok(store_path) => match Store::open(&store_path) {
+    let records = match super::selected_store_location() {
+        Ok(store_path) => match Store::open(&store_path, true) {
             Ok(store) => store.list_records(None).unwrap_or(Vec::default()),
             Err(_) => Vec::default(),
         },
@@ -95,6 +165,24 @@ fn complete_record(current: &OsStr) -> Vec<CompletionCandidate> {
     do_complete(current, records)
 }
 
+fn complete_template(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_str().unwrap_or("");
+
+    let templates = match super::selected_store_location() {
+        Ok(store_path) => match Store::open(&store_path, true) {
+            Ok(store) => store.list_templates().unwrap_or(Vec::default()),
+            Err(_) => Vec::default(),
+        },
+        Err(_) => Vec::default(),
+    };
+
+    templates
+        .into_iter()
+        .filter(|t| t.starts_with(current))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
 fn do_complete(current: &OsStr, options: Vec<PathBuf>) -> Vec<CompletionCandidate> {
     let current = current.to_str().unwrap_or("");
 
@@ -1,9 +1,13 @@
-use crate::{cli::Run, secret_store::Store};
+use crate::cli::{Context, Run};
 use clap::Parser;
 use clap_complete::ArgValueCompleter;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 /// Moves/renames a directory or record.
+///
+/// Either path may be given as `@store:path` to refer to a path in another of the stores in the
+/// user config. Records moved to another store are decrypted in memory and re-encrypted using the
+/// creation rules of that store, with a commit in each store.
 #[derive(Debug, Parser)]
 pub(super) struct Command {
     /// Path to the source directory/record
@@ -16,11 +20,12 @@ pub(super) struct Command {
 }
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        let store = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let (source_store, source) = ctx.open_store_for(&self.source)?;
+        let (destination_store, destination) = ctx.open_store_for(&self.destination)?;
 
-        let source = store.location(&self.source);
-        let destination = store.location(&self.destination);
+        let source = source_store.location(source);
+        let destination = destination_store.location(destination);
 
         source.move_to(destination)?;
 
@@ -0,0 +1,100 @@
+use crate::{
+    cli::{Context, Run},
+    secret_store::Template,
+    utils::document::{Format, Value},
+};
+use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use inquire::validator::ValueRequiredValidator;
+use miette::{IntoDiagnostic, miette};
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+/// Create a new record from a template.
+///
+/// Templates are kept in `.koishi/templates` in the store. The value of each field in the template
+/// is either generated or prompted for, then the record is encrypted in one go.
+/// Paths without a file extension are created as YAML records (or JSON if that is the default
+/// record format).
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Name of the template to use
+    #[arg(short, long, add = ArgValueCompleter::new(super::complete_template))]
+    template: String,
+
+    /// Path to the new record
+    path: PathBuf,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
+
+        let template = store.template(&self.template)?;
+
+        let path = match store.with_default_extension(&self.path) {
+            Some(path) if matches!(Format::from_path(&path), Format::Yaml | Format::Json) => path,
+            // Templates only fill in mappings, so other default formats fall back to YAML
+            Some(_) => self.path.with_extension("yaml"),
+            None if self.path.extension().is_none() => self.path.with_extension("yaml"),
+            None => self.path.clone(),
+        };
+
+        let format = Format::from_path(&path);
+        if !matches!(format, Format::Yaml | Format::Json) {
+            return Err(miette!(
+                "Records created from templates must be YAML or JSON, not `{}`",
+                path.display()
+            ));
+        }
+
+        let record = store.create_record(&path)?;
+
+        let document = fill(&template)?;
+        let contents = Zeroizing::new(document.emit(format)?);
+
+        record.encrypt_entire_file(contents.as_bytes().to_vec().into())?;
+
+        if path != self.path {
+            eprintln!("Created `{}`", path.display());
+        }
+
+        Ok(())
+    }
+}
+
+/// Fills in each field of a template, generating or prompting for its value.
+fn fill(template: &Template) -> miette::Result<Zeroizing<Value>> {
+    let mut entries = Vec::new();
+
+    for field in &template.fields {
+        let value = match &field.generate {
+            Some(generate) => generate.generate()?,
+            None if field.secret => {
+                let mut prompt = inquire::Password::new(&field.prompt)
+                    .with_display_toggle_enabled()
+                    .without_confirmation();
+                if !field.optional {
+                    prompt = prompt.with_validator(ValueRequiredValidator::default());
+                }
+                Zeroizing::new(prompt.prompt().into_diagnostic()?)
+            }
+            None => {
+                let mut prompt = inquire::Text::new(&field.prompt);
+                if let Some(default) = &field.default {
+                    prompt = prompt.with_default(default);
+                }
+                if !field.optional {
+                    prompt = prompt.with_validator(ValueRequiredValidator::default());
+                }
+                Zeroizing::new(prompt.prompt().into_diagnostic()?)
+            }
+        };
+
+        if !value.is_empty() {
+            entries.push((field.name.clone(), Value::String(value.to_string())));
+        }
+    }
+
+    Ok(Zeroizing::new(Value::Mapping(entries)))
+}
@@ -1,14 +1,14 @@
-use crate::{cli::Run, secret_store::Store};
+use crate::cli::{Context, Run, print_json};
 use clap::Parser;
 use clap_complete::ArgValueCompleter;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 /// Generate TOTP codes from records.
 #[derive(Debug, Parser)]
 pub(super) struct Command {
-    /// Part of the record that contains the OTP URL
-    #[arg(long, default_value = "otp")]
-    otp_selector: String,
+    /// Part of the record that contains the OTP URL (defaults to `otp_selector` in the store config)
+    #[arg(long)]
+    otp_selector: Option<String>,
 
     /// Path to a record
     #[arg(add = ArgValueCompleter::new(super::complete_record))]
@@ -16,16 +16,24 @@ pub(super) struct Command {
 }
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        let store = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
 
         let record = store.get_record(&self.path)?;
 
-        let otp_key = record.decrypt_and_extract(Some(self.otp_selector.as_str()))?;
+        let otp_selector = self
+            .otp_selector
+            .as_deref()
+            .unwrap_or(&store.config().otp_selector);
+        let otp_key = record.decrypt_and_extract(Some(otp_selector))?;
         let otp_key = crate::utils::bytes_to_string(otp_key)?;
 
         let otp_pass = crate::utils::totp_from_otpauth(otp_key)?;
 
+        if ctx.json() {
+            return print_json(&serde_json::json!({ "code": otp_pass.as_str() }));
+        }
+
         println!("{}", *otp_pass);
 
         Ok(())
@@ -1,9 +1,9 @@
-use crate::{cli::Run, secret_store::Store};
+use crate::cli::{Context, Run};
 use clap::Parser;
 use clap_complete::ArgValueCompleter;
 use miette::IntoDiagnostic;
 use std::io::{IsTerminal, Write};
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 /// Show the SOPS encrypted contents of a record.
 #[derive(Debug, Parser)]
@@ -14,8 +14,8 @@ pub(super) struct Command {
 }
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        let store = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
 
         let record = store.get_record(&self.path)?;
 
@@ -0,0 +1,84 @@
+use crate::{
+    cli::{Context, Run},
+    utils::render::Reference,
+};
+use clap::Parser;
+use miette::{IntoDiagnostic, WrapErr, miette};
+use std::{io::Write, path::PathBuf};
+use zeroize::Zeroizing;
+
+/// Render a template, filling in secrets from the store.
+///
+/// Placeholders in the template look like `{{ koishi "db/prod" "password" }}`, where the second
+/// string is a selector that works the same as for `get`. It can only be left out for records
+/// that hold a single value, such as binary records.
+///
+/// Rendering fails if any record or part of a record is missing, nothing is written in that case.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Write the output to this file instead of stdout, only the owner can read it
+    #[arg(long = "out", short = 'o', value_name = "FILE")]
+    out: Option<PathBuf>,
+
+    /// Template to render
+    template: PathBuf,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let template = std::fs::read_to_string(&self.template)
+            .into_diagnostic()
+            .wrap_err(format!("Failed to read `{}`", self.template.display()))?;
+
+        let store = ctx.open_store()?;
+
+        let output = crate::utils::render::render(&template, |reference: &Reference| {
+            let value = store
+                .get_record(reference.path.as_ref())?
+                .decrypt_document(None, reference.selector.as_deref())?;
+
+            let value = value.scalar_to_string().ok_or_else(|| {
+                miette!(
+                    "`{}` is not a single value",
+                    reference.selector.as_deref().unwrap_or(&reference.path)
+                )
+            })?;
+
+            Ok(Zeroizing::new(value))
+        })
+        .wrap_err(format!("Failed to render `{}`", self.template.display()))?;
+
+        match &self.out {
+            Some(out) => write_private(out, output.as_bytes())
+                .wrap_err(format!("Failed to write `{}`", out.display())),
+            None => std::io::stdout()
+                .write_all(output.as_bytes())
+                .into_diagnostic(),
+        }
+    }
+}
+
+/// Writes a file that only its owner can read.
+fn write_private(path: &std::path::Path, contents: &[u8]) -> miette::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    let _ = options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        let _ = options.mode(0o600);
+
+        // The mode is only used for new files, so an existing file is restricted first
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+                .into_diagnostic()?;
+        }
+    }
+
+    options
+        .open(path)
+        .into_diagnostic()?
+        .write_all(contents)
+        .into_diagnostic()
+}
@@ -0,0 +1,34 @@
+use crate::cli::{Context, Run};
+use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use std::path::PathBuf;
+
+/// Restore a record to how it was at a previous commit.
+///
+/// The old version is committed as a new change, so no history is lost.
+/// Records that have since been deleted can also be restored.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Git revision to restore the record from (e.g. a commit ID shown by `koishi log`)
+    #[arg(long)]
+    rev: String,
+
+    /// Path to a record
+    #[arg(add = ArgValueCompleter::new(super::complete_record))]
+    path: PathBuf,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
+
+        // The record may no longer exist
+        let record = store.get_record_unchecked(&self.path)?;
+
+        if !record.restore(&self.rev)? {
+            eprintln!("No changes.");
+        }
+
+        Ok(())
+    }
+}
@@ -1,10 +1,10 @@
-use crate::{cli::Run, secret_store::Store};
+use crate::cli::{Context, Run};
 use clap::Parser;
 use clap_complete::ArgValueCompleter;
 use miette::IntoDiagnostic;
 use std::{
     io::{IsTerminal, Read},
-    path::{Path, PathBuf},
+    path::PathBuf,
 };
 use zeroize::Zeroizing;
 
@@ -20,8 +20,8 @@ pub(super) struct Command {
 }
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        let store = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
 
         let record = if self.selector.is_some() {
             // Need an existing secret when using a selector
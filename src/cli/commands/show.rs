@@ -0,0 +1,127 @@
+use crate::{
+    cli::{Context, Run, print_json},
+    utils::tree::Tree,
+};
+use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use std::path::PathBuf;
+
+/// Show records in the store as a tree.
+///
+/// Annotations are read from the encrypted records and the Git history, nothing is decrypted.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Maximum depth of directories to show
+    #[arg(short = 'L', long)]
+    depth: Option<usize>,
+
+    /// Show the number of attributes in each record
+    #[arg(short, long)]
+    attributes: bool,
+
+    /// Show the date each record was last modified
+    #[arg(short, long)]
+    modified: bool,
+
+    /// Show the keys each record is encrypted for
+    #[arg(short, long)]
+    recipients: bool,
+
+    /// Path to show
+    #[arg(add = ArgValueCompleter::new(super::complete_location))]
+    path: Option<PathBuf>,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
+
+        let records = store.list_records(self.path.as_deref())?;
+
+        let modified = if self.modified {
+            crate::utils::git::last_modified(store.root(), &records)?
+        } else {
+            Default::default()
+        };
+
+        let mut tree = Tree::default();
+        let mut json = Vec::new();
+
+        for path in &records {
+            let mut annotations = Vec::new();
+            let mut object = serde_json::Map::new();
+            let _ = object.insert("record".into(), path.display().to_string().into());
+
+            if self.attributes {
+                let record = store.get_record(path)?;
+                let attributes = record.list_attributes().ok();
+                let _ = object.insert(
+                    "attributes".into(),
+                    serde_json::json!(attributes.as_ref().map(Vec::len)),
+                );
+                match attributes {
+                    // Records that aren't YAML or JSON are a single value
+                    Some(attributes) if attributes.is_empty() => {}
+                    Some(attributes) if attributes.len() == 1 => {
+                        annotations.push("1 attribute".into());
+                    }
+                    Some(attributes) => {
+                        annotations.push(format!("{} attributes", attributes.len()));
+                    }
+                    None => annotations.push("unknown attributes".into()),
+                }
+            }
+
+            if self.modified {
+                let _ = object.insert(
+                    "modified".into(),
+                    serde_json::json!(modified.get(path).map(ToString::to_string)),
+                );
+                annotations.push(match modified.get(path) {
+                    Some(timestamp) => format!(
+                        "modified {}",
+                        timestamp
+                            .to_zoned(jiff::tz::TimeZone::system())
+                            .strftime("%Y-%m-%d")
+                    ),
+                    None => "uncommitted".into(),
+                });
+            }
+
+            if self.recipients {
+                let recipients = crate::utils::sops::recipients(&store.root().join(path)).ok();
+                let _ = object.insert("recipients".into(), serde_json::json!(recipients));
+                if let Some(recipients) = recipients {
+                    annotations.push(recipients.join(" "));
+                }
+            }
+
+            json.push(serde_json::Value::Object(object));
+
+            // Show paths relative to the requested path
+            let path = match &self.path {
+                Some(base) => path
+                    .strip_prefix(base)
+                    .ok()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .unwrap_or(path.as_path()),
+                None => path.as_path(),
+            };
+
+            tree.insert(path, annotations);
+        }
+
+        if ctx.json() {
+            return print_json(&serde_json::Value::Array(json));
+        }
+
+        let label = match &self.path {
+            Some(path) => path.display().to_string(),
+            None => store.root().display().to_string(),
+        };
+
+        print!("{}", tree.render(&label, self.depth));
+
+        Ok(())
+    }
+}
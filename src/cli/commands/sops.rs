@@ -1,6 +1,5 @@
-use crate::{cli::Run, secret_store::Store};
+use crate::cli::{Context, Run};
 use clap::Parser;
-use std::path::Path;
 
 /// Run SOPS commands from the root of the store.
 #[derive(Debug, Parser)]
@@ -9,16 +8,24 @@ pub(super) struct Command {
 }
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        let store = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
 
-        let _ = crate::utils::git::git_operation(
-            store.root(),
+        let _ = store.git_operation(
             &format!("Perform SOPS command: `sops {}`", self.args.join(" ")),
             || {
+                // SOPS may touch any file, so commit whatever it changed that was not already
+                // changed beforehand
+                let dirty = crate::utils::git::dirty_paths(store.root())?;
+
                 crate::utils::sops::interactive_command(store.root(), |cmd| {
                     let _ = cmd.args(&self.args);
-                })
+                })?;
+
+                Ok(crate::utils::git::dirty_paths(store.root())?
+                    .into_iter()
+                    .filter(|path| !dirty.contains(path))
+                    .collect())
             },
         )?;
 
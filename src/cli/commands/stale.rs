@@ -0,0 +1,75 @@
+use crate::cli::{Context, Run, print_json};
+use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use miette::IntoDiagnostic;
+use std::path::PathBuf;
+
+/// List attributes that have not changed for some time and are due for rotation.
+///
+/// The time each attribute last changed is found from the Git history of the store by comparing
+/// its ciphertext between commits, nothing is decrypted. Changes that have not been committed are
+/// not taken into account.
+///
+/// Writing a record with the SOPS executable (including when koishi falls back to it) re-encrypts
+/// every value in it, which counts as a change to all of its attributes.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// List attributes that have not changed for at least this long (e.g. `90d`, `6mo` or `1y`)
+    #[arg(long, default_value = "180d")]
+    older_than: jiff::Span,
+
+    /// Path under which to look for stale attributes
+    #[arg(add = ArgValueCompleter::new(super::complete_location))]
+    path: Option<PathBuf>,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
+
+        let now = jiff::Zoned::now();
+        let cutoff = now.checked_sub(self.older_than).into_diagnostic()?;
+
+        let records = store.list_records(self.path.as_deref())?;
+        let modified = crate::utils::git::last_modified_parts(
+            store.root(),
+            &records,
+            crate::utils::sops::ciphertexts,
+        )?;
+
+        let mut stale: Vec<_> = records
+            .iter()
+            .filter_map(|path| Some((path, modified.get(path)?)))
+            .flat_map(|(path, attributes)| {
+                attributes
+                    .iter()
+                    .filter(|(_, time)| **time < cutoff.timestamp())
+                    .map(move |(attribute, time)| (*time, path, attribute))
+            })
+            .collect();
+        stale.sort();
+
+        if ctx.json() {
+            return print_json(&serde_json::json!(
+                stale
+                    .iter()
+                    .map(|(time, path, attribute)| serde_json::json!({
+                        "record": path.display().to_string(),
+                        "attribute": attribute,
+                        "last_changed": time.to_string(),
+                    }))
+                    .collect::<Vec<_>>()
+            ));
+        }
+
+        for (time, path, attribute) in stale {
+            println!(
+                "{}:{attribute} (last changed {})",
+                path.display(),
+                time.to_zoned(now.time_zone().clone()).strftime("%Y-%m-%d")
+            );
+        }
+
+        Ok(())
+    }
+}
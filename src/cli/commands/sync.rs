@@ -0,0 +1,55 @@
+use crate::{
+    cli::{Context, Run},
+    secret_store::{Store, SyncStrategy},
+};
+use clap::Parser;
+
+/// Synchronise the store with its Git remote.
+///
+/// Changes are fetched from the upstream of the current branch and merged in (or local commits are
+/// rebased onto them), then local commits are pushed.
+/// When the same record was changed on both sides, both versions are decrypted and merged
+/// attribute by attribute. Only changes to the same attribute on both sides cannot be merged, in
+/// which case nothing is changed.
+///
+/// The `git` executable must be on the `PATH`, it is used to talk to the remote.
+#[derive(Debug, Parser)]
+pub(super) struct Command {
+    /// Rebase local commits onto the remote changes instead of merging them
+    #[arg(long)]
+    rebase: bool,
+
+    /// Do not push local commits to the remote
+    #[arg(long)]
+    no_push: bool,
+}
+
+impl Run for Command {
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        // The store is opened offline so that the chosen strategy is used for pulling
+        let store = Store::open(ctx.store_path(), true)?;
+
+        let strategy = if self.rebase {
+            SyncStrategy::Rebase
+        } else {
+            SyncStrategy::Merge
+        };
+
+        let summary = store.sync(strategy, !self.no_push)?;
+
+        for path in &summary.merged {
+            eprintln!("Merged changes to `{}`", path.display());
+        }
+
+        if summary.pulled == 0 && summary.pushed == 0 {
+            eprintln!("Already up to date.");
+        } else {
+            eprintln!(
+                "Pulled {} commit(s), pushed {} commit(s).",
+                summary.pulled, summary.pushed
+            );
+        }
+
+        Ok(())
+    }
+}
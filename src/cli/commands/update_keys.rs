@@ -1,6 +1,6 @@
-use crate::{cli::Run, secret_store::Store};
+use crate::cli::{Context, Run};
 use clap::Parser;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 /// Re-encrypt secrets under a given path.
 ///
@@ -16,14 +16,13 @@ pub(super) struct Command {
 }
 
 impl Run for Command {
-    fn run(&self, store_path: &Path) -> miette::Result<()> {
-        let store = Store::open(store_path)?;
+    fn run(&self, ctx: &Context) -> miette::Result<()> {
+        let store = ctx.open_store()?;
 
         // Get a list of records under the specified path
         let records = store.list_records(self.path.as_deref())?;
 
-        let _ = crate::utils::git::git_operation(
-            store.root(),
+        let _ = store.git_operation(
             &match &self.path {
                 Some(path) => format!("Update keys for records in `{}`", path.display()),
                 None => "Update keys for all records".into(),
@@ -32,7 +31,7 @@ impl Run for Command {
                 for record in &records {
                     crate::utils::sops::update_keys(store.root(), record, self.yes)?;
                 }
-                Ok(())
+                Ok(records.clone())
             },
         )?;
 
@@ -1,51 +1,165 @@
 mod commands;
+mod stores;
 
-use clap::{CommandFactory, Parser};
+use crate::secret_store::Store;
+use clap::{CommandFactory, Parser, ValueEnum};
 use clap_complete::CompleteEnv;
+use clap_complete::{ArgValueCompleter, CompletionCandidate};
 use commands::Command;
-use miette::{Context, IntoDiagnostic};
+use miette::{IntoDiagnostic, WrapErr};
 use std::{
-    env::VarError,
+    ffi::OsStr,
     path::{Path, PathBuf},
 };
+use stores::UserConfig;
 
-fn get_store_location() -> miette::Result<PathBuf> {
-    const DEFAULT_STORE_LOCATION: &str = "$XDG_DATA_HOME/koishi-store";
+/// Finds the location of the store selected on the command line, without parsing it.
+///
+/// Used during completion so that candidates come from the store that will be used.
+fn selected_store_location() -> miette::Result<PathBuf> {
+    let config = UserConfig::load()?;
+    config.store_location(stores::store_arg(std::env::args()).as_deref())
+}
 
-    let location = match std::env::var("KOISHI_STORE") {
-        Ok(val) => val,
-        Err(VarError::NotPresent) => DEFAULT_STORE_LOCATION.into(),
-        Err(VarError::NotUnicode(s)) => {
-            return Err(miette::miette!(
-                "Failed to read KOISHI_STORE environment variable: {s:?}"
-            ));
-        }
-    };
+/// How commands present their results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Plain text, as read by a human
+    Text,
+    /// JSON, for scripts (errors are also reported as JSON)
+    Json,
+}
+
+/// Options that apply to every command.
+#[derive(Debug)]
+struct Context {
+    config: UserConfig,
+    store_path: PathBuf,
+    offline: bool,
+    output: OutputFormat,
+}
+
+impl Context {
+    fn json(&self) -> bool {
+        self.output == OutputFormat::Json
+    }
+
+    fn store_path(&self) -> &Path {
+        &self.store_path
+    }
+
+    /// Opens the store, pulling from its remote first unless running offline.
+    fn open_store(&self) -> miette::Result<Store> {
+        Store::open(&self.store_path, self.offline)
+    }
 
-    Ok(shellexpand::path::full(&location)
-        .into_diagnostic()
-        .wrap_err("Failed to perform shell expansion on store path")?
-        .into_owned())
+    /// Opens the store that a path given as `@store:path` is in, returning it along with the path
+    /// within it.
+    ///
+    /// Paths without a store prefix are in the selected store.
+    fn open_store_for<'a>(&self, path: &'a Path) -> miette::Result<(Store, &'a Path)> {
+        match stores::split_store_path(path) {
+            (Some(name), path) => {
+                let store_path = self.config.store_location(Some(name))?;
+                Ok((Store::open(&store_path, self.offline)?, path))
+            }
+            (None, path) => Ok((self.open_store()?, path)),
+        }
+    }
 }
 
 trait Run {
-    fn run(&self, store_path: &Path) -> miette::Result<()>;
+    fn run(&self, ctx: &Context) -> miette::Result<()>;
 }
 
 /// Koishi: the keeper of important secrets, hierarchically indexed.
 #[derive(Debug, Parser)]
 #[command(name = "koishi", author, version = self::version(), about, long_about = None)]
 struct Cli {
+    /// Name of the store to use, from the user config
+    #[arg(long, global = true, value_name = "NAME", add = ArgValueCompleter::new(complete_store_name))]
+    store: Option<String>,
+
+    /// Format of the output
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Never pull from or push to the store's remote, even if auto-push is enabled
+    #[arg(long, global = true, env = "KOISHI_OFFLINE")]
+    offline: bool,
+
     #[command(subcommand)]
     command: Command,
 }
 
 pub(super) fn main() -> miette::Result<()> {
     CompleteEnv::with_factory(Cli::command).complete();
-    let cli = Cli::parse();
+    let cli = parse_args();
+
+    if cli.output == OutputFormat::Json {
+        miette::set_hook(Box::new(|_| Box::new(miette::JSONReportHandler::new())))?;
 
-    let store_path = get_store_location().wrap_err("Failed to determine store location")?;
-    cli.command.run(&store_path)
+        // Returning the error would prefix it with `Error: `, which isn't valid JSON
+        if let Err(e) = run(cli) {
+            eprintln!("{e:?}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    run(cli)
+}
+
+/// Name that Docker runs the credential helper by, when it is configured with `"credsStore": "koishi"`.
+const DOCKER_CREDENTIAL_HELPER: &str = "docker-credential-koishi";
+
+/// Parses the command line, treating `docker-credential-koishi <operation>` as
+/// `koishi docker-credential <operation>` so that koishi can be linked to by that name.
+fn parse_args() -> Cli {
+    let mut args = std::env::args_os();
+    let argv0 = args.next().unwrap_or_default();
+
+    if Path::new(&argv0).file_stem() == Some(OsStr::new(DOCKER_CREDENTIAL_HELPER)) {
+        Cli::parse_from(
+            ["koishi".into(), "docker-credential".into()]
+                .into_iter()
+                .chain(args),
+        )
+    } else {
+        Cli::parse()
+    }
+}
+
+fn run(cli: Cli) -> miette::Result<()> {
+    let config = UserConfig::load().wrap_err("Failed to load user config")?;
+    let ctx = Context {
+        store_path: config
+            .store_location(cli.store.as_deref())
+            .wrap_err("Failed to determine store location")?,
+        config,
+        offline: cli.offline,
+        output: cli.output,
+    };
+    cli.command.run(&ctx)
+}
+
+/// Prints a value as JSON, for commands run with `--output json`.
+fn print_json(value: &serde_json::Value) -> miette::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value).into_diagnostic()?);
+    Ok(())
+}
+
+fn complete_store_name(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_str().unwrap_or("");
+
+    match UserConfig::load() {
+        Ok(config) => config
+            .store_names()
+            .filter(|name| name.starts_with(current))
+            .map(CompletionCandidate::new)
+            .collect(),
+        Err(_) => Vec::default(),
+    }
 }
 
 fn version() -> String {
@@ -0,0 +1,210 @@
+use miette::{IntoDiagnostic, WrapErr, miette};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env::VarError,
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_CONFIG_LOCATION: &str = "$XDG_CONFIG_HOME/koishi/config.toml";
+const FALLBACK_CONFIG_LOCATION: &str = "~/.config/koishi/config.toml";
+const DEFAULT_STORE_LOCATION: &str = "$XDG_DATA_HOME/koishi-store";
+
+/// The user's own config, listing the stores they use by name.
+///
+/// Read from `KOISHI_CONFIG` if it is set, otherwise from `$XDG_CONFIG_HOME/koishi/config.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct UserConfig {
+    /// Name of the store that is used when none is selected
+    default_store: Option<String>,
+    /// Stores by name
+    stores: BTreeMap<String, StoreEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StoreEntry {
+    path: String,
+}
+
+impl UserConfig {
+    /// Loads the user config, using an empty config if there is no config file.
+    pub(super) fn load() -> miette::Result<Self> {
+        let filename = match std::env::var("KOISHI_CONFIG") {
+            Ok(val) => expand(&val)?,
+            Err(VarError::NotPresent) => {
+                expand(DEFAULT_CONFIG_LOCATION).or_else(|_| expand(FALLBACK_CONFIG_LOCATION))?
+            }
+            Err(VarError::NotUnicode(s)) => {
+                return Err(miette!(
+                    "Failed to read KOISHI_CONFIG environment variable: {s:?}"
+                ));
+            }
+        };
+
+        if !filename.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(&filename)
+            .into_diagnostic()
+            .wrap_err(format!("Failed to read `{}`", filename.display()))?;
+
+        Self::parse(&contents).wrap_err(format!("Invalid config in `{}`", filename.display()))
+    }
+
+    fn parse(contents: &str) -> miette::Result<Self> {
+        let config: Self = toml::from_str(contents).into_diagnostic()?;
+
+        if let Some(name) = &config.default_store {
+            if !config.stores.contains_key(name) {
+                return Err(miette!("Default store `{name}` is not one of the stores"));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Names of all configured stores.
+    pub(super) fn store_names(&self) -> impl Iterator<Item = &str> {
+        self.stores.keys().map(String::as_str)
+    }
+
+    /// Finds the location of the store to use.
+    ///
+    /// A store selected by name is used first, followed by `KOISHI_STORE`, then the default store
+    /// from the config and finally `$XDG_DATA_HOME/koishi-store`.
+    pub(super) fn store_location(&self, name: Option<&str>) -> miette::Result<PathBuf> {
+        if let Some(name) = name {
+            return self.named_store_location(name);
+        }
+
+        match std::env::var("KOISHI_STORE") {
+            Ok(val) => return expand(&val),
+            Err(VarError::NotPresent) => {}
+            Err(VarError::NotUnicode(s)) => {
+                return Err(miette!(
+                    "Failed to read KOISHI_STORE environment variable: {s:?}"
+                ));
+            }
+        }
+
+        match &self.default_store {
+            Some(name) => self.named_store_location(name),
+            None => expand(DEFAULT_STORE_LOCATION),
+        }
+    }
+
+    fn named_store_location(&self, name: &str) -> miette::Result<PathBuf> {
+        let store = self.stores.get(name).ok_or_else(|| {
+            miette!(
+                "No store named `{name}`, expected one of: {}",
+                self.store_names().collect::<Vec<_>>().join(", ")
+            )
+        })?;
+
+        expand(&store.path)
+    }
+}
+
+/// Splits a path given as `@store:path` into the name of the store and the path within it.
+///
+/// Paths without a store prefix are returned as they are.
+pub(super) fn split_store_path(path: &Path) -> (Option<&str>, &Path) {
+    path.to_str()
+        .and_then(|p| p.strip_prefix('@'))
+        .and_then(|p| p.split_once(':'))
+        .map_or((None, path), |(name, path)| (Some(name), Path::new(path)))
+}
+
+/// Finds the value of `--store` in a list of command line arguments.
+///
+/// Used during completion, when the arguments have not been parsed.
+pub(super) fn store_arg<I: IntoIterator<Item = String>>(args: I) -> Option<String> {
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--store" {
+            return args.next();
+        } else if let Some(name) = arg.strip_prefix("--store=") {
+            return Some(name.to_owned());
+        }
+    }
+
+    None
+}
+
+fn expand(location: &str) -> miette::Result<PathBuf> {
+    Ok(shellexpand::path::full(location)
+        .into_diagnostic()
+        .wrap_err(format!("Failed to perform shell expansion on `{location}`"))?
+        .into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_stores() {
+        let config = UserConfig::parse(
+            r#"
+default_store = "personal"
+
+[stores.personal]
+path = "/stores/personal"
+
+[stores.team]
+path = "/stores/team"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.store_names().collect::<Vec<_>>(),
+            vec!["personal", "team"]
+        );
+        assert_eq!(
+            config.store_location(Some("team")).unwrap(),
+            PathBuf::from("/stores/team")
+        );
+        assert!(config.store_location(Some("other")).is_err());
+    }
+
+    #[test]
+    fn unknown_default_store() {
+        assert!(UserConfig::parse("default_store = \"team\"").is_err());
+    }
+
+    #[test]
+    fn split_store_paths() {
+        assert_eq!(
+            split_store_path(Path::new("@team:web/site")),
+            (Some("team"), Path::new("web/site"))
+        );
+        assert_eq!(
+            split_store_path(Path::new("web/site")),
+            (None, Path::new("web/site"))
+        );
+        assert_eq!(
+            split_store_path(Path::new("@web/site")),
+            (None, Path::new("@web/site"))
+        );
+    }
+
+    #[test]
+    fn store_args() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            store_arg(args(&["koishi", "--store", "team", "get"])),
+            Some("team".into())
+        );
+        assert_eq!(
+            store_arg(args(&["koishi", "get", "--store=team"])),
+            Some("team".into())
+        );
+        assert_eq!(store_arg(args(&["koishi", "get"])), None);
+    }
+}
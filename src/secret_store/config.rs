@@ -0,0 +1,120 @@
+use crate::{
+    auto_transforms,
+    utils::{clipboard, document::Format},
+};
+use miette::{Context, IntoDiagnostic, miette};
+use serde::Deserialize;
+use std::path::Path;
+
+pub(super) const CONFIG_FILENAME: &str = ".koishi.toml";
+
+/// Settings for koishi itself, read from `.koishi.toml` in the root of the store.
+///
+/// The file is optional and every setting in it has a default, so a store without one behaves as
+/// it always has.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct StoreConfig {
+    /// Part of a record that contains the OTP URL, when none is given to `otp`
+    pub(crate) otp_selector: String,
+    /// Push every commit to the remote and fast-forward to changes from it when the store is
+    /// opened, using the `git` executable on the `PATH`
+    pub(crate) auto_push: bool,
+    /// Format of new records whose path has no file extension
+    pub(crate) default_format: Format,
+    /// Automatic transformations that are applied to retrieved secrets
+    pub(crate) auto_transforms: Vec<auto_transforms::Kind>,
+    pub(crate) clipboard: clipboard::Config,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self {
+            otp_selector: "otp".into(),
+            auto_push: false,
+            default_format: Format::Binary,
+            auto_transforms: auto_transforms::Kind::ALL.to_vec(),
+            clipboard: clipboard::Config::default(),
+        }
+    }
+}
+
+impl StoreConfig {
+    /// Loads the config from the root of a store, using the defaults if there is no config file.
+    pub(super) fn load(root: &Path) -> miette::Result<Self> {
+        let filename = root.join(CONFIG_FILENAME);
+
+        if !filename.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(&filename)
+            .into_diagnostic()
+            .wrap_err(format!("Failed to read `{}`", filename.display()))?;
+
+        Self::parse(&contents).wrap_err(format!("Invalid config in `{}`", filename.display()))
+    }
+
+    fn parse(contents: &str) -> miette::Result<Self> {
+        let config: Self = toml::from_str(contents).into_diagnostic()?;
+
+        if config.otp_selector.is_empty() {
+            return Err(miette!("`otp_selector` must not be empty"));
+        }
+
+        config.clipboard.validate()?;
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let config = StoreConfig::parse("").unwrap();
+        assert_eq!(config.otp_selector, "otp");
+        assert!(!config.auto_push);
+        assert_eq!(config.default_format, Format::Binary);
+        assert_eq!(config.auto_transforms, auto_transforms::Kind::ALL);
+        assert_eq!(config.clipboard.timeout, None);
+    }
+
+    #[test]
+    fn full() {
+        let config = StoreConfig::parse(
+            r#"
+otp_selector = "totp"
+auto_push = true
+default_format = "yaml"
+auto_transforms = []
+
+[clipboard]
+backend = "command"
+command = "xclip -selection clipboard"
+timeout = 30
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.otp_selector, "totp");
+        assert!(config.auto_push);
+        assert_eq!(config.default_format, Format::Yaml);
+        assert!(config.auto_transforms.is_empty());
+        assert_eq!(
+            config.clipboard.backend,
+            Some(clipboard::BackendKind::Command)
+        );
+        assert_eq!(config.clipboard.timeout, Some(30));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(StoreConfig::parse("unknown = true").is_err());
+        assert!(StoreConfig::parse("default_format = \"xml\"").is_err());
+        assert!(StoreConfig::parse("auto_transforms = [\"rot13\"]").is_err());
+        assert!(StoreConfig::parse("[clipboard]\nbackend = \"command\"").is_err());
+        assert!(StoreConfig::parse("otp_selector = \"\"").is_err());
+    }
+}
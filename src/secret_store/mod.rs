@@ -1,5 +1,11 @@
+mod config;
 mod record;
-pub(crate) use record::Record;
+mod sync;
+mod template;
+pub(crate) use config::StoreConfig;
+pub(crate) use record::{Record, Values};
+pub(crate) use sync::SyncStrategy;
+pub(crate) use template::Template;
 
 use crate::utils::git::GitOperationResult;
 use miette::{Context, IntoDiagnostic, miette};
@@ -22,6 +28,9 @@ creation_rules:
 #[derive(Debug)]
 pub(crate) struct Store {
     root: PathBuf,
+    config: StoreConfig,
+    /// Whether commits are pushed to the remote as soon as they are made
+    auto_push: bool,
 }
 
 impl Store {
@@ -45,41 +54,125 @@ impl Store {
         let _ = crate::utils::git::git_operation(root, "Write default SOPS config", || {
             std::fs::write(root.join(SOPS_CONFIG_FILENAME), DEFAULT_SOPS_CONFIG)
                 .into_diagnostic()
-                .wrap_err("Failed to write SOPS config file")
+                .wrap_err("Failed to write SOPS config file")?;
+            Ok(vec![SOPS_CONFIG_FILENAME.into()])
         })?;
 
-        Ok(Self { root: root.into() })
+        Ok(Self {
+            root: root.into(),
+            config: StoreConfig::default(),
+            auto_push: false,
+        })
     }
 
-    /// Opens a secret store, verifying that it is valid.
-    pub(crate) fn open(root: &Path) -> miette::Result<Self> {
-        check_store_is_valid(root).wrap_err(format!("Store at `{}` is invalid", root.display()))?;
+    /// Opens a secret store, verifying that it is valid and loading its config.
+    ///
+    /// If auto-push is enabled in the store config and not running `offline`, then the store is
+    /// fast-forwarded to its upstream if it is behind, and every commit made through the store is
+    /// pushed. Failing to pull is reported as a warning, so that the store can still be used
+    /// locally.
+    pub(crate) fn open(root: &Path, offline: bool) -> miette::Result<Self> {
+        let config = check_store_is_valid(root)
+            .and_then(|_| StoreConfig::load(root))
+            .wrap_err(format!("Store at `{}` is invalid", root.display()))?;
+
+        let store = Self {
+            root: root.into(),
+            auto_push: config.auto_push && !offline,
+            config,
+        };
+
+        if store.auto_push {
+            if let Err(e) = store.pull_if_behind() {
+                warn("Failed to pull changes from the remote", e);
+            }
+        }
 
-        Ok(Self { root: root.into() })
+        Ok(store)
     }
 
     pub(crate) fn root(&self) -> &Path {
         &self.root
     }
 
+    pub(crate) fn config(&self) -> &StoreConfig {
+        &self.config
+    }
+
     pub(crate) fn location(&self, path: &Path) -> StoreLocation {
-        StoreLocation::from_path(&self.root, path)
+        StoreLocation::from_path(self, path)
+    }
+
+    /// Performs an operation that changes the store and commits the result, see
+    /// [`crate::utils::git::git_operation`].
+    ///
+    /// If auto-push is enabled then the commit is pushed afterwards. Failing to reach the remote is
+    /// reported as a warning, so that the store can still be used locally.
+    pub(crate) fn git_operation<F: Fn() -> miette::Result<Vec<PathBuf>>>(
+        &self,
+        commit_msg: &str,
+        op: F,
+    ) -> miette::Result<GitOperationResult> {
+        let result = crate::utils::git::git_operation(&self.root, commit_msg, op)?;
+
+        if self.auto_push && result == GitOperationResult::Commit {
+            if let Err(e) = self.push() {
+                warn(
+                    "Failed to push changes, they have been committed locally",
+                    e,
+                );
+            }
+        }
+
+        Ok(result)
     }
 
     /// Opens the SOPS config file in EDITOR for interactive editing, committing the changes after
     /// the editor is closed.
     pub(crate) fn edit_config_interactive(&self) -> miette::Result<bool> {
-        Ok(
-            crate::utils::git::git_operation(&self.root, "Edit SOPS config", || {
+        Ok(self
+            .git_operation("Edit SOPS config", || {
                 crate::utils::file::edit_file_interactive(self.root.join(SOPS_CONFIG_FILENAME))
-                    .wrap_err("Failed to edit SOPS config file")
+                    .wrap_err("Failed to edit SOPS config file")?;
+                Ok(vec![SOPS_CONFIG_FILENAME.into()])
             })
             .wrap_err("Failed to edit SOPS config file")?
-                == GitOperationResult::Commit,
-        )
+            == GitOperationResult::Commit)
+    }
+}
+
+/// A problem that doesn't stop a command from completing.
+#[derive(Debug)]
+struct Warning {
+    message: &'static str,
+    cause: miette::Report,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message)
+    }
+}
+
+impl std::error::Error for Warning {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(AsRef::<dyn std::error::Error + Send + Sync>::as_ref(
+            &self.cause,
+        ))
     }
 }
 
+impl miette::Diagnostic for Warning {
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(miette::Severity::Warning)
+    }
+}
+
+/// Prints a warning on stderr, in the same format as errors (i.e. as JSON with `--output json`).
+fn warn(message: &'static str, cause: miette::Report) {
+    eprintln!("{:?}", miette::Report::new(Warning { message, cause }));
+}
+
 fn check_store_is_valid(root: &Path) -> miette::Result<()> {
     // Check that the root of the store exists at all
     if !root.exists() {
@@ -117,20 +210,20 @@ fn check_store_is_valid(root: &Path) -> miette::Result<()> {
 
 #[derive(Debug)]
 pub(crate) struct StoreLocation<'a> {
-    root: &'a Path,
+    store: &'a Store,
     store_filename: PathBuf,
 }
 
 impl<'a> StoreLocation<'a> {
-    pub(crate) fn from_path(root: &'a Path, store_path: &Path) -> Self {
+    pub(crate) fn from_path(store: &'a Store, store_path: &Path) -> Self {
         StoreLocation {
-            root,
+            store,
             store_filename: store_path.into(),
         }
     }
 
     pub(crate) fn filename(&self) -> PathBuf {
-        self.root.join(&self.store_filename)
+        self.store.root.join(&self.store_filename)
     }
 
     pub(crate) fn store_filename(&self) -> &Path {
@@ -138,7 +231,7 @@ impl<'a> StoreLocation<'a> {
     }
 
     fn create_directories(&self) -> miette::Result<()> {
-        let dir = self.root.join(&self.store_filename);
+        let dir = self.store.root.join(&self.store_filename);
         let parent = dir.parent().unwrap();
 
         std::fs::create_dir_all(parent)
@@ -155,15 +248,10 @@ impl<'a> StoreLocation<'a> {
     }
 
     /// Moves/renames this directory/record, committing changes.
+    ///
+    /// Records moved to a different store are re-encrypted for that store, with a commit in each
+    /// store.
     pub(crate) fn move_to(self, destination: StoreLocation<'a>) -> miette::Result<()> {
-        // Ensure that the destination is in the same store
-        if self.root != destination.root {
-            return Err(miette!(
-                "Cannot move record to a different store: {}",
-                destination.root.display()
-            ));
-        }
-
         // Ensure that the destination does not already exist
         if destination.filename().exists() {
             return Err(miette!(
@@ -172,12 +260,15 @@ impl<'a> StoreLocation<'a> {
             ));
         }
 
+        if self.store.root != destination.store.root {
+            return self.move_to_store(destination);
+        }
+
         // Ensure directories for destination exist
         destination.create_directories()?;
 
         // Move the directory/record
-        let _ = crate::utils::git::git_operation(
-            self.root,
+        let _ = self.store.git_operation(
             &format!(
                 "Move `{}` => `{}`",
                 self.store_filename().display(),
@@ -192,35 +283,148 @@ impl<'a> StoreLocation<'a> {
                             self.store_filename().display(),
                             destination.store_filename().display()
                         )
-                    })
+                    })?;
+
+                Ok(vec![
+                    self.store_filename().to_owned(),
+                    destination.store_filename().to_owned(),
+                ])
+            },
+        )?;
+
+        Ok(())
+    }
+
+    /// Copies this directory/record, re-encrypting each record at its new path and committing
+    /// the copies.
+    pub(crate) fn copy_to(&self, destination: &StoreLocation) -> miette::Result<()> {
+        // Ensure that the destination does not already exist
+        if destination.filename().exists() {
+            return Err(miette!(
+                "Destination `{}` already exists",
+                destination.store_filename().display()
+            ));
+        }
+
+        let commit_msg = if self.store.root == destination.store.root {
+            format!(
+                "Copy `{}` => `{}`",
+                self.store_filename().display(),
+                destination.store_filename().display()
+            )
+        } else {
+            format!(
+                "Copy `{}` from store `{}`",
+                self.store_filename().display(),
+                self.store.root.display()
+            )
+        };
+
+        let _ = destination
+            .store
+            .git_operation(&commit_msg, || self.reencrypt_to(destination))?;
+
+        Ok(())
+    }
+
+    /// Moves this directory/record to a different store.
+    ///
+    /// The records are committed to the destination store before they are removed from this one,
+    /// so that a failure part way through never loses a record.
+    fn move_to_store(self, destination: StoreLocation<'a>) -> miette::Result<()> {
+        let _ = destination.store.git_operation(
+            &format!(
+                "Move `{}` from store `{}`",
+                self.store_filename().display(),
+                self.store.root.display()
+            ),
+            || self.reencrypt_to(&destination),
+        )?;
+
+        let _ = self.store.git_operation(
+            &format!(
+                "Move `{}` to store `{}`",
+                self.store_filename().display(),
+                destination.store.root.display()
+            ),
+            || {
+                self.remove()?;
+                Ok(vec![self.store_filename().to_owned()])
             },
         )?;
 
         Ok(())
     }
 
+    /// Decrypts each record at this location in memory and encrypts it at the equivalent path
+    /// under `destination`, so that it is encrypted using the creation rules that apply there.
+    ///
+    /// Returns the paths (relative to the destination store) that were written.
+    fn reencrypt_to(&self, destination: &StoreLocation) -> miette::Result<Vec<PathBuf>> {
+        let records = if self.exists() {
+            vec![self.store_filename.clone()]
+        } else if self.filename().is_dir() {
+            self.store.list_records(Some(&self.store_filename))?
+        } else {
+            return Err(miette!(
+                "No such file or directory: `{}`",
+                self.store_filename().display()
+            ));
+        };
+
+        let mut written = Vec::new();
+
+        for record in records {
+            let target = match record.strip_prefix(&self.store_filename) {
+                Ok(relative) if !relative.as_os_str().is_empty() => {
+                    destination.store_filename.join(relative)
+                }
+                _ => destination.store_filename.clone(),
+            };
+
+            let contents = self.store.get_record(&record)?.decrypt_and_extract(None)?;
+
+            let target_location = destination.store.location(&target);
+            target_location.create_directories()?;
+            crate::utils::sops::encrypt(
+                &destination.store.root,
+                &target_location.filename(),
+                contents,
+            )
+            .wrap_err_with(|| format!("Failed to re-encrypt `{}`", record.display()))?;
+
+            written.push(target);
+        }
+
+        Ok(written)
+    }
+
     /// Deletes this directory/record from the store, committing changes.
     pub(crate) fn delete(&self) -> miette::Result<()> {
-        let _ = crate::utils::git::git_operation(
-            self.root,
+        let _ = self.store.git_operation(
             &format!("Delete `{}`", self.store_filename().display()),
             || {
-                let remove_result = if self.filename().is_file() {
-                    std::fs::remove_file(self.filename()).into_diagnostic()
-                } else if self.filename().is_dir() {
-                    std::fs::remove_dir_all(self.filename()).into_diagnostic()
-                } else {
-                    Err(miette!("No such file or directory"))
-                };
-
-                remove_result.wrap_err_with(|| {
-                    format!("Failed to delete `{}`", self.store_filename().display())
-                })
+                self.remove()?;
+                Ok(vec![self.store_filename().to_owned()])
             },
         )?;
 
         Ok(())
     }
+
+    /// Removes this directory/record from disk.
+    fn remove(&self) -> miette::Result<()> {
+        let remove_result = if self.filename().is_file() {
+            std::fs::remove_file(self.filename()).into_diagnostic()
+        } else if self.filename().is_dir() {
+            std::fs::remove_dir_all(self.filename()).into_diagnostic()
+        } else {
+            Err(miette!("No such file or directory"))
+        };
+
+        remove_result
+            .wrap_err_with(|| format!("Failed to delete `{}`", self.store_filename().display()))
+    }
 }
 
 #[cfg(test)]
@@ -245,7 +449,7 @@ mod tests {
         assert!(config_path.exists());
 
         // Should open successfully
-        let opened = Store::open(root).unwrap();
+        let opened = Store::open(root, true).unwrap();
         assert_eq!(opened.root(), root);
     }
 
@@ -255,7 +459,7 @@ mod tests {
         let root = dir.path();
 
         // Directory exists but not initialized as a store
-        let err = Store::open(root).unwrap_err();
+        let err = Store::open(root, true).unwrap_err();
         assert!(err.to_string().contains("invalid"));
     }
 
@@ -265,10 +469,10 @@ mod tests {
 
         let dir = tempdir().unwrap();
         let root = dir.path();
-        let _ = Store::init(root).unwrap();
+        let store = Store::init(root).unwrap();
 
         let file_path = Path::new("foo/bar.txt");
-        let loc = StoreLocation::from_path(root, file_path);
+        let loc = StoreLocation::from_path(&store, file_path);
 
         // File should not exist yet
         assert!(!loc.exists());
@@ -281,4 +485,125 @@ mod tests {
         assert!(loc.exists());
         assert_eq!(loc.store_filename(), file_path);
     }
+
+    #[test]
+    fn move_to_other_store() {
+        let dir = tempdir().unwrap();
+        let a = crate::utils::test::init_store(&dir.path().join("a"));
+        let b = crate::utils::test::init_store(&dir.path().join("b"));
+
+        for (path, contents) in [("web/one.yaml", "password: one\n"), ("web/two", "two")] {
+            a.create_record(Path::new(path))
+                .unwrap()
+                .encrypt_entire_file(contents.as_bytes().to_vec().into())
+                .unwrap();
+        }
+
+        a.location(Path::new("web"))
+            .move_to(b.location(Path::new("shared/web")))
+            .unwrap();
+
+        assert!(!a.root().join("web").exists());
+        let record = b.get_record(Path::new("shared/web/one.yaml")).unwrap();
+        assert_eq!(
+            *record.decrypt_and_extract(Some("password")).unwrap(),
+            b"one"
+        );
+        let record = b.get_record(Path::new("shared/web/two")).unwrap();
+        assert_eq!(*record.decrypt_and_extract(None).unwrap(), b"two");
+
+        // Both stores have committed their side of the move
+        assert!(crate::utils::git::dirty_paths(a.root()).unwrap().is_empty());
+        assert!(crate::utils::git::dirty_paths(b.root()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn move_to_other_store_existing() {
+        let dir = tempdir().unwrap();
+        let a = crate::utils::test::init_store(&dir.path().join("a"));
+        let b = crate::utils::test::init_store(&dir.path().join("b"));
+
+        for store in [&a, &b] {
+            store
+                .create_record(Path::new("web"))
+                .unwrap()
+                .encrypt_entire_file(b"secret".to_vec().into())
+                .unwrap();
+        }
+
+        assert!(
+            a.location(Path::new("web"))
+                .move_to(b.location(Path::new("web")))
+                .is_err()
+        );
+        assert!(a.location(Path::new("web")).exists());
+    }
+
+    #[test]
+    fn copy_uses_destination_creation_rule() {
+        let dir = tempdir().unwrap();
+        let store = crate::utils::test::init_store(dir.path());
+        let recipient = crate::utils::test::set_age_key();
+        let other = age::x25519::Identity::generate().to_public().to_string();
+
+        let _ = store
+            .git_operation("Add rule for shared records", || {
+                fs::write(
+                    dir.path().join(SOPS_CONFIG_FILENAME),
+                    format!(
+                        "creation_rules:\n  - path_regex: ^shared/\n    age: {recipient},{other}\n  - age: {recipient}\n"
+                    ),
+                )
+                .into_diagnostic()?;
+                Ok(vec![SOPS_CONFIG_FILENAME.into()])
+            })
+            .unwrap();
+
+        store
+            .create_record(Path::new("templates/web.yaml"))
+            .unwrap()
+            .encrypt_entire_file(b"user: alice\n".to_vec().into())
+            .unwrap();
+
+        store
+            .location(Path::new("templates"))
+            .copy_to(&store.location(Path::new("shared/templates")))
+            .unwrap();
+
+        let copy = store
+            .get_record(Path::new("shared/templates/web.yaml"))
+            .unwrap();
+        assert_eq!(*copy.decrypt_and_extract(Some("user")).unwrap(), b"alice");
+        assert!(
+            crate::utils::sops::recipients(&copy.filename())
+                .unwrap()
+                .contains(&other)
+        );
+        assert!(
+            !crate::utils::sops::recipients(&dir.path().join("templates/web.yaml"))
+                .unwrap()
+                .contains(&other)
+        );
+
+        // The source is untouched and everything is in a single commit
+        assert!(store.location(Path::new("templates/web.yaml")).exists());
+        assert!(
+            crate::utils::git::dirty_paths(store.root())
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            crate::utils::git::log(store.root(), Path::new("shared/templates/web.yaml"))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        assert!(
+            store
+                .location(Path::new("templates"))
+                .copy_to(&store.location(Path::new("shared/templates")))
+                .is_err()
+        );
+    }
 }
@@ -1,4 +1,7 @@
-use crate::utils::git::GitOperationResult;
+use crate::utils::{
+    document::{Format, Value, parse_selector},
+    git::GitOperationResult,
+};
 
 use super::{Store, StoreLocation};
 use miette::{IntoDiagnostic, miette};
@@ -7,14 +10,22 @@ use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 use zeroize::Zeroizing;
 
+/// The scalar values of a record along with their paths, which are `None` for records that are
+/// not YAML or JSON.
+pub(crate) type Values = Vec<(Option<String>, Zeroizing<Value>)>;
+
 impl Store {
     /// Create a new record in the store.
     ///
+    /// Paths without a file extension are given the extension of the default record format.
     /// Performs validation that it can exist and creates the required directories, but otherwise
     /// does not write anything to disk.
     /// Writing must be performed using the returned `Record`.
     pub(crate) fn create_record(&self, path: &Path) -> miette::Result<Record> {
-        let location = self.location(path);
+        let location = match self.with_default_extension(path) {
+            Some(path) => self.location(&path),
+            None => self.location(path),
+        };
 
         if location.exists() {
             return Err(miette!(
@@ -78,13 +89,21 @@ impl Store {
     }
 
     /// Get a record given a path in the store.
+    ///
+    /// If there is no record at exactly that path, then one with the extension of the default
+    /// record format is looked for.
     pub(crate) fn get_record(&self, path: &Path) -> miette::Result<Record> {
         let exact_location = self.location(path);
+        let default_location = self
+            .with_default_extension(path)
+            .map(|path| self.location(&path));
 
         if exact_location.exists() {
             Ok(Record {
                 location: exact_location,
             })
+        } else if let Some(location) = default_location.filter(|l| l.exists()) {
+            Ok(Record { location })
         } else {
             Err(miette!(
                 "No secret found at `{}`",
@@ -96,13 +115,32 @@ impl Store {
     /// Get a record given a path in the store.
     ///
     /// Will not fail if the path in the store does not exist.
-    /// Instead a `Record` will be returned that simply does not exist.
+    /// Instead a `Record` will be returned that simply does not exist, with the extension of the
+    /// default record format if the path has none.
     pub(crate) fn get_record_unchecked(&self, path: &Path) -> miette::Result<Record> {
         let exact_location = self.location(path);
 
-        Ok(Record {
-            location: exact_location,
-        })
+        match self.with_default_extension(path) {
+            Some(path) if !exact_location.exists() => Ok(Record {
+                location: self.location(&path),
+            }),
+            _ => Ok(Record {
+                location: exact_location,
+            }),
+        }
+    }
+
+    /// Adds the extension of the default record format to a path that has no extension, `None` if
+    /// the path already has one or new records are binary by default.
+    pub(crate) fn with_default_extension(&self, path: &Path) -> Option<PathBuf> {
+        if path.extension().is_some() {
+            return None;
+        }
+
+        self.config
+            .default_format
+            .extension()
+            .map(|extension| path.with_extension(extension))
     }
 }
 
@@ -120,26 +158,38 @@ impl<'a> Record<'a> {
         // Ensure the directory for this record exists before trying to edit interactively with SOPS
         self.location.create_directories()?;
 
-        Ok(crate::utils::git::git_operation(
-            self.location.root,
+        Ok(self.location.store.git_operation(
             &format!("Edit record `{}`", self.location.store_filename().display()),
-            || crate::utils::sops::edit(self.location.root, &self.location.filename()),
+            || {
+                crate::utils::sops::edit(self.location.store.root(), &self.location.filename())?;
+                Ok(vec![self.location.store_filename().to_owned()])
+            },
         )? == GitOperationResult::Commit)
     }
 
     pub(crate) fn encrypt_entire_file(&self, contents: Zeroizing<Vec<u8>>) -> miette::Result<()> {
-        let _ = crate::utils::git::git_operation(
-            self.location.root,
+        let _ = self.location.store.git_operation(
             &format!(
                 "Update contents of record `{}`",
                 self.location.store_filename().display()
             ),
             || {
-                crate::utils::sops::encrypt(
-                    self.location.root,
-                    &self.location.filename(),
-                    contents.clone(),
-                )
+                // Updating an existing file keeps the ciphertext of values that haven't changed,
+                // which is how `stale` tells when each of them last changed
+                if self.location.filename().exists() {
+                    crate::utils::sops::update(
+                        self.location.store.root(),
+                        &self.location.filename(),
+                        contents.clone(),
+                    )?;
+                } else {
+                    crate::utils::sops::encrypt(
+                        self.location.store.root(),
+                        &self.location.filename(),
+                        contents.clone(),
+                    )?;
+                }
+                Ok(vec![self.location.store_filename().to_owned()])
             },
         )?;
 
@@ -151,8 +201,7 @@ impl<'a> Record<'a> {
         selector: &str,
         contents: Zeroizing<Vec<u8>>,
     ) -> miette::Result<()> {
-        let _ = crate::utils::git::git_operation(
-            self.location.root,
+        let _ = self.location.store.git_operation(
             &format!(
                 "Update `{selector}` in record `{}`",
                 self.location.store_filename().display()
@@ -162,11 +211,12 @@ impl<'a> Record<'a> {
                 let contents = crate::utils::bytes_to_string(contents.clone())?;
 
                 crate::utils::sops::set(
-                    self.location.root,
+                    self.location.store.root(),
                     &self.location.filename(),
                     selector.unwrap().as_str(),
                     contents,
-                )
+                )?;
+                Ok(vec![self.location.store_filename().to_owned()])
             },
         )?;
 
@@ -180,16 +230,164 @@ impl<'a> Record<'a> {
         let selector = format_selector(selector);
 
         crate::utils::sops::decrypt(
-            self.location.root,
+            self.location.store.root(),
+            self.location.store_filename(),
+            selector.as_deref(),
+        )
+    }
+
+    /// Decrypt the record as it was at a given Git revision, optionally extracting part of it.
+    ///
+    /// The working tree is not touched, the encrypted contents are decrypted from a temporary copy.
+    pub(crate) fn decrypt_and_extract_at(
+        &self,
+        rev: &str,
+        selector: Option<&str>,
+    ) -> miette::Result<Zeroizing<Vec<u8>>> {
+        let contents = self.read_at(rev)?;
+        let selector = format_selector(selector);
+
+        crate::utils::sops::decrypt_contents(
+            self.location.store.root(),
             self.location.store_filename(),
+            &contents,
             selector.as_deref(),
         )
     }
 
+    /// Restore the record to how it was at a given Git revision, committing the result.
+    ///
+    /// Returns `false` if the record is already the same as at that revision.
+    pub(crate) fn restore(&self, rev: &str) -> miette::Result<bool> {
+        Ok(self.location.store.git_operation(
+            &format!(
+                "Restore record `{}` from `{rev}`",
+                self.location.store_filename().display()
+            ),
+            || {
+                let contents = self.read_at(rev)?;
+
+                self.location.create_directories()?;
+                std::fs::write(self.location.filename(), contents).into_diagnostic()?;
+
+                Ok(vec![self.location.store_filename().to_owned()])
+            },
+        )? == GitOperationResult::Commit)
+    }
+
+    /// List the commits that changed the record, newest first.
+    pub(crate) fn log(&self) -> miette::Result<Vec<crate::utils::git::LogEntry>> {
+        crate::utils::git::log(self.location.store.root(), self.location.store_filename())
+    }
+
+    /// Check if the record exists, either in the working tree or at a given Git revision.
+    pub(crate) fn exists_at(&self, rev: Option<&str>) -> miette::Result<bool> {
+        match rev {
+            Some(rev) => Ok(crate::utils::git::read_file_at(
+                self.location.store.root(),
+                rev,
+                self.location.store_filename(),
+            )?
+            .is_some()),
+            None => Ok(self.location.exists()),
+        }
+    }
+
+    /// Decrypt the record and return each of its scalar values along with the path to it.
+    ///
+    /// If a Git revision is given then the record is decrypted as it was at that revision.
+    /// Records that are not YAML or JSON are returned as a single string value with no path.
+    pub(crate) fn decrypt_values(&self, rev: Option<&str>) -> miette::Result<Values> {
+        self.decrypt_text_values(rev)?.ok_or_else(|| {
+            miette!(
+                "Record `{}` is binary data, not text",
+                self.location.store_filename().display()
+            )
+        })
+    }
+
+    /// Like `decrypt_values`, but `None` for records that are binary data rather than text, so
+    /// that callers can skip them.
+    pub(crate) fn decrypt_text_values(&self, rev: Option<&str>) -> miette::Result<Option<Values>> {
+        let contents = match rev {
+            Some(rev) => self.decrypt_and_extract_at(rev, None)?,
+            None => self.decrypt_and_extract(None)?,
+        };
+
+        text_values(Format::from_path(&self.filename()), &contents)
+    }
+
+    /// Decrypt the record into a document, optionally selecting part of it.
+    ///
+    /// If a Git revision is given then the record is decrypted as it was at that revision.
+    /// Records that are not YAML or JSON are returned as a single string value, which fails if
+    /// they are not valid UTF-8.
+    pub(crate) fn decrypt_document(
+        &self,
+        rev: Option<&str>,
+        selector: Option<&str>,
+    ) -> miette::Result<Zeroizing<Value>> {
+        let contents = match rev {
+            Some(rev) => self.decrypt_and_extract_at(rev, None)?,
+            None => self.decrypt_and_extract(None)?,
+        };
+
+        let document = Zeroizing::new(match Format::from_path(&self.filename()) {
+            format @ (Format::Yaml | Format::Json) => Value::parse(format, &contents)?,
+            _ => Value::String(
+                std::str::from_utf8(&contents)
+                    .map_err(|_| {
+                        miette!(
+                            "Record `{}` is binary data, not text",
+                            self.location.store_filename().display()
+                        )
+                    })?
+                    .to_owned(),
+            ),
+        });
+
+        match format_selector(selector) {
+            None => Ok(document),
+            Some(selector) => document
+                .get(&parse_selector(&selector)?)
+                .map(|value| Zeroizing::new(value.clone()))
+                .ok_or_else(|| {
+                    miette!(
+                        "Nothing found at `{selector}` in record `{}`",
+                        self.location.store_filename().display()
+                    )
+                }),
+        }
+    }
+
+    /// Read the encrypted contents of the record at a given Git revision.
+    fn read_at(&self, rev: &str) -> miette::Result<Vec<u8>> {
+        crate::utils::git::read_file_at(
+            self.location.store.root(),
+            rev,
+            self.location.store_filename(),
+        )?
+        .ok_or_else(|| {
+            miette!(
+                "No secret found at `{}` in revision `{rev}`",
+                self.location.store_filename().display()
+            )
+        })
+    }
+
     /// Return a list of all paths that lead to values.
     ///
-    /// Will only give results for files that are valid YAML or JSON encoded SOPS files.
+    /// Will only give results for files that are valid YAML or JSON encoded SOPS files. Records in
+    /// other formats are encrypted as a whole (binary records are stored under a single `data`
+    /// key), so they have no attributes.
     pub(crate) fn list_attributes(&self) -> miette::Result<Vec<String>> {
+        if !matches!(
+            Format::from_path(&self.filename()),
+            Format::Yaml | Format::Json
+        ) {
+            return Ok(Vec::new());
+        }
+
         let content = std::fs::read_to_string(self.location.filename()).into_diagnostic()?;
 
         fn handle_json_object(object: &serde_json::Map<String, serde_json::Value>) -> Vec<String> {
@@ -257,6 +455,23 @@ impl<'a> Record<'a> {
     }
 }
 
+/// Splits decrypted contents into their scalar values, `None` if they are binary data that isn't
+/// valid UTF-8.
+fn text_values(format: Format, contents: &[u8]) -> miette::Result<Option<Values>> {
+    match format {
+        Format::Yaml | Format::Json => Ok(Some(
+            Zeroizing::new(Value::parse(format, contents)?)
+                .leaves()
+                .into_iter()
+                .map(|(path, value)| (Some(path), Zeroizing::new(value.clone())))
+                .collect(),
+        )),
+        _ => Ok(std::str::from_utf8(contents)
+            .ok()
+            .map(|text| vec![(None, Zeroizing::new(Value::String(text.to_owned())))])),
+    }
+}
+
 fn format_selector(selector: Option<&str>) -> Option<String> {
     selector.map(|s| {
         if s.contains('[') || s.contains(']') {
@@ -284,6 +499,7 @@ fn format_selector(selector: Option<&str>) -> Option<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::secret_store::StoreConfig;
     use std::path::Path;
     use tempfile::tempdir;
 
@@ -296,6 +512,8 @@ mod tests {
         crate::utils::test::init_git_repo(dir.path());
         let store = Store {
             root: store_path.clone(),
+            config: Default::default(),
+            auto_push: false,
         };
 
         let record_path = Path::new("foo/bar.txt");
@@ -319,7 +537,11 @@ mod tests {
 
         crate::utils::test::set_git_config();
         crate::utils::test::init_git_repo(&store_path);
-        let store = Store { root: store_path };
+        let store = Store {
+            root: store_path,
+            config: Default::default(),
+            auto_push: false,
+        };
 
         let paths = [
             Path::new("foo/bar.txt"),
@@ -351,6 +573,8 @@ mod tests {
         crate::utils::test::init_git_repo(dir.path());
         let store = Store {
             root: store_path.clone(),
+            config: Default::default(),
+            auto_push: false,
         };
 
         let record_path = Path::new("does/not/exist.txt");
@@ -358,6 +582,51 @@ mod tests {
         assert_eq!(record.filename(), store_path.join(record_path));
     }
 
+    #[test]
+    fn default_format_extension() {
+        let dir = tempdir().unwrap();
+        let store_path = dir.path().join("store");
+
+        crate::utils::test::set_git_config();
+        crate::utils::test::init_git_repo(dir.path());
+        let store = Store {
+            root: store_path.clone(),
+            config: StoreConfig {
+                default_format: Format::Yaml,
+                ..Default::default()
+            },
+            auto_push: false,
+        };
+
+        let record = store.create_record(Path::new("web/site")).unwrap();
+        assert_eq!(record.filename(), store_path.join("web/site.yaml"));
+        std::fs::write(record.filename(), "dummy").unwrap();
+
+        let fetched = store.get_record(Path::new("web/site")).unwrap();
+        assert_eq!(fetched.filename(), record.filename());
+
+        let record = store.create_record(Path::new("web/other.json")).unwrap();
+        assert_eq!(record.filename(), store_path.join("web/other.json"));
+
+        let record = store.get_record_unchecked(Path::new("web/new")).unwrap();
+        assert_eq!(record.filename(), store_path.join("web/new.yaml"));
+    }
+
+    #[test]
+    fn text_values_of_binary_data() {
+        let values = text_values(Format::Binary, b"hunter2").unwrap().unwrap();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].0, None);
+        assert_eq!(*values[0].1, Value::String("hunter2".into()));
+
+        assert!(
+            text_values(Format::Binary, &[0xff, 0xfe])
+                .unwrap()
+                .is_none()
+        );
+        assert!(text_values(Format::Yaml, &[0xff, 0xfe]).is_err());
+    }
+
     #[test]
     fn format_selector_none() {
         assert_eq!(format_selector(None), None);
@@ -391,4 +660,29 @@ mod tests {
             Some("[\"foo\"][\"bar\"]".to_string())
         );
     }
+
+    #[test]
+    fn rewrite_keeps_unchanged_ciphertexts() {
+        let dir = tempdir().unwrap();
+        let store = crate::utils::test::init_store(dir.path());
+
+        let record = store.create_record(Path::new("web.yaml")).unwrap();
+        let ciphertexts = || {
+            let filename = record.filename();
+            crate::utils::sops::ciphertexts(&filename, &std::fs::read(&filename).unwrap())
+        };
+
+        record
+            .encrypt_entire_file(b"user: alice\npassword: hunter2\n".to_vec().into())
+            .unwrap();
+        let before = ciphertexts();
+
+        record
+            .encrypt_entire_file(b"user: alice\npassword: hunter3\n".to_vec().into())
+            .unwrap();
+        let after = ciphertexts();
+
+        assert_eq!(before["user"], after["user"]);
+        assert_ne!(before["password"], after["password"]);
+    }
 }
@@ -0,0 +1,620 @@
+use super::Store;
+use crate::utils::document::{Format, Value};
+use miette::{Context, IntoDiagnostic, miette};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+use zeroize::Zeroizing;
+
+/// How remote changes are integrated with local commits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SyncStrategy {
+    Merge,
+    Rebase,
+}
+
+/// What happened during a sync.
+#[derive(Debug, Default)]
+pub(crate) struct SyncSummary {
+    /// Number of commits that were fetched from the remote and integrated
+    pub(crate) pulled: usize,
+    /// Number of commits that were pushed to the remote
+    pub(crate) pushed: usize,
+    /// Records that were changed on both sides and had to be merged
+    pub(crate) merged: Vec<PathBuf>,
+}
+
+impl Store {
+    /// Synchronises the store with the upstream of its current branch using the `git` executable.
+    ///
+    /// Remote changes are fetched and merged (or local commits are rebased onto them), then local
+    /// commits are pushed.
+    /// Records that conflict are merged by decrypting each version and merging them key by key, if
+    /// the same value was changed on both sides then the whole operation is aborted.
+    pub(crate) fn sync(&self, strategy: SyncStrategy, push: bool) -> miette::Result<SyncSummary> {
+        if !crate::utils::git::dirty_paths(&self.root)?.is_empty() {
+            return Err(miette!(
+                "The store has uncommitted changes, commit or discard them before syncing"
+            ));
+        }
+
+        let _ = self
+            .git(["rev-parse", "--abbrev-ref", "--symbolic-full-name", "@{upstream}"])
+            .wrap_err(
+                "The current branch has no upstream, set one with `koishi git push --set-upstream <remote> <branch>`",
+            )?;
+
+        let _ = self.git(["fetch", "--quiet"])?;
+
+        let mut summary = SyncSummary {
+            pulled: self.count_commits("HEAD..@{upstream}")?,
+            ..Default::default()
+        };
+
+        if summary.pulled > 0 {
+            summary.merged = match strategy {
+                SyncStrategy::Merge => self.merge_upstream()?,
+                SyncStrategy::Rebase => self.rebase_onto_upstream()?,
+            };
+        }
+
+        if push {
+            summary.pushed = self.count_commits("@{upstream}..HEAD")?;
+            if summary.pushed > 0 {
+                self.push()?;
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Fast-forwards the current branch to its upstream if it is behind, without pushing.
+    ///
+    /// Branches without an upstream are left alone. A branch that has diverged from its upstream
+    /// is also left alone and reported as an error, integrating the changes is left to `sync`.
+    pub(super) fn pull_if_behind(&self) -> miette::Result<()> {
+        if !self
+            .git_output(["rev-parse", "--verify", "--quiet", "@{upstream}"])?
+            .status
+            .success()
+        {
+            return Ok(());
+        }
+
+        let _ = self.git(["fetch", "--quiet"])?;
+
+        if self.count_commits("HEAD..@{upstream}")? == 0 {
+            return Ok(());
+        }
+
+        if self.count_commits("@{upstream}..HEAD")? > 0 {
+            return Err(miette!(
+                "The store has diverged from its remote, run `koishi sync` to integrate the changes"
+            ));
+        }
+
+        let _ = self.git(["merge", "--quiet", "--ff-only", "@{upstream}"])?;
+
+        Ok(())
+    }
+
+    /// Pushes the current branch to its configured remote.
+    pub(super) fn push(&self) -> miette::Result<()> {
+        let _ = self.git(["push", "--quiet"])?;
+        Ok(())
+    }
+
+    fn merge_upstream(&self) -> miette::Result<Vec<PathBuf>> {
+        if self
+            .git_output(["merge", "--no-edit", "@{upstream}"])?
+            .status
+            .success()
+        {
+            return Ok(Vec::new());
+        }
+
+        let conflicted = self.conflicted_paths()?;
+
+        let result = if conflicted.is_empty() {
+            Err(miette!("Failed to merge remote changes"))
+        } else {
+            self.resolve_conflicts(&conflicted)
+                .and_then(|_| self.git(["commit", "--no-edit"]).map(|_| ()))
+        };
+
+        match result {
+            Ok(()) => Ok(conflicted),
+            Err(e) => {
+                let _ = self.git(["merge", "--abort"]);
+                Err(e.wrap_err("Merge aborted"))
+            }
+        }
+    }
+
+    fn rebase_onto_upstream(&self) -> miette::Result<Vec<PathBuf>> {
+        let mut merged = Vec::new();
+
+        let mut output = self.git_output(["rebase", "@{upstream}"])?;
+
+        while !output.status.success() {
+            let conflicted = self.conflicted_paths()?;
+
+            let result = if conflicted.is_empty() {
+                Err(miette!("Failed to rebase onto remote changes"))
+            } else {
+                self.resolve_conflicts(&conflicted)
+            };
+
+            if let Err(e) = result {
+                let _ = self.git(["rebase", "--abort"]);
+                return Err(e.wrap_err("Rebase aborted"));
+            }
+
+            merged.extend(conflicted);
+            output = self.git_output(["rebase", "--continue"])?;
+        }
+
+        merged.sort();
+        merged.dedup();
+        Ok(merged)
+    }
+
+    fn resolve_conflicts(&self, paths: &[PathBuf]) -> miette::Result<()> {
+        for path in paths {
+            self.resolve_conflict(path)
+                .wrap_err(format!("Failed to merge changes to `{}`", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Resolves a conflict in a record by merging the decrypted base, our and their versions.
+    fn resolve_conflict(&self, path: &Path) -> miette::Result<()> {
+        if path
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        {
+            return Err(miette!(
+                "`{}` is not a record, conflicts in it must be resolved by hand",
+                path.display()
+            ));
+        }
+
+        let format = Format::from_path(path);
+
+        // Index stages 1, 2 and 3 hold the base, our and their versions respectively
+        let base = self.conflict_stage(path, 1)?;
+        let ours = self.conflict_stage(path, 2)?;
+        let theirs = self.conflict_stage(path, 3)?;
+
+        let decrypt = |contents: &Option<Vec<u8>>| -> miette::Result<Option<Zeroizing<Value>>> {
+            contents
+                .as_ref()
+                .map(|contents| {
+                    let plaintext =
+                        crate::utils::sops::decrypt_contents(&self.root, path, contents, None)?;
+                    Ok(Zeroizing::new(to_document(format, &plaintext)?))
+                })
+                .transpose()
+        };
+
+        let merged = crate::utils::document::merge3(
+            decrypt(&base)?.as_deref(),
+            decrypt(&ours)?.as_deref(),
+            decrypt(&theirs)?.as_deref(),
+        )
+        .map_err(|conflicts| {
+            let conflicts: Vec<String> = conflicts
+                .iter()
+                .map(|c| {
+                    if c.is_empty() {
+                        "the record".into()
+                    } else {
+                        format!("`{c}`")
+                    }
+                })
+                .collect();
+            miette!("Both sides changed {}", conflicts.join(", "))
+        })?;
+
+        match merged.map(Zeroizing::new) {
+            None => {
+                let _ = self.git([
+                    OsStr::new("rm"),
+                    "--quiet".as_ref(),
+                    "--".as_ref(),
+                    path.as_ref(),
+                ])?;
+            }
+            Some(merged) => {
+                let contents = Zeroizing::new(from_document(format, &merged)?.into_bytes());
+
+                match ours {
+                    // Start from our version so that values we did not change keep their ciphertext
+                    Some(ours) => {
+                        std::fs::write(self.root.join(path), ours).into_diagnostic()?;
+                        crate::utils::sops::update(&self.root, path, contents)?;
+                    }
+                    None => crate::utils::sops::encrypt(&self.root, path, contents)?,
+                }
+
+                let _ = self.git([OsStr::new("add"), "--".as_ref(), path.as_ref()])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads a version of a conflicted file from the index, `None` if it is absent in that version.
+    fn conflict_stage(&self, path: &Path, stage: u8) -> miette::Result<Option<Vec<u8>>> {
+        // `./` makes the path relative to the store rather than the root of the repository
+        let object = format!(":{stage}:./{}", path.to_string_lossy().replace('\\', "/"));
+        let output = self.git_output(["cat-file", "blob", object.as_str()])?;
+
+        Ok(output.status.success().then_some(output.stdout))
+    }
+
+    /// Lists the conflicted paths in the store, relative to its root.
+    ///
+    /// Conflicts outside the store (if it is in a subdirectory of the repository) aren't included,
+    /// so they make the merge fail rather than being merged as records.
+    fn conflicted_paths(&self) -> miette::Result<Vec<PathBuf>> {
+        Ok(self
+            .git(["diff", "--name-only", "--diff-filter=U", "--relative", "-z"])?
+            .split('\0')
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    fn count_commits(&self, range: &str) -> miette::Result<usize> {
+        self.git(["rev-list", "--count", range])?
+            .trim()
+            .parse()
+            .into_diagnostic()
+    }
+
+    /// Runs a Git command in the store, returning its output if it succeeds.
+    fn git<I, S>(&self, args: I) -> miette::Result<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let output = self.git_output(args)?;
+
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(miette!(
+                "Git exited with non-success error code: {}\n{}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    /// Runs a Git command in the store, returning its output regardless of whether it succeeds.
+    fn git_output<I, S>(&self, args: I) -> miette::Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let result = Command::new("git")
+            .arg("-C")
+            .arg(&self.root)
+            .args(args)
+            // Never open an editor for commit messages
+            .env("GIT_EDITOR", "true")
+            .output();
+
+        match result {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(miette!(
+                "The `git` executable was not found on the PATH, it is needed to sync with a remote"
+            )),
+            result => result
+                .into_diagnostic()
+                .wrap_err("Failed to run git command"),
+        }
+    }
+}
+
+/// Parses decrypted contents into a document that can be merged, formats without structure are
+/// merged as a single value.
+fn to_document(format: Format, plaintext: &[u8]) -> miette::Result<Value> {
+    match format {
+        Format::Dotenv | Format::Ini => Ok(Value::String(
+            String::from_utf8_lossy(plaintext).into_owned(),
+        )),
+        format => Value::parse(format, plaintext),
+    }
+}
+
+fn from_document(format: Format, document: &Value) -> miette::Result<String> {
+    match format {
+        Format::Dotenv | Format::Ini => document
+            .scalar_to_string()
+            .ok_or_else(|| miette!("Merged {format:?} record is not a single value")),
+        format => document.emit(format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    }
+
+    /// Creates a store pushed to a bare remote, along with a second clone of it.
+    fn setup(dir: &Path) -> (Store, Store) {
+        setup_in(dir, None)
+    }
+
+    /// Like [`setup`], but with the stores in a subdirectory of their repositories.
+    fn setup_in(dir: &Path, subdir: Option<&str>) -> (Store, Store) {
+        let store_root = |repo: PathBuf| match subdir {
+            Some(subdir) => repo.join(subdir),
+            None => repo,
+        };
+
+        let a = match subdir {
+            None => crate::utils::test::init_store(&dir.join("a")),
+            Some(_) => {
+                crate::utils::test::set_git_config();
+                let recipient = crate::utils::test::set_age_key();
+
+                let root = store_root(dir.join("a"));
+                std::fs::create_dir_all(&root).unwrap();
+                let _ = git(&dir.join("a"), &["init", "--quiet"]);
+
+                std::fs::write(
+                    root.join(".sops.yaml"),
+                    format!("creation_rules:\n  - age: {recipient}\n"),
+                )
+                .unwrap();
+
+                let store = Store::open(&root, true).unwrap();
+                let _ = store
+                    .git_operation("Use test key", || Ok(vec![".sops.yaml".into()]))
+                    .unwrap();
+                store
+            }
+        };
+
+        let record = a.create_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_entire_file(Zeroizing::new(b"user: alice\npassword: one\n".to_vec()))
+            .unwrap();
+
+        let branch = git(a.root(), &["symbolic-ref", "--short", "HEAD"]);
+        let remote = dir.join("remote.git");
+        let _ = git(
+            dir,
+            &[
+                "init",
+                "--quiet",
+                "--bare",
+                "--initial-branch",
+                &branch,
+                remote.to_str().unwrap(),
+            ],
+        );
+        let _ = git(
+            a.root(),
+            &["remote", "add", "origin", remote.to_str().unwrap()],
+        );
+        let _ = git(
+            a.root(),
+            &["push", "--quiet", "--set-upstream", "origin", &branch],
+        );
+
+        let _ = git(
+            dir,
+            &[
+                "clone",
+                "--quiet",
+                remote.to_str().unwrap(),
+                dir.join("b").to_str().unwrap(),
+            ],
+        );
+        let b = Store::open(&store_root(dir.join("b")), true).unwrap();
+
+        (a, b)
+    }
+
+    fn decrypt(store: &Store) -> Vec<u8> {
+        store
+            .get_record(Path::new("web.yaml"))
+            .unwrap()
+            .decrypt_and_extract(None)
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn merge_different_attributes() {
+        let dir = tempdir().unwrap();
+        let (a, b) = setup(dir.path());
+
+        let record = b.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("password", Zeroizing::new(b"two".to_vec()))
+            .unwrap();
+        let summary = b.sync(SyncStrategy::Merge, true).unwrap();
+        assert_eq!(summary.pushed, 1);
+
+        let record = a.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("user", Zeroizing::new(b"bob".to_vec()))
+            .unwrap();
+        let summary = a.sync(SyncStrategy::Merge, true).unwrap();
+        assert_eq!(summary.pulled, 1);
+        assert_eq!(summary.merged, vec![PathBuf::from("web.yaml")]);
+        assert_eq!(decrypt(&a), b"user: bob\npassword: two\n");
+
+        let summary = b.sync(SyncStrategy::Rebase, true).unwrap();
+        assert_eq!(summary.pushed, 0);
+        assert_eq!(decrypt(&b), b"user: bob\npassword: two\n");
+        assert!(crate::utils::git::dirty_paths(b.root()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rebase_different_attributes() {
+        let dir = tempdir().unwrap();
+        let (a, b) = setup(dir.path());
+
+        let record = b.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("password", Zeroizing::new(b"two".to_vec()))
+            .unwrap();
+        let _ = b.sync(SyncStrategy::Rebase, true).unwrap();
+
+        let record = a.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("user", Zeroizing::new(b"bob".to_vec()))
+            .unwrap();
+        let summary = a.sync(SyncStrategy::Rebase, true).unwrap();
+        assert_eq!(summary.merged, vec![PathBuf::from("web.yaml")]);
+        assert_eq!(summary.pushed, 1);
+        assert_eq!(decrypt(&a), b"user: bob\npassword: two\n");
+
+        // History is linear after a rebase
+        assert_eq!(
+            git(a.root(), &["rev-list", "--merges", "--count", "HEAD"]),
+            "0"
+        );
+    }
+
+    #[test]
+    fn merge_in_subdirectory() {
+        let dir = tempdir().unwrap();
+        let (a, b) = setup_in(dir.path(), Some("secrets"));
+
+        let record = b.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("password", Zeroizing::new(b"two".to_vec()))
+            .unwrap();
+        let _ = b.sync(SyncStrategy::Merge, true).unwrap();
+
+        let record = a.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("user", Zeroizing::new(b"bob".to_vec()))
+            .unwrap();
+        let summary = a.sync(SyncStrategy::Merge, true).unwrap();
+        assert_eq!(summary.merged, vec![PathBuf::from("web.yaml")]);
+        assert_eq!(decrypt(&a), b"user: bob\npassword: two\n");
+
+        let record = b.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("password", Zeroizing::new(b"three".to_vec()))
+            .unwrap();
+        let record = a.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("user", Zeroizing::new(b"carol".to_vec()))
+            .unwrap();
+        let _ = a.sync(SyncStrategy::Merge, true).unwrap();
+        let summary = b.sync(SyncStrategy::Rebase, true).unwrap();
+        assert_eq!(summary.merged, vec![PathBuf::from("web.yaml")]);
+        assert_eq!(decrypt(&b), b"user: carol\npassword: three\n");
+        assert!(crate::utils::git::dirty_paths(b.root()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn conflicting_attribute_aborts() {
+        let dir = tempdir().unwrap();
+        let (a, b) = setup(dir.path());
+
+        let record = b.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("password", Zeroizing::new(b"two".to_vec()))
+            .unwrap();
+        let _ = b.sync(SyncStrategy::Merge, true).unwrap();
+
+        let record = a.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("password", Zeroizing::new(b"three".to_vec()))
+            .unwrap();
+        let err = a.sync(SyncStrategy::Merge, true).unwrap_err();
+        assert!(format!("{err:?}").contains("`password`"));
+
+        // Nothing is left half merged
+        assert!(crate::utils::git::dirty_paths(a.root()).unwrap().is_empty());
+        assert_eq!(decrypt(&a), b"user: alice\npassword: three\n");
+    }
+
+    #[test]
+    fn auto_push_and_pull() {
+        let dir = tempdir().unwrap();
+        let (a, b) = setup(dir.path());
+        let _ = a
+            .git_operation("Enable auto-push", || {
+                std::fs::write(
+                    a.root().join(super::super::config::CONFIG_FILENAME),
+                    "auto_push = true\n",
+                )
+                .into_diagnostic()?;
+                Ok(vec![super::super::config::CONFIG_FILENAME.into()])
+            })
+            .unwrap();
+        let _ = a.sync(SyncStrategy::Merge, true).unwrap();
+        let _ = b.sync(SyncStrategy::Merge, true).unwrap();
+
+        // Offline stores neither push nor pull
+        let a = Store::open(a.root(), true).unwrap();
+        let record = a.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("password", Zeroizing::new(b"two".to_vec()))
+            .unwrap();
+        assert_eq!(a.count_commits("@{upstream}..HEAD").unwrap(), 1);
+
+        let a = Store::open(a.root(), false).unwrap();
+        let record = a.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("user", Zeroizing::new(b"bob".to_vec()))
+            .unwrap();
+        assert_eq!(a.count_commits("@{upstream}..HEAD").unwrap(), 0);
+
+        // Opening the store fast-forwards it, so that reading gives the latest values
+        let b = Store::open(b.root(), true).unwrap();
+        assert_eq!(decrypt(&b), b"user: alice\npassword: one\n");
+        let b = Store::open(b.root(), false).unwrap();
+        assert_eq!(decrypt(&b), b"user: bob\npassword: two\n");
+        assert_eq!(
+            git(b.root(), &["rev-list", "--merges", "--count", "HEAD"]),
+            "0"
+        );
+    }
+
+    #[test]
+    fn auto_pull_only_fast_forwards() {
+        let dir = tempdir().unwrap();
+        let (a, b) = setup(dir.path());
+
+        let record = a.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("password", Zeroizing::new(b"two".to_vec()))
+            .unwrap();
+        let _ = a.sync(SyncStrategy::Merge, true).unwrap();
+
+        let record = b.get_record(Path::new("web.yaml")).unwrap();
+        record
+            .encrypt_set("user", Zeroizing::new(b"bob".to_vec()))
+            .unwrap();
+
+        // Diverged branches are left for `sync` rather than merged behind the user's back
+        assert!(b.pull_if_behind().is_err());
+        assert_eq!(b.count_commits("@{upstream}..HEAD").unwrap(), 1);
+        assert_eq!(b.count_commits("HEAD..@{upstream}").unwrap(), 1);
+        assert_eq!(decrypt(&b), b"user: bob\npassword: one\n");
+    }
+}
@@ -0,0 +1,308 @@
+use super::Store;
+use crate::utils::{
+    document::{Format, Value},
+    password::CharacterClasses,
+};
+use miette::{Context, IntoDiagnostic, miette};
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+/// Directory (relative to the root of the store) that templates are kept in.
+const TEMPLATES_DIR: &str = ".koishi/templates";
+
+/// The shape of a kind of record, e.g. a website login.
+///
+/// Templates are YAML files in `.koishi/templates` where each key is a field of the record and
+/// its value describes how the field is filled in:
+///
+/// ```yaml
+/// username: {}
+/// password:
+///   generate: password
+///   length: 32
+/// url:
+///   default: https://
+/// otp:
+///   prompt: OTP URL
+///   secret: true
+///   optional: true
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Template {
+    pub(crate) fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Field {
+    /// Key of the field in the record
+    pub(crate) name: String,
+    /// Text shown when prompting for the value, defaults to the name
+    pub(crate) prompt: String,
+    /// Value suggested when prompting
+    pub(crate) default: Option<String>,
+    /// Hide the value while it is typed
+    pub(crate) secret: bool,
+    /// Leave the field out of the record if no value is given
+    pub(crate) optional: bool,
+    /// Generate the value instead of prompting for it
+    pub(crate) generate: Option<Generate>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Generate {
+    Password { length: usize },
+    Passphrase { words: usize },
+}
+
+impl Generate {
+    pub(crate) fn generate(&self) -> miette::Result<Zeroizing<String>> {
+        match self {
+            Self::Password { length } => crate::utils::password::generate_password(
+                *length,
+                &CharacterClasses {
+                    lowercase: true,
+                    uppercase: true,
+                    digits: true,
+                    symbols: true,
+                },
+                false,
+            ),
+            Self::Passphrase { words } => crate::utils::password::generate_passphrase(*words, "-"),
+        }
+    }
+}
+
+impl Store {
+    /// Lists the names of the templates in the store.
+    pub(crate) fn list_templates(&self) -> miette::Result<Vec<String>> {
+        let dir = self.root.join(TEMPLATES_DIR);
+
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .into_diagnostic()
+            .wrap_err(format!("Failed to read `{}`", dir.display()))?
+            .flat_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| Format::from_path(p) == Format::Yaml)
+            .filter_map(|p| Some(p.file_stem()?.to_str()?.to_owned()))
+            .collect();
+        names.sort();
+
+        Ok(names)
+    }
+
+    /// Loads a template by name.
+    ///
+    /// Names must refer to a file directly inside the templates directory.
+    pub(crate) fn template(&self, name: &str) -> miette::Result<Template> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(miette!("`{name}` isn't a valid template name"));
+        }
+
+        let filename = ["yaml", "yml"]
+            .iter()
+            .map(|extension| {
+                self.root
+                    .join(TEMPLATES_DIR)
+                    .join(name)
+                    .with_extension(extension)
+            })
+            .find(|p| p.is_file())
+            .ok_or_else(|| {
+                miette!(
+                    "No template named `{name}` in `{}`",
+                    PathBuf::from(TEMPLATES_DIR).display()
+                )
+            })?;
+
+        let contents = std::fs::read(&filename)
+            .into_diagnostic()
+            .wrap_err(format!("Failed to read `{}`", filename.display()))?;
+
+        Template::parse(&contents).wrap_err(format!("Invalid template `{}`", filename.display()))
+    }
+}
+
+impl Template {
+    fn parse(contents: &[u8]) -> miette::Result<Self> {
+        let Value::Mapping(entries) = Value::parse(Format::Yaml, contents)? else {
+            return Err(miette!("Template must be a mapping of field names"));
+        };
+
+        let fields = entries
+            .into_iter()
+            .map(|(name, spec)| Field::parse(name, spec))
+            .collect::<miette::Result<Vec<_>>>()?;
+
+        if fields.is_empty() {
+            return Err(miette!("Template has no fields"));
+        }
+
+        Ok(Self { fields })
+    }
+}
+
+impl Field {
+    fn parse(name: String, spec: Value) -> miette::Result<Self> {
+        let mut field = Self {
+            prompt: name.clone(),
+            name,
+            default: None,
+            secret: false,
+            optional: false,
+            generate: None,
+        };
+
+        let entries = match spec {
+            Value::Mapping(entries) => entries,
+            Value::Null => Vec::new(),
+            _ => {
+                return Err(miette!(
+                    "Field `{}` must be a mapping of options",
+                    field.name
+                ));
+            }
+        };
+
+        let mut generate = None;
+        let mut length = 24;
+        let mut words = 6;
+
+        for (key, value) in entries {
+            let invalid = || miette!("Invalid value for `{key}` in field `{}`", field.name);
+
+            match (key.as_str(), value) {
+                ("prompt", Value::String(v)) => field.prompt = v,
+                ("default", v) => field.default = Some(v.scalar_to_string().ok_or_else(invalid)?),
+                ("secret", Value::Boolean(v)) => field.secret = v,
+                ("optional", Value::Boolean(v)) => field.optional = v,
+                ("generate", Value::String(v)) => generate = Some(v),
+                ("length", Value::Integer(v)) => {
+                    length = usize::try_from(v).map_err(|_| invalid())?
+                }
+                ("words", Value::Integer(v)) => {
+                    words = usize::try_from(v).map_err(|_| invalid())?
+                }
+                ("prompt" | "secret" | "optional" | "generate" | "length" | "words", _) => {
+                    return Err(invalid());
+                }
+                _ => {
+                    return Err(miette!("Unknown option `{key}` in field `{}`", field.name));
+                }
+            }
+        }
+
+        field.generate = match generate.as_deref() {
+            None => None,
+            Some("password") => Some(Generate::Password { length }),
+            Some("passphrase") => Some(Generate::Passphrase { words }),
+            Some(other) => {
+                return Err(miette!(
+                    "Unknown generator `{other}` in field `{}`, expected `password` or `passphrase`",
+                    field.name
+                ));
+            }
+        };
+
+        Ok(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let template = Template::parse(
+            br#"
+username: {}
+password:
+  generate: password
+  length: 32
+url:
+  default: https://
+otp:
+  prompt: OTP URL
+  secret: true
+  optional: true
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            template
+                .fields
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["username", "password", "url", "otp"]
+        );
+        assert_eq!(template.fields[0].prompt, "username");
+        assert_eq!(
+            template.fields[1].generate,
+            Some(Generate::Password { length: 32 })
+        );
+        assert_eq!(template.fields[2].default.as_deref(), Some("https://"));
+        assert_eq!(template.fields[3].prompt, "OTP URL");
+        assert!(template.fields[3].secret && template.fields[3].optional);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Template::parse(b"").is_err());
+        assert!(Template::parse(b"- username").is_err());
+        assert!(Template::parse(b"username: plain").is_err());
+        assert!(Template::parse(b"username:\n  colour: red").is_err());
+        assert!(Template::parse(b"password:\n  generate: pin").is_err());
+        assert!(Template::parse(b"password:\n  length: -1").is_err());
+    }
+
+    #[test]
+    fn generate() {
+        assert_eq!(
+            Generate::Password { length: 20 }
+                .generate()
+                .unwrap()
+                .chars()
+                .count(),
+            20
+        );
+        assert_eq!(
+            Generate::Passphrase { words: 4 }
+                .generate()
+                .unwrap()
+                .split('-')
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn list_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        crate::utils::test::set_git_config();
+        let store = Store::init(dir.path()).unwrap();
+
+        assert!(store.list_templates().unwrap().is_empty());
+
+        let templates = dir.path().join(TEMPLATES_DIR);
+        std::fs::create_dir_all(&templates).unwrap();
+        std::fs::write(templates.join("website.yaml"), "username: {}\n").unwrap();
+        std::fs::write(templates.join("server.yml"), "host: {}\n").unwrap();
+        std::fs::write(templates.join("notes.txt"), "").unwrap();
+
+        assert_eq!(store.list_templates().unwrap(), vec!["server", "website"]);
+        assert_eq!(store.template("server").unwrap().fields[0].name, "host");
+        assert!(store.template("missing").is_err());
+
+        std::fs::write(dir.path().join(".koishi/outside.yaml"), "username: {}\n").unwrap();
+        assert!(store.template("../outside").is_err());
+        assert!(store.template("..\\outside").is_err());
+        assert!(store.template(".hidden").is_err());
+        assert!(store.template("").is_err());
+    }
+}
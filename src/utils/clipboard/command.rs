@@ -0,0 +1,57 @@
+use miette::{Context, IntoDiagnostic, miette};
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    time::Duration,
+};
+use zeroize::Zeroizing;
+
+/// Copies something to the clipboard by running a command (e.g. `xclip` or `pbcopy`) with the
+/// data on its stdin.
+///
+/// Whether the data can be pasted only once is up to the command.
+/// With a timeout, the clipboard is cleared by running the command again with no input once it
+/// expires.
+pub(super) fn copy(
+    data: Zeroizing<Vec<u8>>,
+    command: &str,
+    timeout: Option<Duration>,
+) -> miette::Result<()> {
+    run(&data, command)?;
+
+    if let Some(timeout) = timeout {
+        let _ = super::countdown(timeout, || Ok(false))?;
+        run(&[], command)?;
+    }
+
+    Ok(())
+}
+
+fn run(data: &[u8], command: &str) -> miette::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .spawn()
+        .into_diagnostic()
+        .wrap_err(format!("Failed to run clipboard command `{command}`"))?;
+
+    {
+        // Dropped at the end of this block, closing stdin so that the command can finish
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| miette!("Failed to open stdin of clipboard command"))?;
+        stdin.write_all(data).into_diagnostic()?;
+    }
+
+    let status = child.wait().into_diagnostic()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(miette!(
+            "Clipboard command `{command}` failed (status {status})"
+        ))
+    }
+}
@@ -0,0 +1,268 @@
+mod command;
+mod osc52;
+mod wayland;
+mod x11;
+
+use miette::miette;
+use serde::Deserialize;
+use std::{
+    io::IsTerminal,
+    time::{Duration, Instant},
+};
+use zeroize::Zeroizing;
+
+/// How often the state of the clipboard is checked while counting down a timeout.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Backend {
+    /// The Wayland clipboard
+    Wayland,
+    /// The X11 `CLIPBOARD` selection
+    X11,
+    /// OSC 52 escape sequences, handled by the terminal emulator (works over SSH)
+    Osc52,
+    /// An external command that is given the data on stdin
+    Command(String),
+}
+
+/// The name of a clipboard backend, as used in the store config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum BackendKind {
+    Wayland,
+    X11,
+    Osc52,
+    Command,
+}
+
+/// Clipboard settings from the store config.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Backend to use instead of detecting one
+    pub(crate) backend: Option<BackendKind>,
+    /// Command that is given the data on stdin, for the `command` backend
+    pub(crate) command: Option<String>,
+    /// Seconds after which copied secrets are withdrawn from the clipboard
+    pub(crate) timeout: Option<u64>,
+}
+
+impl Config {
+    pub(crate) fn validate(&self) -> miette::Result<()> {
+        if self.backend == Some(BackendKind::Command) && self.command.is_none() {
+            Err(miette!(
+                "Clipboard backend is set to `command` but no command is set"
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Selects a clipboard backend.
+///
+/// `KOISHI_CLIPBOARD` can be used to explicitly choose a backend, otherwise a command set in
+/// `KOISHI_CLIPBOARD_COMMAND` is used, followed by the backend set in the store config, followed
+/// by the first backend that appears to be usable in the current session.
+///
+/// Environment variables are looked up with `env`.
+fn select_backend(
+    config: &Config,
+    env: impl Fn(&str) -> Option<String>,
+) -> miette::Result<Backend> {
+    let command = env("KOISHI_CLIPBOARD_COMMAND").filter(|c| !c.is_empty());
+
+    match env("KOISHI_CLIPBOARD").as_deref() {
+        Some("wayland") => Ok(Backend::Wayland),
+        Some("x11") => Ok(Backend::X11),
+        Some("osc52") => Ok(Backend::Osc52),
+        Some("command") => command.map(Backend::Command).ok_or_else(|| {
+            miette!("KOISHI_CLIPBOARD is set to `command` but KOISHI_CLIPBOARD_COMMAND is not set")
+        }),
+        Some(other) if !other.is_empty() => Err(miette!(
+            "Unknown clipboard backend `{other}` in KOISHI_CLIPBOARD, expected `wayland`, `x11`, `osc52` or `command`"
+        )),
+        _ => {
+            if let Some(command) = command {
+                Ok(Backend::Command(command))
+            } else if let Some(backend) = config.backend {
+                match backend {
+                    BackendKind::Wayland => Ok(Backend::Wayland),
+                    BackendKind::X11 => Ok(Backend::X11),
+                    BackendKind::Osc52 => Ok(Backend::Osc52),
+                    BackendKind::Command => {
+                        config.command.clone().map(Backend::Command).ok_or_else(|| {
+                            miette!("Clipboard backend is set to `command` but no command is set")
+                        })
+                    }
+                }
+            } else if env("WAYLAND_DISPLAY").is_some() {
+                Ok(Backend::Wayland)
+            } else if env("DISPLAY").is_some() {
+                Ok(Backend::X11)
+            } else if std::io::stdout().is_terminal() || std::io::stderr().is_terminal() {
+                Ok(Backend::Osc52)
+            } else {
+                Err(miette!(
+                    "No usable clipboard found, set KOISHI_CLIPBOARD or KOISHI_CLIPBOARD_COMMAND"
+                ))
+            }
+        }
+    }
+}
+
+/// Copies something to the clipboard.
+///
+/// Where the backend allows it, the data can be pasted only once and this function blocks until
+/// it has been.
+///
+/// If a timeout is given then the data is withdrawn from the clipboard once it expires, restoring
+/// the previous contents of the clipboard where the backend allows it.
+pub(crate) fn copy(
+    data: Zeroizing<Vec<u8>>,
+    timeout: Option<Duration>,
+    config: &Config,
+) -> miette::Result<()> {
+    match select_backend(config, |name| std::env::var(name).ok())? {
+        Backend::Wayland => wayland::copy(data, timeout),
+        Backend::X11 => x11::copy(data, timeout),
+        Backend::Osc52 => osc52::copy(data, timeout),
+        Backend::Command(command) => command::copy(data, &command, timeout),
+    }
+}
+
+/// Waits until either `poll` reports that the data on offer is no longer available (i.e. it was
+/// pasted or replaced) or the timeout expires, showing a countdown on stderr.
+///
+/// Returns false if the timeout expired.
+fn countdown(
+    timeout: Duration,
+    mut poll: impl FnMut() -> miette::Result<bool>,
+) -> miette::Result<bool> {
+    let deadline = Instant::now() + timeout;
+    let mut shown = None;
+
+    let finished = loop {
+        if poll()? {
+            break true;
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break false;
+        }
+
+        let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+        if shown != Some(seconds) {
+            eprint!("\rClearing clipboard in {seconds}s ");
+            shown = Some(seconds);
+        }
+
+        std::thread::sleep(POLL_INTERVAL.min(remaining));
+    };
+
+    eprintln!();
+
+    Ok(finished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn countdown_expires() {
+        let mut polls = 0;
+        assert!(
+            !countdown(Duration::from_millis(100), || {
+                polls += 1;
+                Ok(false)
+            })
+            .unwrap()
+        );
+        assert!(polls > 1);
+
+        assert!(countdown(Duration::from_secs(10), || Ok(true)).unwrap());
+    }
+
+    #[test]
+    fn backend_selection() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| (*value).to_owned())
+            }
+        };
+        let default = Config::default();
+
+        assert!(select_backend(&default, env(&[("KOISHI_CLIPBOARD", "unknown")])).is_err());
+        assert!(select_backend(&default, env(&[("KOISHI_CLIPBOARD", "command")])).is_err());
+        assert_eq!(
+            select_backend(
+                &default,
+                env(&[
+                    ("KOISHI_CLIPBOARD", "x11"),
+                    ("KOISHI_CLIPBOARD_COMMAND", "cat"),
+                ])
+            )
+            .unwrap(),
+            Backend::X11
+        );
+        assert_eq!(
+            select_backend(
+                &default,
+                env(&[
+                    ("KOISHI_CLIPBOARD_COMMAND", "cat"),
+                    ("WAYLAND_DISPLAY", "wayland-0"),
+                ])
+            )
+            .unwrap(),
+            Backend::Command("cat".into())
+        );
+        assert_eq!(
+            select_backend(&default, env(&[("DISPLAY", ":0")])).unwrap(),
+            Backend::X11
+        );
+
+        let config = Config {
+            backend: Some(BackendKind::Osc52),
+            ..Default::default()
+        };
+        assert_eq!(
+            select_backend(&config, env(&[("WAYLAND_DISPLAY", "wayland-0")])).unwrap(),
+            Backend::Osc52
+        );
+
+        let config = Config {
+            backend: Some(BackendKind::Command),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        assert!(select_backend(&config, env(&[])).is_err());
+    }
+
+    #[test]
+    fn command_backend() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("clipboard");
+        let config = Config {
+            backend: Some(BackendKind::Command),
+            command: Some(format!("cat > {}", output.display())),
+            ..Default::default()
+        };
+
+        copy(Zeroizing::new(b"hunter2".to_vec()), None, &config).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), b"hunter2");
+
+        copy(
+            Zeroizing::new(b"hunter2".to_vec()),
+            Some(Duration::from_millis(100)),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), b"");
+    }
+}
@@ -0,0 +1,44 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+use miette::{Context, IntoDiagnostic};
+use std::{io::Write, time::Duration};
+use zeroize::Zeroizing;
+
+/// Copies something to the clipboard using an OSC 52 escape sequence.
+///
+/// The terminal emulator takes ownership of the data, so it cannot be restricted to being pasted
+/// only once.
+/// With a timeout, the clipboard is cleared once it expires (the previous contents cannot be
+/// restored, as not all terminals allow reading the clipboard).
+pub(super) fn copy(data: Zeroizing<Vec<u8>>, timeout: Option<Duration>) -> miette::Result<()> {
+    write(&data)?;
+
+    if let Some(timeout) = timeout {
+        let _ = super::countdown(timeout, || Ok(false))?;
+        write(&[])?;
+    }
+
+    Ok(())
+}
+
+fn write(data: &[u8]) -> miette::Result<()> {
+    let sequence = Zeroizing::new(format!(
+        "\x1b]52;c;{}\x07",
+        Zeroizing::new(BASE64_STANDARD.encode(data)).as_str()
+    ));
+
+    // tmux only passes escape sequences through to the outer terminal when they are wrapped
+    let sequence = if std::env::var_os("TMUX").is_some() {
+        Zeroizing::new(format!("\x1bPtmux;\x1b{}\x1b\\", sequence.as_str()))
+    } else {
+        sequence
+    };
+
+    let mut tty = std::fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/tty")
+        .into_diagnostic()
+        .wrap_err("Failed to open terminal")?;
+
+    tty.write_all(sequence.as_bytes()).into_diagnostic()?;
+    tty.flush().into_diagnostic()
+}
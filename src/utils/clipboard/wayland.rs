@@ -0,0 +1,148 @@
+use miette::{IntoDiagnostic, miette};
+use std::{io::Read, time::Duration};
+use wl_clipboard_rs::{
+    copy::{self, ClipboardType, MimeType, Seat, ServeRequests, Source},
+    paste,
+};
+use zeroize::Zeroizing;
+
+/// Copies something to the Wayland clipboard, and allow it to be pasted only once.
+///
+/// With a timeout, the data is withdrawn once it expires and the previous contents of the
+/// clipboard are put back. They are left alone if the data was pasted or another application
+/// took over the clipboard first.
+pub(super) fn copy(data: Zeroizing<Vec<u8>>, timeout: Option<Duration>) -> miette::Result<()> {
+    let Some(timeout) = timeout else {
+        return serve_once(data);
+    };
+
+    let previous = previous_contents();
+
+    serve_until(
+        move || serve_once(data),
+        timeout,
+        // Withdrawing the offer causes the server to stop
+        || copy::clear(ClipboardType::Regular, Seat::All).into_diagnostic(),
+        || {
+            let Some(previous) = previous else {
+                return Ok(());
+            };
+
+            // Serves the previous contents from a background process, as the original owner no
+            // longer offers them
+            let mut opts = copy::Options::new();
+            let _ = opts.clipboard(ClipboardType::Regular).trim_newline(false);
+            opts.copy(Source::Bytes(previous.to_vec().into()), MimeType::Text)
+                .into_diagnostic()
+        },
+    )
+}
+
+/// Runs `serve` in the background until it finishes or the timeout expires.
+///
+/// `serve` finishes once its data is pasted or replaced by another application. Only if the
+/// timeout expires while it is still serving is it stopped with `withdraw` and the previous
+/// contents put back with `restore`, as otherwise they would overwrite whatever was copied since.
+fn serve_until(
+    serve: impl FnOnce() -> miette::Result<()> + Send + 'static,
+    timeout: Duration,
+    withdraw: impl FnOnce() -> miette::Result<()>,
+    restore: impl FnOnce() -> miette::Result<()>,
+) -> miette::Result<()> {
+    let server = std::thread::spawn(serve);
+
+    let finished = super::countdown(timeout, || Ok(server.is_finished()))?;
+    // The data may have been taken since the countdown last checked
+    let expired = !finished && !server.is_finished();
+
+    if expired {
+        withdraw()?;
+    }
+
+    server
+        .join()
+        .map_err(|_| miette!("Wayland clipboard thread panicked"))??;
+
+    if expired {
+        restore()?;
+    }
+
+    Ok(())
+}
+
+fn serve_once(data: Zeroizing<Vec<u8>>) -> miette::Result<()> {
+    let source = Source::Bytes(data.to_vec().into());
+
+    let mut opts = copy::Options::new();
+    let _ = opts
+        .serve_requests(ServeRequests::Only(1))
+        .foreground(true)
+        .clipboard(ClipboardType::Regular)
+        .trim_newline(false)
+        .seat(Default::default());
+
+    let copy = opts
+        .prepare_copy(source, MimeType::Text)
+        .into_diagnostic()?;
+
+    copy.serve().into_diagnostic()?;
+
+    Ok(())
+}
+
+/// Reads the current text contents of the clipboard, if there are any.
+fn previous_contents() -> Option<Zeroizing<Vec<u8>>> {
+    let (mut pipe, _) = paste::get_contents(
+        paste::ClipboardType::Regular,
+        paste::Seat::Unspecified,
+        paste::MimeType::Text,
+    )
+    .ok()?;
+
+    let mut contents = Zeroizing::new(Vec::new());
+    let _ = pipe.read_to_end(&mut contents).ok()?;
+
+    Some(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    /// Runs `serve_until`, returning whether the previous contents were restored.
+    fn restored(
+        serve: impl FnOnce(mpsc::Receiver<()>) -> miette::Result<()> + Send + 'static,
+    ) -> bool {
+        let (withdraw, withdrawn) = mpsc::channel();
+        let mut restored = false;
+
+        serve_until(
+            move || serve(withdrawn),
+            Duration::from_millis(200),
+            || withdraw.send(()).into_diagnostic(),
+            || {
+                restored = true;
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        restored
+    }
+
+    #[test]
+    fn restore_after_timeout() {
+        // Nothing pastes the data, so it is only withdrawn once the timeout expires
+        assert!(restored(|withdrawn| withdrawn.recv().into_diagnostic()));
+    }
+
+    #[test]
+    fn no_restore_once_taken() {
+        // Another application takes over the clipboard (or the data is pasted) before the timeout
+        assert!(!restored(|_| {
+            std::thread::sleep(Duration::from_millis(20));
+            Ok(())
+        }));
+    }
+}
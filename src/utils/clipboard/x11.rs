@@ -0,0 +1,198 @@
+use miette::{Context, IntoDiagnostic, miette};
+use std::time::Duration;
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        Event,
+        xproto::{
+            AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode,
+            SELECTION_NOTIFY_EVENT, SelectionNotifyEvent, SelectionRequestEvent, WindowClass,
+        },
+    },
+    wrapper::ConnectionExt as _,
+};
+use zeroize::Zeroizing;
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        CLIPBOARD,
+        TARGETS,
+        UTF8_STRING,
+        TEXT,
+    }
+}
+
+/// Copies something to the X11 clipboard, and allow it to be pasted only once.
+///
+/// X11 has no clipboard manager that data is handed over to, so this blocks while serving the
+/// selection until it has been pasted or another application takes ownership of the clipboard.
+///
+/// With a timeout, the selection is given up once it expires.
+/// The previous contents of the clipboard cannot be restored, as they were only ever held by the
+/// application that owned the selection before.
+pub(super) fn copy(data: Zeroizing<Vec<u8>>, timeout: Option<Duration>) -> miette::Result<()> {
+    let (conn, screen_num) = x11rb::connect(None)
+        .into_diagnostic()
+        .wrap_err("Failed to connect to X11 display")?;
+
+    let atoms = Atoms::new(&conn)
+        .into_diagnostic()?
+        .reply()
+        .into_diagnostic()?;
+
+    // An invisible window is needed to own the selection
+    let window = conn.generate_id().into_diagnostic()?;
+    let _ = conn
+        .create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            conn.setup().roots[screen_num].root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )
+        .into_diagnostic()?;
+
+    let _ = conn
+        .set_selection_owner(window, atoms.CLIPBOARD, x11rb::CURRENT_TIME)
+        .into_diagnostic()?;
+
+    let owner = conn
+        .get_selection_owner(atoms.CLIPBOARD)
+        .into_diagnostic()?
+        .reply()
+        .into_diagnostic()?
+        .owner;
+    if owner != window {
+        return Err(miette!("Failed to take ownership of the X11 clipboard"));
+    }
+
+    conn.flush().into_diagnostic()?;
+
+    let Some(timeout) = timeout else {
+        loop {
+            let event = conn.wait_for_event().into_diagnostic()?;
+            if handle_event(&conn, &atoms, event, &data)? {
+                return Ok(());
+            }
+        }
+    };
+
+    let finished = super::countdown(timeout, || {
+        while let Some(event) = conn.poll_for_event().into_diagnostic()? {
+            if handle_event(&conn, &atoms, event, &data)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    })?;
+
+    if !finished {
+        let _ = conn
+            .set_selection_owner(x11rb::NONE, atoms.CLIPBOARD, x11rb::CURRENT_TIME)
+            .into_diagnostic()?;
+        conn.flush().into_diagnostic()?;
+    }
+
+    Ok(())
+}
+
+/// Handles an event for the window owning the selection, returning true once the data is no
+/// longer on offer.
+fn handle_event(
+    conn: &impl Connection,
+    atoms: &Atoms,
+    event: Event,
+    data: &[u8],
+) -> miette::Result<bool> {
+    match event {
+        Event::SelectionRequest(request) => respond(conn, atoms, &request, data),
+        // Another application has taken over the clipboard
+        Event::SelectionClear(_) => Ok(true),
+        _ => Ok(false),
+    }
+}
+
+/// Responds to a request for the contents of the clipboard, returning true if the data was sent.
+fn respond(
+    conn: &impl Connection,
+    atoms: &Atoms,
+    request: &SelectionRequestEvent,
+    data: &[u8],
+) -> miette::Result<bool> {
+    // Obsolete clients may not specify a property to write to
+    let property = if request.property == x11rb::NONE {
+        request.target
+    } else {
+        request.property
+    };
+
+    // Data larger than a single request would need the INCR protocol, which is not worth
+    // supporting for secrets
+    let max_length = conn.maximum_request_bytes().saturating_sub(32);
+
+    let (property, sent) = if request.target == atoms.TARGETS {
+        let _ = conn
+            .change_property32(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                AtomEnum::ATOM,
+                &[
+                    atoms.TARGETS,
+                    atoms.UTF8_STRING,
+                    atoms.TEXT,
+                    AtomEnum::STRING.into(),
+                ],
+            )
+            .into_diagnostic()?;
+        (property, false)
+    } else if [atoms.UTF8_STRING, atoms.TEXT, AtomEnum::STRING.into()].contains(&request.target)
+        && data.len() <= max_length
+    {
+        let property_type = if request.target == atoms.TEXT {
+            atoms.UTF8_STRING
+        } else {
+            request.target
+        };
+
+        let _ = conn
+            .change_property8(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                property_type,
+                data,
+            )
+            .into_diagnostic()?;
+        (property, true)
+    } else {
+        (x11rb::NONE, false)
+    };
+
+    let _ = conn
+        .send_event(
+            false,
+            request.requestor,
+            EventMask::NO_EVENT,
+            SelectionNotifyEvent {
+                response_type: SELECTION_NOTIFY_EVENT,
+                sequence: 0,
+                time: request.time,
+                requestor: request.requestor,
+                selection: request.selection,
+                target: request.target,
+                property,
+            },
+        )
+        .into_diagnostic()?;
+
+    conn.flush().into_diagnostic()?;
+
+    Ok(sent)
+}
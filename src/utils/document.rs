@@ -0,0 +1,609 @@
+use miette::{IntoDiagnostic, miette};
+use saphyr::{LoadableYamlNode, Scalar, Yaml, YamlEmitter};
+use serde::Deserialize;
+use std::{borrow::Cow, path::Path};
+use zeroize::Zeroize;
+
+/// File formats that a record may be stored in, as determined by SOPS from the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    Yaml,
+    Json,
+    Dotenv,
+    Ini,
+    Binary,
+}
+
+impl Format {
+    pub(crate) fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => Self::Yaml,
+            Some("json") => Self::Json,
+            Some("env") => Self::Dotenv,
+            Some("ini") => Self::Ini,
+            _ => Self::Binary,
+        }
+    }
+
+    /// The file extension given to new records in this format, `None` for binary records.
+    pub(crate) fn extension(self) -> Option<&'static str> {
+        match self {
+            Self::Yaml => Some("yaml"),
+            Self::Json => Some("json"),
+            Self::Dotenv => Some("env"),
+            Self::Ini => Some("ini"),
+            Self::Binary => None,
+        }
+    }
+}
+
+/// A structured document, i.e. the tree of values held in a record.
+///
+/// Mappings preserve the order of their keys, as SOPS relies on this ordering when computing the
+/// MAC of a file.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Mapping(Vec<(String, Value)>),
+    Sequence(Vec<Value>),
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Null,
+}
+
+impl Zeroize for Value {
+    fn zeroize(&mut self) {
+        match self {
+            Self::Mapping(items) => {
+                for (k, v) in items.iter_mut() {
+                    k.zeroize();
+                    v.zeroize();
+                }
+                items.clear();
+            }
+            Self::Sequence(items) => {
+                for v in items.iter_mut() {
+                    v.zeroize();
+                }
+                items.clear();
+            }
+            Self::String(s) => s.zeroize(),
+            Self::Integer(i) => i.zeroize(),
+            Self::Float(f) => f.zeroize(),
+            Self::Boolean(b) => b.zeroize(),
+            Self::Null => {}
+        }
+    }
+}
+
+/// An element of a path into a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PathElement {
+    Key(String),
+    Index(usize),
+}
+
+impl Value {
+    /// Parses a document in a given format.
+    pub(crate) fn parse(format: Format, data: &[u8]) -> miette::Result<Self> {
+        match format {
+            Format::Yaml => {
+                let data = std::str::from_utf8(data).into_diagnostic()?;
+                let docs = Yaml::load_from_str(data).into_diagnostic()?;
+                match docs.first() {
+                    Some(doc) => Self::from_yaml(doc),
+                    None => Ok(Self::Mapping(Vec::new())),
+                }
+            }
+            Format::Json => {
+                let value: serde_json::Value = serde_json::from_slice(data).into_diagnostic()?;
+                Self::from_json(value)
+            }
+            Format::Binary => Ok(Self::Mapping(vec![(
+                "data".into(),
+                Self::String(
+                    String::from_utf8(data.to_vec())
+                        .into_diagnostic()
+                        .map_err(|_| miette!("Binary data is not valid UTF-8"))?,
+                ),
+            )])),
+            Format::Dotenv | Format::Ini => Err(miette!("Unsupported document format: {format:?}")),
+        }
+    }
+
+    /// Serialises a document in a given format.
+    pub(crate) fn emit(&self, format: Format) -> miette::Result<String> {
+        match format {
+            Format::Yaml => {
+                let mut out = String::new();
+                let mut emitter = YamlEmitter::new(&mut out);
+                emitter.multiline_strings(true);
+                emitter
+                    .dump(&self.to_yaml())
+                    .map_err(|e| miette!("Failed to emit YAML: {e:?}"))?;
+
+                let mut out = out
+                    .strip_prefix("---\n")
+                    .map(|s| s.to_owned())
+                    .unwrap_or(out);
+                out.push('\n');
+                Ok(out)
+            }
+            Format::Json => {
+                let mut out = serde_json::to_string_pretty(&self.to_json()).into_diagnostic()?;
+                out.push('\n');
+                Ok(out)
+            }
+            Format::Binary => match self.get(&[PathElement::Key("data".into())]) {
+                Some(Self::String(data)) => Ok(data.clone()),
+                _ => Err(miette!("Binary document does not contain a `data` string")),
+            },
+            Format::Dotenv | Format::Ini => Err(miette!("Unsupported document format: {format:?}")),
+        }
+    }
+
+    /// Gets the string representation of a scalar value, or `None` for collections.
+    pub(crate) fn scalar_to_string(&self) -> Option<String> {
+        match self {
+            Self::String(s) => Some(s.clone()),
+            Self::Integer(i) => Some(i.to_string()),
+            Self::Float(f) => Some(f.to_string()),
+            Self::Boolean(b) => Some(b.to_string()),
+            Self::Null => Some(String::new()),
+            Self::Mapping(_) | Self::Sequence(_) => None,
+        }
+    }
+
+    /// Lists every scalar value in the document, along with its slash delimited path.
+    ///
+    /// Sequence elements are addressed by their index, e.g. `urls/0`.
+    pub(crate) fn leaves(&self) -> Vec<(String, &Self)> {
+        let mut leaves = Vec::new();
+        self.collect_leaves(String::new(), &mut leaves);
+        leaves
+    }
+
+    fn collect_leaves<'a>(&'a self, path: String, leaves: &mut Vec<(String, &'a Self)>) {
+        let child = |name: &str| {
+            if path.is_empty() {
+                name.to_owned()
+            } else {
+                format!("{path}/{name}")
+            }
+        };
+
+        match self {
+            Self::Mapping(items) => {
+                for (k, v) in items {
+                    v.collect_leaves(child(k), leaves);
+                }
+            }
+            Self::Sequence(items) => {
+                for (idx, v) in items.iter().enumerate() {
+                    v.collect_leaves(child(&idx.to_string()), leaves);
+                }
+            }
+            _ => leaves.push((path, self)),
+        }
+    }
+
+    /// Gets the value at a given path.
+    pub(crate) fn get(&self, path: &[PathElement]) -> Option<&Self> {
+        match path.split_first() {
+            None => Some(self),
+            Some((PathElement::Key(key), rest)) => match self {
+                Self::Mapping(items) => items
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .and_then(|(_, v)| v.get(rest)),
+                _ => None,
+            },
+            Some((PathElement::Index(idx), rest)) => match self {
+                Self::Sequence(items) => items.get(*idx).and_then(|v| v.get(rest)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Sets the value at a given path, creating any missing mappings along the way.
+    pub(crate) fn set(&mut self, path: &[PathElement], value: Self) -> miette::Result<()> {
+        match path.split_first() {
+            None => {
+                *self = value;
+                Ok(())
+            }
+            Some((PathElement::Key(key), rest)) => match self {
+                Self::Mapping(items) => {
+                    match items.iter_mut().find(|(k, _)| k == key) {
+                        Some((_, v)) => v.set(rest, value)?,
+                        None => {
+                            let mut v = Self::Mapping(Vec::new());
+                            v.set(rest, value)?;
+                            items.push((key.clone(), v));
+                        }
+                    }
+                    Ok(())
+                }
+                _ => Err(miette!(
+                    "Cannot set key `{key}` on a value that is not a mapping"
+                )),
+            },
+            Some((PathElement::Index(idx), rest)) => match self {
+                Self::Sequence(items) => {
+                    if *idx == items.len() {
+                        let mut v = Self::Mapping(Vec::new());
+                        v.set(rest, value)?;
+                        items.push(v);
+                        Ok(())
+                    } else {
+                        match items.get_mut(*idx) {
+                            Some(v) => v.set(rest, value),
+                            None => Err(miette!("Index {idx} is out of bounds")),
+                        }
+                    }
+                }
+                _ => Err(miette!("Cannot index a value that is not a sequence")),
+            },
+        }
+    }
+
+    /// Removes the value at a given path, returning it if it existed.
+    pub(crate) fn remove(&mut self, path: &[PathElement]) -> Option<Self> {
+        match path.split_first() {
+            None => None,
+            Some((PathElement::Key(key), [])) => match self {
+                Self::Mapping(items) => items
+                    .iter()
+                    .position(|(k, _)| k == key)
+                    .map(|i| items.remove(i).1),
+                _ => None,
+            },
+            Some((PathElement::Index(idx), [])) => match self {
+                Self::Sequence(items) if *idx < items.len() => Some(items.remove(*idx)),
+                _ => None,
+            },
+            Some((PathElement::Key(key), rest)) => match self {
+                Self::Mapping(items) => items
+                    .iter_mut()
+                    .find(|(k, _)| k == key)
+                    .and_then(|(_, v)| v.remove(rest)),
+                _ => None,
+            },
+            Some((PathElement::Index(idx), rest)) => match self {
+                Self::Sequence(items) => items.get_mut(*idx).and_then(|v| v.remove(rest)),
+                _ => None,
+            },
+        }
+    }
+
+    fn from_yaml(yaml: &Yaml) -> miette::Result<Self> {
+        Ok(match yaml {
+            Yaml::Mapping(mapping) => Self::Mapping(
+                mapping
+                    .iter()
+                    .map(|(k, v)| {
+                        let k = k
+                            .as_str()
+                            .ok_or_else(|| miette!("Only string keys are supported"))?;
+                        Ok((k.to_owned(), Self::from_yaml(v)?))
+                    })
+                    .collect::<miette::Result<_>>()?,
+            ),
+            Yaml::Sequence(items) => Self::Sequence(
+                items
+                    .iter()
+                    .map(Self::from_yaml)
+                    .collect::<miette::Result<_>>()?,
+            ),
+            Yaml::Value(Scalar::String(s)) => Self::String(s.to_string()),
+            Yaml::Value(Scalar::Integer(i)) => Self::Integer(*i),
+            Yaml::Value(Scalar::FloatingPoint(f)) => Self::Float(f.into_inner()),
+            Yaml::Value(Scalar::Boolean(b)) => Self::Boolean(*b),
+            Yaml::Value(Scalar::Null) => Self::Null,
+            Yaml::Representation(s, _, _) => Self::String(s.to_string()),
+            Yaml::Tagged(_, node) => Self::from_yaml(node)?,
+            Yaml::Alias(_) | Yaml::BadValue => {
+                return Err(miette!("Unsupported YAML node"));
+            }
+        })
+    }
+
+    fn to_yaml(&self) -> Yaml<'static> {
+        match self {
+            Self::Mapping(items) => Yaml::Mapping(
+                items
+                    .iter()
+                    .map(|(k, v)| {
+                        (
+                            Yaml::Value(Scalar::String(Cow::Owned(k.clone()))),
+                            v.to_yaml(),
+                        )
+                    })
+                    .collect(),
+            ),
+            Self::Sequence(items) => Yaml::Sequence(items.iter().map(Self::to_yaml).collect()),
+            Self::String(s) => Yaml::Value(Scalar::String(Cow::Owned(s.clone()))),
+            Self::Integer(i) => Yaml::Value(Scalar::Integer(*i)),
+            Self::Float(f) => Yaml::Value(Scalar::FloatingPoint((*f).into())),
+            Self::Boolean(b) => Yaml::Value(Scalar::Boolean(*b)),
+            Self::Null => Yaml::Value(Scalar::Null),
+        }
+    }
+
+    fn from_json(json: serde_json::Value) -> miette::Result<Self> {
+        Ok(match json {
+            serde_json::Value::Object(map) => Self::Mapping(
+                map.into_iter()
+                    .map(|(k, v)| Ok((k, Self::from_json(v)?)))
+                    .collect::<miette::Result<_>>()?,
+            ),
+            serde_json::Value::Array(items) => Self::Sequence(
+                items
+                    .into_iter()
+                    .map(Self::from_json)
+                    .collect::<miette::Result<_>>()?,
+            ),
+            serde_json::Value::String(s) => Self::String(s),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Self::Integer(i),
+                None => Self::Float(
+                    n.as_f64()
+                        .ok_or_else(|| miette!("Unsupported JSON number: {n}"))?,
+                ),
+            },
+            serde_json::Value::Bool(b) => Self::Boolean(b),
+            serde_json::Value::Null => Self::Null,
+        })
+    }
+
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        match self {
+            Self::Mapping(items) => serde_json::Value::Object(
+                items
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.to_json()))
+                    .collect(),
+            ),
+            Self::Sequence(items) => {
+                serde_json::Value::Array(items.iter().map(Self::to_json).collect())
+            }
+            Self::String(s) => serde_json::Value::String(s.clone()),
+            Self::Integer(i) => serde_json::Value::from(*i),
+            Self::Float(f) => serde_json::Value::from(*f),
+            Self::Boolean(b) => serde_json::Value::Bool(*b),
+            Self::Null => serde_json::Value::Null,
+        }
+    }
+}
+
+/// Merges two versions of a document that have diverged from a common base.
+///
+/// Mappings are merged key by key, any other value is taken as a whole from whichever version
+/// changed it. `None` means that the document (or value) is absent in that version.
+/// Values that were changed differently in both versions cannot be merged, their paths are
+/// returned as the error.
+pub(crate) fn merge3(
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+) -> Result<Option<Value>, Vec<String>> {
+    let mut conflicts = Vec::new();
+    let merged = merge3_at("", base, ours, theirs, &mut conflicts);
+
+    if conflicts.is_empty() {
+        Ok(merged)
+    } else {
+        Err(conflicts)
+    }
+}
+
+fn merge3_at(
+    path: &str,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<String>,
+) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+
+    match (ours, theirs) {
+        (Some(Value::Mapping(ours)), Some(Value::Mapping(theirs))) => {
+            let base = match base {
+                Some(Value::Mapping(base)) => base.as_slice(),
+                _ => &[],
+            };
+            fn get<'a>(items: &'a [(String, Value)], key: &str) -> Option<&'a Value> {
+                items.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+            }
+
+            // Keep the order of our keys, followed by any keys only they have
+            let keys = ours.iter().map(|(k, _)| k).chain(
+                theirs
+                    .iter()
+                    .map(|(k, _)| k)
+                    .filter(|k| get(ours, k).is_none()),
+            );
+
+            let mut items = Vec::new();
+            for key in keys {
+                let child = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}/{key}")
+                };
+
+                if let Some(value) = merge3_at(
+                    &child,
+                    get(base, key),
+                    get(ours, key),
+                    get(theirs, key),
+                    conflicts,
+                ) {
+                    items.push((key.clone(), value));
+                }
+            }
+
+            Some(Value::Mapping(items))
+        }
+        _ => {
+            conflicts.push(path.to_owned());
+            ours.cloned()
+        }
+    }
+}
+
+/// Parses a SOPS style path selector (e.g. `["foo"]["bar"][0]`).
+pub(crate) fn parse_selector(selector: &str) -> miette::Result<Vec<PathElement>> {
+    let mut path = Vec::new();
+    let mut rest = selector.trim();
+
+    while !rest.is_empty() {
+        let inner = rest
+            .strip_prefix('[')
+            .ok_or_else(|| miette!("Invalid selector `{selector}`: expected `[`"))?;
+
+        if let Some(inner) = inner.strip_prefix('"') {
+            let end = inner
+                .find("\"]")
+                .ok_or_else(|| miette!("Invalid selector `{selector}`: unterminated key"))?;
+            path.push(PathElement::Key(inner[..end].to_owned()));
+            rest = &inner[end + 2..];
+        } else {
+            let end = inner
+                .find(']')
+                .ok_or_else(|| miette!("Invalid selector `{selector}`: unterminated index"))?;
+            let idx = inner[..end].trim().parse::<usize>().map_err(|_| {
+                miette!(
+                    "Invalid selector `{selector}`: `{}` is not an index",
+                    &inner[..end]
+                )
+            })?;
+            path.push(PathElement::Index(idx));
+            rest = &inner[end + 1..];
+        }
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(Format::from_path(Path::new("a/b.yaml")), Format::Yaml);
+        assert_eq!(Format::from_path(Path::new("a/b.yml")), Format::Yaml);
+        assert_eq!(Format::from_path(Path::new("a/b.json")), Format::Json);
+        assert_eq!(Format::from_path(Path::new("a/b.env")), Format::Dotenv);
+        assert_eq!(Format::from_path(Path::new("a/b")), Format::Binary);
+    }
+
+    #[test]
+    fn yaml_roundtrip() {
+        let yaml = "username: alice\npassword: hunter2\nnested:\n  port: 22\n  enabled: true\n";
+        let value = Value::parse(Format::Yaml, yaml.as_bytes()).unwrap();
+        assert_eq!(value.emit(Format::Yaml).unwrap(), yaml);
+    }
+
+    #[test]
+    fn json_preserves_order() {
+        let json = r#"{"z": "1", "a": {"y": 2, "b": [true, null]}}"#;
+        let value = Value::parse(Format::Json, json.as_bytes()).unwrap();
+        let out = value.emit(Format::Json).unwrap();
+        assert!(out.find("\"z\"").unwrap() < out.find("\"a\"").unwrap());
+        assert_eq!(Value::parse(Format::Json, out.as_bytes()).unwrap(), value);
+    }
+
+    #[test]
+    fn get_set_remove() {
+        let mut value = Value::Mapping(Vec::new());
+        let path = parse_selector(r#"["a"]["b"]"#).unwrap();
+
+        value.set(&path, Value::String("c".into())).unwrap();
+        assert_eq!(value.get(&path), Some(&Value::String("c".into())));
+
+        assert_eq!(value.remove(&path), Some(Value::String("c".into())));
+        assert_eq!(value.get(&path), None);
+    }
+
+    #[test]
+    fn leaves() {
+        let yaml = "username: alice\nurls:\n  - a\n  - b\nnested:\n  port: 22\n";
+        let value = Value::parse(Format::Yaml, yaml.as_bytes()).unwrap();
+
+        let leaves: Vec<_> = value
+            .leaves()
+            .into_iter()
+            .map(|(path, v)| (path, v.scalar_to_string().unwrap()))
+            .collect();
+
+        assert_eq!(
+            leaves,
+            vec![
+                ("username".into(), "alice".into()),
+                ("urls/0".into(), "a".into()),
+                ("urls/1".into(), "b".into()),
+                ("nested/port".into(), "22".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_different_keys() {
+        let parse = |yaml: &str| Value::parse(Format::Yaml, yaml.as_bytes()).unwrap();
+
+        let base = parse("user: alice\npassword: one\nold: x\n");
+        let ours = parse("user: alice\npassword: two\nold: x\n");
+        let theirs = parse("user: bob\npassword: one\nnew: y\n");
+
+        assert_eq!(
+            merge3(Some(&base), Some(&ours), Some(&theirs)).unwrap(),
+            Some(parse("user: bob\npassword: two\nnew: y\n"))
+        );
+    }
+
+    #[test]
+    fn merge_conflicting_keys() {
+        let parse = |yaml: &str| Value::parse(Format::Yaml, yaml.as_bytes()).unwrap();
+
+        let base = parse("a: 1\nnested:\n  b: 1\n");
+        let ours = parse("a: 2\nnested:\n  b: 2\n");
+        let theirs = parse("a: 3\nnested:\n  b: 2\n");
+
+        assert_eq!(
+            merge3(Some(&base), Some(&ours), Some(&theirs)).unwrap_err(),
+            vec!["a"]
+        );
+    }
+
+    #[test]
+    fn merge_deleted_document() {
+        let value = Value::String("a".into());
+        let changed = Value::String("b".into());
+
+        assert_eq!(merge3(Some(&value), None, Some(&value)).unwrap(), None);
+        assert!(merge3(Some(&value), None, Some(&changed)).is_err());
+    }
+
+    #[test]
+    fn selector_parsing() {
+        assert_eq!(
+            parse_selector(r#"["foo"][2]["bar"]"#).unwrap(),
+            vec![
+                PathElement::Key("foo".into()),
+                PathElement::Index(2),
+                PathElement::Key("bar".into())
+            ]
+        );
+        assert!(parse_selector(r#"["foo""#).is_err());
+        assert!(parse_selector("foo").is_err());
+    }
+}
@@ -1,5 +1,14 @@
+use gix::{
+    Repository,
+    bstr::{BStr, BString, ByteSlice},
+    index::entry::{Flags, Mode, Stat},
+    object::tree::EntryKind,
+};
 use miette::{Context, IntoDiagnostic, miette};
-use std::path::Path;
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) enum GitOperationResult {
@@ -9,72 +18,912 @@ pub(crate) enum GitOperationResult {
 
 /// Performs an operation that will modify files, committing those files to a Git repository only
 /// when the operation succeeds.
-pub(crate) fn git_operation<F: Fn() -> miette::Result<()>>(
+///
+/// The operation returns the paths (relative to `repo_dir`) that it touched.
+/// Only changes to these paths (or to files under them, in the case of directories) are committed,
+/// any other changes in the working tree are left as they are.
+/// Commits are made with gix, unless the repository signs commits or has commit hooks, in which
+/// case they are made with the `git` executable so that these still apply.
+pub(crate) fn git_operation<F: Fn() -> miette::Result<Vec<PathBuf>>>(
     repo_dir: &Path,
     commit_msg: &str,
     op: F,
 ) -> miette::Result<GitOperationResult> {
-    // TODO: replace this with use of gix
+    let (repo, prefix) = open(repo_dir)?;
 
-    if !is_clean(repo_dir)? {
-        return Err(miette!(
-            "Git repository at `{}` is not clean. Please commit or stash your changes.",
+    let touched = op()?;
+    if touched.is_empty() {
+        return Ok(GitOperationResult::NoChanges);
+    }
+
+    let pathspecs = touched
+        .iter()
+        .map(|path| literal_pathspec(&prefix.join(path)))
+        .collect();
+
+    let changed = changed_paths(&repo, pathspecs)?;
+    if changed.is_empty() {
+        Ok(GitOperationResult::NoChanges)
+    } else {
+        let result = if needs_git_cli(&repo) {
+            commit_paths_with_cli(&repo, &changed, commit_msg)
+        } else {
+            commit_paths(&repo, &changed, commit_msg)
+        };
+        result.wrap_err(format!(
+            "Failed to commit changes to `{}`",
             repo_dir.display()
-        ));
+        ))?;
+        Ok(GitOperationResult::Commit)
     }
+}
+
+/// Lists the paths (relative to `repo_dir`) that have staged or unstaged changes, or that are
+/// untracked.
+pub(crate) fn dirty_paths(repo_dir: &Path) -> miette::Result<Vec<PathBuf>> {
+    let (repo, prefix) = open(repo_dir)?;
+
+    let pathspecs = if prefix.as_os_str().is_empty() {
+        Vec::new()
+    } else {
+        vec![literal_pathspec(&prefix)]
+    };
+
+    Ok(changed_paths(&repo, pathspecs)?
+        .iter()
+        .filter_map(|path| {
+            gix::path::from_bstr(path.as_bstr())
+                .strip_prefix(&prefix)
+                .ok()
+                .map(Path::to_path_buf)
+        })
+        .collect())
+}
+
+/// Finds the time of the most recent commit that changed each of the given paths (relative to
+/// `repo_dir`).
+///
+/// Paths that have never been committed are not included in the result.
+pub(crate) fn last_modified(
+    repo_dir: &Path,
+    paths: &[PathBuf],
+) -> miette::Result<HashMap<PathBuf, jiff::Timestamp>> {
+    let (repo, prefix) = open(repo_dir)?;
 
-    match op() {
-        Ok(_) => {
-            if is_clean(repo_dir)? {
-                Ok(GitOperationResult::NoChanges)
+    let mut result = HashMap::new();
+    let mut remaining: Vec<&PathBuf> = paths.iter().collect();
+
+    walk_history(&repo, |_, time, tree, parent_trees| {
+        let mut unchanged = Vec::new();
+        for path in std::mem::take(&mut remaining) {
+            let (current, previous) = entry_ids(tree, parent_trees, &prefix.join(path))?;
+
+            if current.is_some() && !previous.contains(&current) {
+                let _ = result.insert(path.clone(), time);
             } else {
-                // Stage all changes (this should only be those changed as a result of `op()`, however
-                // this is not a perfectly clean test)
-                run_git_command(repo_dir, &["add", "."]).wrap_err("Failed to stage file")?;
+                unchanged.push(path);
+            }
+        }
+        remaining = unchanged;
+
+        Ok(!remaining.is_empty())
+    })?;
+
+    Ok(result)
+}
+
+/// Finds the time of the most recent commit that changed each part of each of the given paths
+/// (relative to `repo_dir`).
+///
+/// `parts` splits the contents of a file into named parts, a part is considered to have changed
+/// whenever its value differs from that in every parent of a commit.
+/// Only parts that exist in the file at `HEAD` are included in the result.
+pub(crate) fn last_modified_parts<F>(
+    repo_dir: &Path,
+    paths: &[PathBuf],
+    parts: F,
+) -> miette::Result<HashMap<PathBuf, HashMap<String, jiff::Timestamp>>>
+where
+    F: Fn(&Path, &[u8]) -> HashMap<String, String>,
+{
+    let (repo, prefix) = open(repo_dir)?;
+
+    let mut result: HashMap<PathBuf, HashMap<String, jiff::Timestamp>> = HashMap::new();
+    let mut cache: HashMap<gix::ObjectId, HashMap<String, String>> = HashMap::new();
+
+    let mut parts_of =
+        |path: &Path, id: Option<gix::ObjectId>| -> miette::Result<HashMap<String, String>> {
+            let Some(id) = id else {
+                return Ok(HashMap::new());
+            };
+            Ok(match cache.entry(id) {
+                Entry::Occupied(entry) => entry.get().clone(),
+                Entry::Vacant(entry) => {
+                    let data = repo.find_object(id).into_diagnostic()?.detach().data;
+                    entry.insert(parts(path, &data)).clone()
+                }
+            })
+        };
 
-                // Commit with the supplied message
-                run_git_command(repo_dir, &["commit", "-m", commit_msg])
-                    .wrap_err("Failed to commit changes")?;
+    // The parts that have not yet been found to change, for each path
+    let mut remaining: Vec<(&PathBuf, Vec<String>)> = match repo.head_tree() {
+        Ok(tree) => paths
+            .iter()
+            .map(|path| {
+                let id = tree
+                    .lookup_entry_by_path(prefix.join(path))
+                    .into_diagnostic()?
+                    .map(|e| e.object_id());
+                Ok((path, parts_of(path, id)?.into_keys().collect()))
+            })
+            .collect::<miette::Result<_>>()?,
+        // Nothing has been committed yet
+        Err(_) => return Ok(result),
+    };
 
-                Ok(GitOperationResult::Commit)
+    walk_history(&repo, |_, time, tree, parent_trees| {
+        for (path, keys) in &mut remaining {
+            let (current, previous) = entry_ids(tree, parent_trees, &prefix.join(&**path))?;
+            if previous.contains(&current) {
+                continue;
             }
+
+            let current = parts_of(path, current)?;
+            let previous = previous
+                .into_iter()
+                .map(|id| parts_of(path, id))
+                .collect::<miette::Result<Vec<_>>>()?;
+
+            keys.retain(|key| {
+                let value = current.get(key);
+                if value.is_some() && previous.iter().all(|parts| parts.get(key) != value) {
+                    let _ = result
+                        .entry(path.to_path_buf())
+                        .or_default()
+                        .insert(key.clone(), time);
+                    false
+                } else {
+                    true
+                }
+            });
         }
-        Err(e) => Err(e),
-    }
+        remaining.retain(|(_, keys)| !keys.is_empty());
+
+        Ok(!remaining.is_empty())
+    })?;
+
+    Ok(result)
+}
+
+/// A commit in the history of a path.
+#[derive(Debug)]
+pub(crate) struct LogEntry {
+    /// Abbreviated commit ID
+    pub(crate) id: String,
+    pub(crate) time: jiff::Timestamp,
+    /// First line of the commit message
+    pub(crate) summary: String,
 }
 
-fn run_git_command(repo_dir: &Path, args: &[&str]) -> miette::Result<()> {
-    let result = std::process::Command::new("git")
-        .arg("-C")
-        .arg(repo_dir)
-        .args(args)
-        .output()
+/// Lists the commits that changed a path (relative to `repo_dir`), newest first.
+pub(crate) fn log(repo_dir: &Path, path: &Path) -> miette::Result<Vec<LogEntry>> {
+    let (repo, prefix) = open(repo_dir)?;
+    let path = prefix.join(path);
+
+    let mut entries = Vec::new();
+
+    walk_history(&repo, |commit, time, tree, parent_trees| {
+        let (current, previous) = entry_ids(tree, parent_trees, &path)?;
+
+        if !previous.contains(&current) {
+            entries.push(LogEntry {
+                id: commit.id().shorten_or_id().to_string(),
+                time,
+                summary: commit
+                    .message()
+                    .into_diagnostic()?
+                    .summary()
+                    .to_str_lossy()
+                    .into_owned(),
+            });
+        }
+
+        Ok(true)
+    })?;
+
+    Ok(entries)
+}
+
+/// Reads the contents of a file (relative to `repo_dir`) as it was at a given revision.
+///
+/// The revision may be anything Git understands, e.g. a commit ID, `HEAD~2` or a branch name.
+/// Returns `None` if there was no file at that path in the revision.
+pub(crate) fn read_file_at(
+    repo_dir: &Path,
+    rev: &str,
+    path: &Path,
+) -> miette::Result<Option<Vec<u8>>> {
+    let (repo, prefix) = open(repo_dir)?;
+
+    let commit = repo
+        .rev_parse_single(rev)
         .into_diagnostic()
-        .wrap_err("Failed to run git executable")?;
+        .wrap_err(format!("Failed to resolve revision `{rev}`"))?
+        .object()
+        .into_diagnostic()?
+        .peel_to_commit()
+        .into_diagnostic()?;
+
+    match commit
+        .tree()
+        .into_diagnostic()?
+        .lookup_entry_by_path(prefix.join(path))
+        .into_diagnostic()?
+    {
+        Some(entry) if entry.mode().is_blob() => {
+            Ok(Some(entry.object().into_diagnostic()?.detach().data))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Walks the history of `HEAD`, including every parent of merge commits, newest commit first,
+/// calling `f` with each commit along with its time, tree and the trees of its parents until it
+/// returns `false`.
+fn walk_history<F>(repo: &Repository, mut f: F) -> miette::Result<()>
+where
+    F: FnMut(
+        &gix::Commit<'_>,
+        jiff::Timestamp,
+        &gix::Tree<'_>,
+        &[gix::Tree<'_>],
+    ) -> miette::Result<bool>,
+{
+    let head = match repo.head_id() {
+        Ok(id) => id,
+        // Nothing has been committed yet
+        Err(_) => return Ok(()),
+    };
 
-    if !result.status.success() {
-        Err(miette::miette!(
-            "Git command failed (status {}): {}",
-            result.status,
-            String::from_utf8_lossy(&result.stderr)
+    for info in repo
+        .rev_walk([head])
+        .sorting(gix::revision::walk::Sorting::ByCommitTime(
+            Default::default(),
         ))
+        .all()
+        .into_diagnostic()?
+    {
+        let info = info.into_diagnostic()?;
+        let commit = info.object().into_diagnostic()?;
+
+        let tree = commit.tree().into_diagnostic()?;
+        let parent_trees = info
+            .parent_ids()
+            .map(|id| {
+                id.object()
+                    .into_diagnostic()?
+                    .try_into_commit()
+                    .into_diagnostic()?
+                    .tree()
+                    .into_diagnostic()
+            })
+            .collect::<miette::Result<Vec<_>>>()?;
+
+        let time = jiff::Timestamp::from_second(commit.time().into_diagnostic()?.seconds)
+            .into_diagnostic()?;
+
+        if !f(&commit, time, &tree, &parent_trees)? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets the object IDs of a path in a tree and in each of its parent trees.
+///
+/// A tree with no parents is treated as having a single parent that doesn't contain the path, so a
+/// path has changed in a commit when its object ID differs from that in every parent.
+fn entry_ids(
+    tree: &gix::Tree<'_>,
+    parent_trees: &[gix::Tree<'_>],
+    path: &Path,
+) -> miette::Result<(Option<gix::ObjectId>, Vec<Option<gix::ObjectId>>)> {
+    let lookup = |tree: &gix::Tree<'_>| -> miette::Result<Option<gix::ObjectId>> {
+        Ok(tree
+            .lookup_entry_by_path(path)
+            .into_diagnostic()?
+            .map(|e| e.object_id()))
+    };
+
+    let current = lookup(tree)?;
+
+    let previous = if parent_trees.is_empty() {
+        vec![None]
     } else {
-        Ok(())
+        parent_trees
+            .iter()
+            .map(lookup)
+            .collect::<miette::Result<_>>()?
+    };
+
+    Ok((current, previous))
+}
+
+/// Opens the Git repository that `repo_dir` is in.
+///
+/// `repo_dir` may be a subdirectory of the working tree of the repository, so its path within the
+/// working tree is also returned. Paths relative to `repo_dir` must be joined onto it to be used
+/// with the repository.
+fn open(repo_dir: &Path) -> miette::Result<(Repository, PathBuf)> {
+    let repo = gix::discover(repo_dir).into_diagnostic().wrap_err(format!(
+        "Failed to open Git repository at `{}`",
+        repo_dir.display()
+    ))?;
+
+    let prefix = match repo.workdir() {
+        Some(workdir) => {
+            let canonicalize = |path: &Path| {
+                std::fs::canonicalize(path)
+                    .into_diagnostic()
+                    .wrap_err(format!("Failed to resolve `{}`", path.display()))
+            };
+
+            canonicalize(repo_dir)?
+                .strip_prefix(canonicalize(workdir)?)
+                .into_diagnostic()?
+                .to_owned()
+        }
+        None => PathBuf::new(),
+    };
+
+    Ok((repo, prefix))
+}
+
+/// Builds a pathspec that matches exactly the given path (relative to the root of the working tree)
+/// and anything under it, wherever the current directory is.
+fn literal_pathspec(path: &Path) -> BString {
+    let path = gix::path::to_unix_separators_on_windows(gix::path::into_bstr(path));
+    let mut pathspec = BString::from(":(top,literal)");
+    pathspec.extend_from_slice(&path);
+    pathspec
+}
+
+/// Lists the files (relative to the root of the working tree) matching `pathspecs` that have
+/// staged or unstaged changes, or that are untracked.
+/// An empty list of pathspecs matches every file.
+fn changed_paths(repo: &Repository, pathspecs: Vec<BString>) -> miette::Result<Vec<BString>> {
+    let mut paths = Vec::new();
+
+    for item in repo
+        .status(gix::progress::Discard)
+        .into_diagnostic()?
+        .untracked_files(gix::status::UntrackedFiles::Files)
+        .index_worktree_rewrites(None)
+        .tree_index_track_renames(gix::status::tree_index::TrackRenames::Disabled)
+        .into_iter(pathspecs)
+        .into_diagnostic()
+        .wrap_err("Failed to get Git status")?
+    {
+        let item = item
+            .into_diagnostic()
+            .wrap_err("Failed to get Git status")?;
+        paths.push(item.location().to_owned());
     }
+
+    paths.sort();
+    paths.dedup();
+
+    Ok(paths)
 }
 
-/// Checks if a Git repository is clean, i.e. it has no staged or unstaged changes and no untracked
-/// files.
-fn is_clean(repo_dir: &Path) -> miette::Result<bool> {
-    // TODO: replace this with use of gix
+/// Stages the given paths (relative to the root of the working tree) as they currently are in the
+/// working tree and commits them on top of `HEAD`.
+///
+/// Paths that no longer exist in the working tree are removed from the index and the committed
+/// tree.
+fn commit_paths(repo: &Repository, paths: &[BString], commit_msg: &str) -> miette::Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| miette!("Git repository does not have a working tree"))?;
 
-    let output = std::process::Command::new("git")
-        .arg("-C")
-        .arg(repo_dir)
-        .arg("status")
-        .arg("--porcelain")
-        .output()
-        .into_diagnostic()?;
+    let mut index = (*repo.index_or_empty().into_diagnostic()?).clone();
+
+    let (parent, head_tree) = match repo.head_commit() {
+        Ok(commit) => (
+            Some(commit.id),
+            commit.tree_id().into_diagnostic()?.detach(),
+        ),
+        Err(_) if repo.head().into_diagnostic()?.is_unborn() => {
+            (None, gix::ObjectId::empty_tree(repo.object_hash()))
+        }
+        Err(e) => return Err(e).into_diagnostic(),
+    };
+    let mut tree = repo.edit_tree(head_tree).into_diagnostic()?;
+
+    for path in paths {
+        let filename = workdir.join(gix::path::from_bstr(path.as_bstr()));
+
+        match gix::index::fs::Metadata::from_path_no_follow(&filename) {
+            Ok(metadata) if metadata.is_dir() => {
+                return Err(miette!("Cannot stage directory `{}`", filename.display()));
+            }
+            Ok(metadata) => {
+                let (data, kind, mode) = if metadata.is_symlink() {
+                    let target = std::fs::read_link(&filename).into_diagnostic()?;
+                    let target = gix::path::into_bstr(target).into_owned();
+                    (Vec::from(target), EntryKind::Link, Mode::SYMLINK)
+                } else if metadata.is_executable() {
+                    let data = std::fs::read(&filename).into_diagnostic()?;
+                    (data, EntryKind::BlobExecutable, Mode::FILE_EXECUTABLE)
+                } else {
+                    let data = std::fs::read(&filename).into_diagnostic()?;
+                    (data, EntryKind::Blob, Mode::FILE)
+                };
+
+                let id = repo
+                    .write_blob(&data)
+                    .into_diagnostic()
+                    .wrap_err(format!("Failed to write `{path}` to object database"))?
+                    .detach();
+                let stat = Stat::from_fs(&metadata).into_diagnostic()?;
+
+                upsert_index_entry(&mut index, path.as_bstr(), id, mode, stat);
+                let _ = tree.upsert(path, kind, id).into_diagnostic()?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                index.remove_entries(|_, entry_path, _| entry_path == path);
+                let _ = tree.remove(path).into_diagnostic()?;
+            }
+            Err(e) => {
+                return Err(e)
+                    .into_diagnostic()
+                    .wrap_err(format!("Failed to read `{}`", filename.display()));
+            }
+        }
+    }
+
+    index.sort_entries();
+    // The cached trees no longer match the entries
+    let _ = index.remove_tree();
+
+    let tree_id = tree.write().into_diagnostic()?;
+
+    let _ = repo
+        .commit("HEAD", commit_msg, tree_id, parent)
+        .into_diagnostic()
+        .wrap_err("Failed to create commit")?;
+
+    index
+        .write(Default::default())
+        .into_diagnostic()
+        .wrap_err("Failed to write Git index")
+}
 
-    Ok(output.stdout.is_empty())
+/// Hooks that Git runs when committing.
+const COMMIT_HOOKS: [&str; 4] = [
+    "pre-commit",
+    "prepare-commit-msg",
+    "commit-msg",
+    "post-commit",
+];
+
+/// Whether commits have to be made by the `git` executable, because the repository signs commits
+/// or has commit hooks and gix does neither.
+fn needs_git_cli(repo: &Repository) -> bool {
+    let config = repo.config_snapshot();
+
+    if config.boolean("commit.gpgsign") == Some(true) {
+        return true;
+    }
+
+    let hooks_dir = match config.trusted_path("core.hooksPath") {
+        Some(Ok(path)) => match repo.workdir() {
+            Some(workdir) => workdir.join(path),
+            None => path.into_owned(),
+        },
+        _ => repo.common_dir().join("hooks"),
+    };
+
+    COMMIT_HOOKS
+        .iter()
+        .any(|hook| is_executable(&hooks_dir.join(hook)))
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::metadata(path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// Stages and commits the given paths (relative to the root of the working tree) with the `git`
+/// executable, so that commits are signed and hooks are run as configured.
+fn commit_paths_with_cli(
+    repo: &Repository,
+    paths: &[BString],
+    commit_msg: &str,
+) -> miette::Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| miette!("Git repository does not have a working tree"))?;
+
+    let paths: Vec<_> = paths
+        .iter()
+        .map(|path| gix::path::from_bstr(path.as_bstr()).into_owned())
+        .collect();
+
+    let git = |args: &[&str]| -> miette::Result<()> {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(workdir)
+            .arg("--literal-pathspecs")
+            .args(args)
+            .arg("--")
+            .args(&paths)
+            .output()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => miette!(
+                    "The `git` executable was not found on the PATH, it is needed to sign commits or run commit hooks"
+                ),
+                _ => miette!("Failed to run git command: {e}"),
+            })?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(miette!(
+                "`git {}` failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    };
+
+    git(&["add", "--all"])?;
+    // Only the given paths are committed, anything else that is staged stays staged
+    git(&["commit", "--quiet", "--message", commit_msg])
+}
+
+fn upsert_index_entry(
+    index: &mut gix::index::File,
+    path: &BStr,
+    id: gix::ObjectId,
+    mode: Mode,
+    stat: Stat,
+) {
+    match index.entry_mut_by_path_and_stage(path, gix::index::entry::Stage::Unconflicted) {
+        Some(entry) => {
+            entry.id = id;
+            entry.mode = mode;
+            entry.stat = stat;
+        }
+        None => index.dangerously_push_entry(stat, id, Flags::empty(), mode, path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::{init_git_repo, set_git_config};
+    use tempfile::tempdir;
+
+    fn head_files(repo_dir: &Path) -> Vec<String> {
+        let (repo, _) = open(repo_dir).unwrap();
+        let index = repo.index_from_tree(&repo.head_tree_id().unwrap()).unwrap();
+        index
+            .entries()
+            .iter()
+            .map(|e| e.path(&index).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn commit_add_modify_remove() {
+        set_git_config();
+
+        let dir = tempdir().unwrap();
+        init_git_repo(dir.path());
+
+        let result = git_operation(dir.path(), "Add", || {
+            std::fs::create_dir_all(dir.path().join("a/b")).into_diagnostic()?;
+            std::fs::write(dir.path().join("a/b/one.yaml"), "1").into_diagnostic()?;
+            std::fs::write(dir.path().join("two.yaml"), "2").into_diagnostic()?;
+            Ok(vec!["a".into(), "two.yaml".into()])
+        })
+        .unwrap();
+        assert_eq!(result, GitOperationResult::Commit);
+        assert_eq!(head_files(dir.path()), vec!["a/b/one.yaml", "two.yaml"]);
+        assert!(dirty_paths(dir.path()).unwrap().is_empty());
+
+        let result = git_operation(dir.path(), "Nothing", || Ok(vec!["a".into()])).unwrap();
+        assert_eq!(result, GitOperationResult::NoChanges);
+
+        let result = git_operation(dir.path(), "Modify and remove", || {
+            std::fs::write(dir.path().join("a/b/one.yaml"), "one").into_diagnostic()?;
+            std::fs::remove_file(dir.path().join("two.yaml")).into_diagnostic()?;
+            Ok(vec!["a/b/one.yaml".into(), "two.yaml".into()])
+        })
+        .unwrap();
+        assert_eq!(result, GitOperationResult::Commit);
+        assert_eq!(head_files(dir.path()), vec!["a/b/one.yaml"]);
+        assert!(dirty_paths(dir.path()).unwrap().is_empty());
+
+        let (repo, _) = open(dir.path()).unwrap();
+        let head = repo.head_commit().unwrap();
+        assert_eq!(head.message_raw_sloppy(), "Modify and remove");
+        assert_eq!(head.parent_ids().count(), 1);
+    }
+
+    #[test]
+    fn last_modified_times() {
+        set_git_config();
+
+        let dir = tempdir().unwrap();
+        init_git_repo(dir.path());
+
+        for name in ["one", "two"] {
+            let _ = git_operation(dir.path(), name, || {
+                std::fs::write(dir.path().join(name), name).into_diagnostic()?;
+                Ok(vec![name.into()])
+            })
+            .unwrap();
+        }
+
+        let paths: Vec<PathBuf> = vec!["one".into(), "two".into(), "three".into()];
+        let times = last_modified(dir.path(), &paths).unwrap();
+
+        let (repo, _) = open(dir.path()).unwrap();
+        let head = repo.head_commit().unwrap();
+        let head_time = jiff::Timestamp::from_second(head.time().unwrap().seconds).unwrap();
+
+        assert_eq!(times.len(), 2);
+        assert_eq!(times[Path::new("two")], head_time);
+        assert!(times[Path::new("one")] <= head_time);
+    }
+
+    #[test]
+    fn last_modified_part_times() {
+        set_git_config();
+
+        let dir = tempdir().unwrap();
+        init_git_repo(dir.path());
+
+        for contents in ["a=1\nb=1\nc=1", "a=1\nb=2"] {
+            let _ = git_operation(dir.path(), contents, || {
+                std::fs::write(dir.path().join("record"), contents).into_diagnostic()?;
+                Ok(vec!["record".into()])
+            })
+            .unwrap();
+        }
+
+        let parts = |_: &Path, data: &[u8]| {
+            String::from_utf8_lossy(data)
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect()
+        };
+
+        let paths: Vec<PathBuf> = vec!["record".into(), "missing".into()];
+        let times = last_modified_parts(dir.path(), &paths, parts).unwrap();
+
+        let (repo, _) = open(dir.path()).unwrap();
+        let head = repo.head_commit().unwrap();
+        let head_time = jiff::Timestamp::from_second(head.time().unwrap().seconds).unwrap();
+
+        assert_eq!(times.len(), 1);
+        let record = &times[Path::new("record")];
+        assert_eq!(record.len(), 2);
+        assert_eq!(record["b"], head_time);
+        assert!(record["a"] <= head_time);
+    }
+
+    #[test]
+    fn log_and_read_file_at() {
+        set_git_config();
+
+        let dir = tempdir().unwrap();
+        init_git_repo(dir.path());
+
+        for (name, contents) in [("record", "1"), ("other", "1"), ("record", "2")] {
+            let _ = git_operation(dir.path(), &format!("{name} {contents}"), || {
+                std::fs::write(dir.path().join(name), contents).into_diagnostic()?;
+                Ok(vec![name.into()])
+            })
+            .unwrap();
+        }
+
+        let summaries: Vec<String> = log(dir.path(), Path::new("record"))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.summary)
+            .collect();
+        assert_eq!(summaries, vec!["record 2", "record 1"]);
+
+        assert_eq!(
+            read_file_at(dir.path(), "HEAD~2", Path::new("record")).unwrap(),
+            Some(b"1".to_vec())
+        );
+        assert_eq!(
+            read_file_at(dir.path(), "HEAD~2", Path::new("other")).unwrap(),
+            None
+        );
+        assert!(read_file_at(dir.path(), "nonexistent", Path::new("record")).is_err());
+    }
+
+    #[test]
+    fn only_touched_paths_are_committed() {
+        set_git_config();
+
+        let dir = tempdir().unwrap();
+        init_git_repo(dir.path());
+
+        let _ = git_operation(dir.path(), "Add", || {
+            std::fs::write(dir.path().join("config"), "1").into_diagnostic()?;
+            Ok(vec!["config".into()])
+        })
+        .unwrap();
+
+        // Unrelated changes that are present before the operation
+        std::fs::write(dir.path().join("config"), "2").unwrap();
+        std::fs::write(dir.path().join("untracked"), "").unwrap();
+
+        let result = git_operation(dir.path(), "Move", || {
+            std::fs::create_dir_all(dir.path().join("dir")).into_diagnostic()?;
+            std::fs::write(dir.path().join("dir/record"), "").into_diagnostic()?;
+            Ok(vec!["dir".into()])
+        })
+        .unwrap();
+        assert_eq!(result, GitOperationResult::Commit);
+        assert_eq!(head_files(dir.path()), vec!["config", "dir/record"]);
+
+        let dirty: Vec<PathBuf> = vec!["config".into(), "untracked".into()];
+        assert_eq!(dirty_paths(dir.path()).unwrap(), dirty);
+    }
+
+    #[test]
+    fn store_in_subdirectory() {
+        set_git_config();
+
+        let dir = tempdir().unwrap();
+        init_git_repo(dir.path());
+        let store = dir.path().join("store");
+        std::fs::create_dir(&store).unwrap();
+
+        for contents in ["1", "2"] {
+            let result = git_operation(&store, contents, || {
+                std::fs::write(store.join("record"), contents).into_diagnostic()?;
+                Ok(vec!["record".into()])
+            })
+            .unwrap();
+            assert_eq!(result, GitOperationResult::Commit);
+        }
+        assert_eq!(head_files(dir.path()), vec!["store/record"]);
+
+        std::fs::write(dir.path().join("outside"), "").unwrap();
+        std::fs::write(store.join("inside"), "").unwrap();
+        let dirty: Vec<PathBuf> = vec!["inside".into()];
+        assert_eq!(dirty_paths(&store).unwrap(), dirty);
+
+        let paths: Vec<PathBuf> = vec!["record".into()];
+        assert_eq!(last_modified(&store, &paths).unwrap().len(), 1);
+        assert_eq!(log(&store, Path::new("record")).unwrap().len(), 2);
+        assert_eq!(
+            read_file_at(&store, "HEAD~1", Path::new("record")).unwrap(),
+            Some(b"1".to_vec())
+        );
+    }
+
+    #[test]
+    fn log_follows_merged_history() {
+        set_git_config();
+
+        let dir = tempdir().unwrap();
+        init_git_repo(dir.path());
+
+        for (name, contents) in [("record", "1"), ("other", "1")] {
+            let _ = git_operation(dir.path(), &format!("{name} {contents}"), || {
+                std::fs::write(dir.path().join(name), contents).into_diagnostic()?;
+                Ok(vec![name.into()])
+            })
+            .unwrap();
+        }
+
+        let (repo, _) = open(dir.path()).unwrap();
+        let head = repo.head_commit().unwrap();
+        let base = head
+            .parent_ids()
+            .next()
+            .unwrap()
+            .object()
+            .unwrap()
+            .into_commit();
+
+        let with_record = |commit: &gix::Commit<'_>| {
+            let mut tree = repo.edit_tree(commit.tree_id().unwrap()).unwrap();
+            let blob = repo.write_blob("2").unwrap();
+            let _ = tree.upsert("record", EntryKind::Blob, blob).unwrap();
+            tree.write().unwrap().detach()
+        };
+
+        // A change to the record made on another branch, then merged in
+        let side = repo
+            .new_commit("side", with_record(&base), [base.id])
+            .unwrap();
+        let _ = repo
+            .commit("HEAD", "Merge", with_record(&head), [head.id, side.id])
+            .unwrap();
+
+        let summaries: Vec<String> = log(dir.path(), Path::new("record"))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.summary)
+            .collect();
+        assert_eq!(summaries, vec!["side", "record 1"]);
+
+        let summaries: Vec<String> = log(dir.path(), Path::new("other"))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.summary)
+            .collect();
+        assert_eq!(summaries, vec!["other 1"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commit_hooks_are_run() {
+        use std::os::unix::fs::PermissionsExt;
+
+        set_git_config();
+
+        let dir = tempdir().unwrap();
+        init_git_repo(dir.path());
+
+        let hook = dir.path().join(".git/hooks/commit-msg");
+        std::fs::create_dir_all(hook.parent().unwrap()).unwrap();
+        std::fs::write(&hook, "#!/bin/sh\necho 'Hooked' >> \"$1\"\n").unwrap();
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (repo, _) = open(dir.path()).unwrap();
+        assert!(needs_git_cli(&repo));
+
+        let result = git_operation(dir.path(), "Add", || {
+            std::fs::write(dir.path().join("one.yaml"), "1").into_diagnostic()?;
+            std::fs::write(dir.path().join("two.yaml"), "2").into_diagnostic()?;
+            Ok(vec!["one.yaml".into()])
+        })
+        .unwrap();
+        assert_eq!(result, GitOperationResult::Commit);
+        assert_eq!(head_files(dir.path()), vec!["one.yaml"]);
+        assert_eq!(
+            dirty_paths(dir.path()).unwrap(),
+            vec![PathBuf::from("two.yaml")]
+        );
+
+        let (repo, _) = open(dir.path()).unwrap();
+        let message = repo
+            .head_commit()
+            .unwrap()
+            .message_raw()
+            .unwrap()
+            .to_string();
+        assert_eq!(message, "Add\nHooked\n");
+
+        let result = git_operation(dir.path(), "Remove", || {
+            std::fs::remove_file(dir.path().join("one.yaml")).into_diagnostic()?;
+            Ok(vec!["one.yaml".into()])
+        })
+        .unwrap();
+        assert_eq!(result, GitOperationResult::Commit);
+        assert!(head_files(dir.path()).is_empty());
+    }
 }
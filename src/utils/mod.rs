@@ -1,11 +1,16 @@
 pub(crate) mod clipboard;
+pub(crate) mod document;
 pub(crate) mod file;
 pub(crate) mod git;
+pub(crate) mod parallel;
+pub(crate) mod password;
 pub(crate) mod qr;
+pub(crate) mod render;
 pub(crate) mod skim;
 pub(crate) mod sops;
 #[cfg(test)]
 pub(crate) mod test;
+pub(crate) mod tree;
 
 use miette::{Context, IntoDiagnostic};
 use totp_rs::TOTP;
@@ -0,0 +1,233 @@
+use miette::miette;
+use rand::{
+    Rng,
+    seq::{IndexedRandom, SliceRandom},
+};
+use zeroize::Zeroizing;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!#$%&()*+,-./:;<=>?@[]^_{}~";
+
+/// Characters that are easily confused with one another when read by a human.
+const AMBIGUOUS: &str = "Il1O0o";
+
+#[derive(Debug, Clone)]
+pub(crate) struct CharacterClasses {
+    pub(crate) lowercase: bool,
+    pub(crate) uppercase: bool,
+    pub(crate) digits: bool,
+    pub(crate) symbols: bool,
+}
+
+impl CharacterClasses {
+    fn sets(&self, exclude_ambiguous: bool) -> Vec<Vec<char>> {
+        [
+            (self.lowercase, LOWERCASE),
+            (self.uppercase, UPPERCASE),
+            (self.digits, DIGITS),
+            (self.symbols, SYMBOLS),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, chars)| {
+            chars
+                .chars()
+                .filter(|c| !exclude_ambiguous || !AMBIGUOUS.contains(*c))
+                .collect()
+        })
+        .collect()
+    }
+}
+
+/// Generates a random password of a given length.
+///
+/// The password will contain at least one character from each of the enabled character classes.
+pub(crate) fn generate_password(
+    length: usize,
+    classes: &CharacterClasses,
+    exclude_ambiguous: bool,
+) -> miette::Result<Zeroizing<String>> {
+    let sets = classes.sets(exclude_ambiguous);
+
+    if sets.is_empty() {
+        return Err(miette!("At least one character class must be enabled"));
+    }
+
+    if length < sets.len() {
+        return Err(miette!(
+            "Password length must be at least {} to include all enabled character classes",
+            sets.len()
+        ));
+    }
+
+    let mut rng = rand::rng();
+    let alphabet: Vec<char> = sets.iter().flatten().copied().collect();
+
+    // Take one character from each class, then fill the remainder from the combined alphabet
+    let mut chars: Zeroizing<Vec<char>> = Zeroizing::new(Vec::with_capacity(length));
+    for set in &sets {
+        chars.push(*set.choose(&mut rng).unwrap());
+    }
+    for _ in sets.len()..length {
+        chars.push(alphabet[rng.random_range(0..alphabet.len())]);
+    }
+
+    // Shuffle so that the guaranteed characters are not always at the start
+    chars.shuffle(&mut rng);
+
+    Ok(Zeroizing::new(chars.iter().collect()))
+}
+
+/// Generates a diceware style passphrase using the EFF large wordlist.
+pub(crate) fn generate_passphrase(
+    words: usize,
+    separator: &str,
+) -> miette::Result<Zeroizing<String>> {
+    if words == 0 {
+        return Err(miette!("Passphrase must contain at least one word"));
+    }
+
+    let mut rng = rand::rng();
+
+    let words: Vec<&str> = (0..words)
+        .map(|_| eff_wordlist::large::LIST.choose(&mut rng).unwrap().1)
+        .collect();
+
+    Ok(Zeroizing::new(words.join(separator)))
+}
+
+/// Estimates the entropy of a secret in bits.
+///
+/// Secrets made up entirely of words from the EFF large wordlist are scored as diceware
+/// passphrases, anything else is scored by its length and the character classes it contains.
+/// This is a rough upper bound, it does not account for dictionary words or common patterns.
+pub(crate) fn estimate_entropy(secret: &str) -> f64 {
+    let words: Vec<&str> = secret
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    if words.len() > 1
+        && words.iter().all(|word| {
+            eff_wordlist::large::LIST
+                .iter()
+                .any(|(_, w)| w.eq_ignore_ascii_case(word))
+        })
+    {
+        return words.len() as f64 * (eff_wordlist::large::LIST.len() as f64).log2();
+    }
+
+    let classes = [LOWERCASE, UPPERCASE, DIGITS, SYMBOLS];
+
+    let mut pool: usize = classes
+        .iter()
+        .filter(|class| secret.chars().any(|c| class.contains(c)))
+        .map(|class| class.len())
+        .sum();
+
+    // Any other character (whitespace, other punctuation or non-ASCII) widens the pool further
+    if secret
+        .chars()
+        .any(|c| !classes.iter().any(|class| class.contains(c)))
+    {
+        pool += 32;
+    }
+
+    if pool == 0 {
+        0.0
+    } else {
+        secret.chars().count() as f64 * (pool as f64).log2()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: CharacterClasses = CharacterClasses {
+        lowercase: true,
+        uppercase: true,
+        digits: true,
+        symbols: true,
+    };
+
+    #[test]
+    fn password_length() {
+        let password = generate_password(32, &ALL, false).unwrap();
+        assert_eq!(password.chars().count(), 32);
+    }
+
+    #[test]
+    fn password_contains_all_classes() {
+        let password = generate_password(4, &ALL, false).unwrap();
+        assert!(password.chars().any(|c| LOWERCASE.contains(c)));
+        assert!(password.chars().any(|c| UPPERCASE.contains(c)));
+        assert!(password.chars().any(|c| DIGITS.contains(c)));
+        assert!(password.chars().any(|c| SYMBOLS.contains(c)));
+    }
+
+    #[test]
+    fn password_digits_only() {
+        let classes = CharacterClasses {
+            lowercase: false,
+            uppercase: false,
+            digits: true,
+            symbols: false,
+        };
+        let password = generate_password(16, &classes, false).unwrap();
+        assert!(password.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn password_exclude_ambiguous() {
+        let password = generate_password(256, &ALL, true).unwrap();
+        assert!(!password.chars().any(|c| AMBIGUOUS.contains(c)));
+    }
+
+    #[test]
+    fn password_no_classes() {
+        let classes = CharacterClasses {
+            lowercase: false,
+            uppercase: false,
+            digits: false,
+            symbols: false,
+        };
+        assert!(generate_password(16, &classes, false).is_err());
+    }
+
+    #[test]
+    fn password_too_short_for_classes() {
+        assert!(generate_password(3, &ALL, false).is_err());
+    }
+
+    #[test]
+    fn passphrase_word_count() {
+        let passphrase = generate_passphrase(6, " ").unwrap();
+        assert_eq!(passphrase.split(' ').count(), 6);
+    }
+
+    #[test]
+    fn passphrase_no_words() {
+        assert!(generate_passphrase(0, "-").is_err());
+    }
+
+    #[test]
+    fn entropy_of_generated_password() {
+        let password = generate_password(24, &ALL, false).unwrap();
+        assert!(estimate_entropy(&password) > 140.0);
+    }
+
+    #[test]
+    fn entropy_of_weak_password() {
+        assert_eq!(estimate_entropy(""), 0.0);
+        assert!(estimate_entropy("hunter2") < 40.0);
+    }
+
+    #[test]
+    fn entropy_of_passphrase() {
+        let passphrase = generate_passphrase(3, "-").unwrap();
+        assert!((estimate_entropy(&passphrase) - 3.0 * 7776f64.log2()).abs() < 0.001);
+    }
+}
@@ -0,0 +1,146 @@
+use miette::miette;
+use zeroize::Zeroizing;
+
+/// A reference to a secret found in a template.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Reference {
+    pub(crate) path: String,
+    pub(crate) selector: Option<String>,
+}
+
+/// Renders a template, replacing each placeholder with the secret that it refers to.
+///
+/// Placeholders look like `{{ koishi "db/prod" "password" }}`, the selector may be left out for
+/// records that hold a single value. Anything else between `{{` and `}}` is left as it is, so
+/// templates for other tools can still be used.
+pub(crate) fn render<F>(template: &str, mut lookup: F) -> miette::Result<Zeroizing<String>>
+where
+    F: FnMut(&Reference) -> miette::Result<Zeroizing<String>>,
+{
+    let mut output = Zeroizing::new(String::with_capacity(template.len()));
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let line = template[..template.len() - rest.len() + start]
+            .matches('\n')
+            .count()
+            + 1;
+        let placeholder = &rest[start + 2..];
+
+        let Some(args) = placeholder
+            .trim_start()
+            .strip_prefix("koishi")
+            .filter(|args| args.starts_with(char::is_whitespace))
+        else {
+            output.push_str("{{");
+            rest = placeholder;
+            continue;
+        };
+
+        let end = args
+            .find("}}")
+            .ok_or_else(|| miette!("Unclosed placeholder on line {line}"))?;
+        let reference = parse_reference(&args[..end])
+            .map_err(|e| miette!("Invalid placeholder on line {line}: {e}"))?;
+
+        output.push_str(&lookup(&reference).map_err(|e| e.wrap_err(format!("On line {line}")))?);
+        rest = &args[end + 2..];
+    }
+
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+fn parse_reference(args: &str) -> Result<Reference, String> {
+    let mut strings = Vec::new();
+    let mut chars = args.trim().chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => string.extend(chars.next()),
+                        Some(c) => string.push(c),
+                        None => return Err("unclosed string".into()),
+                    }
+                }
+                strings.push(string);
+            }
+            c if c.is_whitespace() => {}
+            c => return Err(format!("expected a quoted string but found `{c}`")),
+        }
+    }
+
+    let mut strings = strings.into_iter();
+
+    match (strings.next(), strings.next(), strings.next()) {
+        (Some(path), selector, None) => Ok(Reference { path, selector }),
+        (None, _, _) => Err("expected the path to a record".into()),
+        _ => Err("expected at most a path and a selector".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(reference: &Reference) -> miette::Result<Zeroizing<String>> {
+        match (reference.path.as_str(), reference.selector.as_deref()) {
+            ("db/prod", Some("password")) => Ok(Zeroizing::new("hunter2".into())),
+            ("api/token", None) => Ok(Zeroizing::new("abc".into())),
+            _ => Err(miette!("missing")),
+        }
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            *render(
+                "DB_PASS={{ koishi \"db/prod\" \"password\" }}\nTOKEN={{koishi \"api/token\"}}\n",
+                lookup
+            )
+            .unwrap(),
+            "DB_PASS=hunter2\nTOKEN=abc\n"
+        );
+    }
+
+    #[test]
+    fn other_braces_untouched() {
+        assert_eq!(
+            *render("{{ .Values.name }} {{koishiname}} {", lookup).unwrap(),
+            "{{ .Values.name }} {{koishiname}} {"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(render("{{ koishi \"db/prod\" \"user\" }}", lookup).is_err());
+        assert!(render("{{ koishi \"db/prod\" ", lookup).is_err());
+        assert!(render("{{ koishi db/prod }}", lookup).is_err());
+        assert!(render("{{ koishi }}", lookup).is_err());
+        assert!(render("{{ koishi \"a\" \"b\" \"c\" }}", lookup).is_err());
+        assert!(render("{{ koishi \"a }}", lookup).is_err());
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            parse_reference(r#" "a\"b" "c\\d" "#).unwrap(),
+            Reference {
+                path: "a\"b".into(),
+                selector: Some("c\\d".into())
+            }
+        );
+    }
+
+    #[test]
+    fn error_line() {
+        let err = render("a\nb\n{{ koishi \"missing\" }}", lookup).unwrap_err();
+        assert_eq!(err.to_string(), "On line 3");
+    }
+}
@@ -0,0 +1,107 @@
+use crate::utils::document::{Format, Value};
+use std::{collections::HashMap, path::Path};
+
+/// Extracts the encrypted value of each attribute in the contents of a SOPS encrypted file,
+/// keyed by the slash delimited path to the attribute.
+///
+/// SOPS encrypts each value with its own IV. Koishi keeps the ciphertext of values that haven't
+/// changed when it rewrites a file, but the SOPS executable re-encrypts every value whenever it
+/// writes one (e.g. with `sops edit` or `sops set`), as does rotating the data key.
+/// Nothing is decrypted, contents that cannot be parsed give no attributes.
+pub(crate) fn ciphertexts(file: &Path, contents: &[u8]) -> HashMap<String, String> {
+    match Format::from_path(file) {
+        Format::Dotenv => from_dotenv(&String::from_utf8_lossy(contents)),
+        Format::Ini => from_ini(&String::from_utf8_lossy(contents)),
+        format => {
+            // Binary files are stored as JSON
+            let format = match format {
+                Format::Binary => Format::Json,
+                format => format,
+            };
+
+            match Value::parse(format, contents) {
+                Ok(document) => document
+                    .leaves()
+                    .into_iter()
+                    .filter(|(path, _)| path != "sops" && !path.starts_with("sops/"))
+                    .filter_map(|(path, value)| Some((path, value.scalar_to_string()?)))
+                    .collect(),
+                Err(_) => HashMap::new(),
+            }
+        }
+    }
+}
+
+fn from_dotenv(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .filter(|(key, _)| !key.starts_with("sops_"))
+        .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+        .collect()
+}
+
+fn from_ini(contents: &str) -> HashMap<String, String> {
+    let mut section = String::new();
+    let mut values = HashMap::new();
+
+    for line in contents.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_owned();
+        } else if section != "sops" && !line.starts_with(['#', ';']) {
+            if let Some((key, value)) = line.split_once('=') {
+                let key = key.trim();
+                let key = if section.is_empty() {
+                    key.to_owned()
+                } else {
+                    format!("{section}/{key}")
+                };
+                let _ = values.insert(key, value.trim().to_owned());
+            }
+        }
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yaml_ciphertexts() {
+        let contents = r#"password: ENC[AES256_GCM,data:one=,iv:a=,tag:a=,type:str]
+nested:
+  pin: ENC[AES256_GCM,data:two=,iv:b=,tag:b=,type:int]
+sops:
+  mac: ENC[AES256_GCM,data:mac=,iv:c=,tag:c=,type:str]
+  version: 3.9.0
+"#;
+
+        let ciphertexts = ciphertexts(Path::new("record.yaml"), contents.as_bytes());
+
+        assert_eq!(ciphertexts.len(), 2);
+        assert_eq!(
+            ciphertexts["password"],
+            "ENC[AES256_GCM,data:one=,iv:a=,tag:a=,type:str]"
+        );
+        assert_eq!(
+            ciphertexts["nested/pin"],
+            "ENC[AES256_GCM,data:two=,iv:b=,tag:b=,type:int]"
+        );
+    }
+
+    #[test]
+    fn ini_ciphertexts() {
+        let contents = "[db]\n\
+                        password = ENC[AES256_GCM,data:one=,iv:a=,tag:a=,type:str]\n\
+                        [sops]\n\
+                        version = 3.9.0\n";
+
+        let ciphertexts = ciphertexts(Path::new("record.ini"), contents.as_bytes());
+
+        assert_eq!(ciphertexts.len(), 1);
+        assert!(ciphertexts.contains_key("db/password"));
+    }
+}
@@ -0,0 +1,173 @@
+mod ciphertexts;
+mod native;
+mod recipients;
+mod subprocess;
+
+pub(crate) use ciphertexts::ciphertexts;
+pub(crate) use recipients::recipients;
+pub(crate) use subprocess::interactive_command;
+
+use miette::{IntoDiagnostic, miette};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Operations on SOPS encrypted files.
+///
+/// All paths are either absolute or relative to `workdir`, which is the root of the store (and
+/// therefore where the SOPS config file lives).
+pub(crate) trait Backend {
+    /// Opens a file for interactive editing, encrypting the result.
+    fn edit(&self, workdir: &Path, file: &Path) -> miette::Result<()>;
+
+    /// Decrypts a file, optionally extracting only part of it.
+    fn decrypt(
+        &self,
+        workdir: &Path,
+        file: &Path,
+        extract: Option<&str>,
+    ) -> miette::Result<Zeroizing<Vec<u8>>>;
+
+    /// Encrypts plaintext contents, writing them to a file.
+    fn encrypt(
+        &self,
+        workdir: &Path,
+        file: &Path,
+        contents: Zeroizing<Vec<u8>>,
+    ) -> miette::Result<()>;
+
+    /// Replaces the plaintext of an existing encrypted file.
+    ///
+    /// Where possible the data key and the ciphertext of values that have not changed are kept, so
+    /// that only the values that changed differ in the encrypted file. This is only done while the
+    /// file is encrypted for exactly the recipients of its creation rule, otherwise it is
+    /// encrypted afresh with a new data key.
+    fn update(
+        &self,
+        workdir: &Path,
+        file: &Path,
+        contents: Zeroizing<Vec<u8>>,
+    ) -> miette::Result<()>;
+
+    /// Sets a single value in an encrypted file.
+    fn set(
+        &self,
+        workdir: &Path,
+        file: &Path,
+        selector: &str,
+        contents: Zeroizing<String>,
+    ) -> miette::Result<()>;
+
+    /// Re-encrypts the data key of a file for the recipients given by the SOPS config.
+    fn update_keys(&self, workdir: &Path, file: &Path, yes: bool) -> miette::Result<()>;
+}
+
+/// Error returned by a backend when it is unable to handle a file, indicating that another
+/// backend should be tried instead.
+#[derive(Debug)]
+pub(crate) struct Unsupported(String);
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Not supported by the native SOPS backend: {}", self.0)
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+impl miette::Diagnostic for Unsupported {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackendKind {
+    /// In-process implementation, falling back to the `sops` executable for unsupported files
+    Native,
+    /// Always use the `sops` executable
+    Subprocess,
+}
+
+fn backend_kind() -> miette::Result<BackendKind> {
+    match std::env::var("KOISHI_SOPS_BACKEND").as_deref() {
+        Err(_) | Ok("native") => Ok(BackendKind::Native),
+        Ok("subprocess") => Ok(BackendKind::Subprocess),
+        Ok(other) => Err(miette!(
+            "Unknown SOPS backend `{other}` in KOISHI_SOPS_BACKEND, expected `native` or `subprocess`"
+        )),
+    }
+}
+
+/// Runs an operation with the selected backend.
+///
+/// When using the native backend, operations it reports as unsupported are retried using the
+/// `sops` executable.
+fn with_backend<T, F: Fn(&dyn Backend) -> miette::Result<T>>(op: F) -> miette::Result<T> {
+    match backend_kind()? {
+        BackendKind::Native => match op(&native::Native) {
+            Err(e) if e.downcast_ref::<Unsupported>().is_some() => op(&subprocess::Subprocess),
+            result => result,
+        },
+        BackendKind::Subprocess => op(&subprocess::Subprocess),
+    }
+}
+
+pub(crate) fn edit(workdir: &Path, file: &Path) -> miette::Result<()> {
+    with_backend(|b| b.edit(workdir, file))
+}
+
+pub(crate) fn decrypt(
+    workdir: &Path,
+    file: &Path,
+    extract: Option<&str>,
+) -> miette::Result<Zeroizing<Vec<u8>>> {
+    with_backend(|b| b.decrypt(workdir, file, extract))
+}
+
+/// Decrypts encrypted contents that are not in the store, e.g. a version of a record from the Git
+/// history, optionally extracting only part of them.
+///
+/// The format of the contents is determined from `file`, which does not need to exist.
+pub(crate) fn decrypt_contents(
+    workdir: &Path,
+    file: &Path,
+    contents: &[u8],
+    extract: Option<&str>,
+) -> miette::Result<Zeroizing<Vec<u8>>> {
+    // Keep the file name so that SOPS detects the same format
+    let dir = tempfile::Builder::new()
+        .prefix("koishi-")
+        .tempdir()
+        .into_diagnostic()?;
+    let temp_file = dir
+        .path()
+        .join(file.file_name().unwrap_or("record".as_ref()));
+    std::fs::write(&temp_file, contents).into_diagnostic()?;
+
+    decrypt(workdir, &temp_file, extract)
+}
+
+pub(crate) fn encrypt(
+    workdir: &Path,
+    file: &Path,
+    contents: Zeroizing<Vec<u8>>,
+) -> miette::Result<()> {
+    with_backend(|b| b.encrypt(workdir, file, contents.clone()))
+}
+
+pub(crate) fn update(
+    workdir: &Path,
+    file: &Path,
+    contents: Zeroizing<Vec<u8>>,
+) -> miette::Result<()> {
+    with_backend(|b| b.update(workdir, file, contents.clone()))
+}
+
+pub(crate) fn set(
+    workdir: &Path,
+    file: &Path,
+    selector: &str,
+    contents: Zeroizing<String>,
+) -> miette::Result<()> {
+    with_backend(|b| b.set(workdir, file, selector, contents.clone()))
+}
+
+pub(crate) fn update_keys(workdir: &Path, file: &Path, yes: bool) -> miette::Result<()> {
+    with_backend(|b| b.update_keys(workdir, file, yes))
+}
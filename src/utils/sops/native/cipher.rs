@@ -0,0 +1,195 @@
+use crate::utils::document::Value;
+use aes_gcm::{
+    AesGcm,
+    aead::{Aead, KeyInit, Payload, consts::U32},
+    aes::Aes256,
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use miette::{IntoDiagnostic, miette};
+use rand::RngCore;
+use regex::Regex;
+use std::sync::LazyLock;
+use zeroize::Zeroizing;
+
+/// SOPS uses AES-GCM with a non-standard 256 bit nonce.
+type Cipher = AesGcm<Aes256, U32>;
+
+const NONCE_SIZE: usize = 32;
+const TAG_SIZE: usize = 16;
+
+static ENCRYPTED_VALUE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^ENC\[AES256_GCM,data:(.+),iv:(.+),tag:(.+),type:(.+)\]$").unwrap()
+});
+
+/// Length of the data key used to encrypt values.
+pub(super) const DATA_KEY_SIZE: usize = 32;
+
+pub(super) type DataKey = Zeroizing<[u8; DATA_KEY_SIZE]>;
+
+pub(super) fn generate_data_key() -> DataKey {
+    let mut key = Zeroizing::new([0u8; DATA_KEY_SIZE]);
+    rand::rng().fill_bytes(key.as_mut_slice());
+    key
+}
+
+/// Encrypts a scalar value, producing the `ENC[...]` representation used by SOPS.
+///
+/// Empty strings are left as they are, matching SOPS behaviour.
+pub(super) fn encrypt(
+    value: &Value,
+    key: &DataKey,
+    additional_data: &str,
+) -> miette::Result<String> {
+    let (plaintext, value_type) = match value {
+        Value::String(s) if s.is_empty() => return Ok(String::new()),
+        Value::String(s) => (Zeroizing::new(s.clone()), "str"),
+        Value::Integer(i) => (Zeroizing::new(i.to_string()), "int"),
+        Value::Float(f) => (Zeroizing::new(f.to_string()), "float"),
+        Value::Boolean(b) => (Zeroizing::new(b.to_string()), "bool"),
+        Value::Null | Value::Mapping(_) | Value::Sequence(_) => {
+            return Err(miette!("Only scalar values can be encrypted"));
+        }
+    };
+
+    let mut iv = [0u8; NONCE_SIZE];
+    rand::rng().fill_bytes(&mut iv);
+
+    let cipher = Cipher::new_from_slice(key.as_slice()).into_diagnostic()?;
+    let sealed = cipher
+        .encrypt(
+            (&iv).into(),
+            Payload {
+                msg: plaintext.as_bytes(),
+                aad: additional_data.as_bytes(),
+            },
+        )
+        .map_err(|_| miette!("Failed to encrypt value"))?;
+    let (data, tag) = sealed.split_at(sealed.len() - TAG_SIZE);
+
+    Ok(format!(
+        "ENC[AES256_GCM,data:{},iv:{},tag:{},type:{value_type}]",
+        BASE64_STANDARD.encode(data),
+        BASE64_STANDARD.encode(iv),
+        BASE64_STANDARD.encode(tag),
+    ))
+}
+
+/// Decrypts a value in the `ENC[...]` representation used by SOPS.
+pub(super) fn decrypt(
+    encrypted: &str,
+    key: &DataKey,
+    additional_data: &str,
+) -> miette::Result<Value> {
+    if encrypted.is_empty() {
+        return Ok(Value::String(String::new()));
+    }
+
+    let captures = ENCRYPTED_VALUE
+        .captures(encrypted)
+        .ok_or_else(|| miette!("Value is not in the SOPS encrypted format"))?;
+
+    let decode = |idx: usize| BASE64_STANDARD.decode(&captures[idx]).into_diagnostic();
+    let mut data = decode(1)?;
+    let iv = decode(2)?;
+    let tag = decode(3)?;
+
+    if iv.len() != NONCE_SIZE {
+        return Err(miette!("Unsupported IV length: {}", iv.len()));
+    }
+
+    data.extend_from_slice(&tag);
+
+    let cipher = Cipher::new_from_slice(key.as_slice()).into_diagnostic()?;
+    let plaintext = Zeroizing::new(
+        cipher
+            .decrypt(
+                iv.as_slice().into(),
+                Payload {
+                    msg: &data,
+                    aad: additional_data.as_bytes(),
+                },
+            )
+            .map_err(|_| miette!("Failed to decrypt value (incorrect key or tampered data)"))?,
+    );
+    let plaintext = Zeroizing::new(String::from_utf8(plaintext.to_vec()).map_err(|_| {
+        miette::Report::new(super::super::Unsupported(
+            "values that are not valid UTF-8".into(),
+        ))
+    })?);
+
+    match &captures[4] {
+        "str" | "bytes" | "comment" => Ok(Value::String(plaintext.to_string())),
+        "int" => Ok(Value::Integer(plaintext.parse().into_diagnostic()?)),
+        "float" => Ok(Value::Float(plaintext.parse().into_diagnostic()?)),
+        "bool" => match plaintext.to_lowercase().as_str() {
+            "true" | "1" | "t" => Ok(Value::Boolean(true)),
+            "false" | "0" | "f" => Ok(Value::Boolean(false)),
+            other => Err(miette!("Invalid boolean value: {other}")),
+        },
+        other => Err(miette!("Unknown encrypted value type: {other}")),
+    }
+}
+
+/// The byte representation of a value used when computing the MAC of a file.
+pub(super) fn mac_bytes(value: &Value) -> Option<Zeroizing<Vec<u8>>> {
+    let bytes = match value {
+        Value::String(s) => s.as_bytes().to_vec(),
+        Value::Integer(i) => i.to_string().into_bytes(),
+        Value::Float(f) => f.to_string().into_bytes(),
+        // SOPS uses Python style booleans here, for compatibility with the original implementation
+        Value::Boolean(true) => b"True".to_vec(),
+        Value::Boolean(false) => b"False".to_vec(),
+        Value::Null | Value::Mapping(_) | Value::Sequence(_) => return None,
+    };
+
+    Some(Zeroizing::new(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let key = generate_data_key();
+
+        for value in [
+            Value::String("hunter2".into()),
+            Value::Integer(42),
+            Value::Float(1.5),
+            Value::Boolean(true),
+        ] {
+            let encrypted = encrypt(&value, &key, "password:").unwrap();
+            assert!(ENCRYPTED_VALUE.is_match(&encrypted));
+            assert_eq!(decrypt(&encrypted, &key, "password:").unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn empty_string_is_not_encrypted() {
+        let key = generate_data_key();
+        let value = Value::String(String::new());
+
+        let encrypted = encrypt(&value, &key, "password:").unwrap();
+        assert_eq!(encrypted, "");
+        assert_eq!(decrypt(&encrypted, &key, "password:").unwrap(), value);
+    }
+
+    #[test]
+    fn wrong_additional_data() {
+        let key = generate_data_key();
+        let encrypted = encrypt(&Value::String("hunter2".into()), &key, "password:").unwrap();
+        assert!(decrypt(&encrypted, &key, "username:").is_err());
+    }
+
+    #[test]
+    fn wrong_key() {
+        let encrypted = encrypt(
+            &Value::String("hunter2".into()),
+            &generate_data_key(),
+            "password:",
+        )
+        .unwrap();
+        assert!(decrypt(&encrypted, &generate_data_key(), "password:").is_err());
+    }
+}
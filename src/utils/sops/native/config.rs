@@ -0,0 +1,289 @@
+use super::super::Unsupported;
+use crate::utils::document::{Format, PathElement, Value};
+use miette::{Context, IntoDiagnostic, miette};
+use regex::Regex;
+use std::path::Path;
+
+const SOPS_CONFIG_FILENAME: &str = ".sops.yaml";
+
+/// Settings controlling which values in a file are encrypted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct EncryptionRules {
+    pub(super) unencrypted_suffix: Option<String>,
+    pub(super) encrypted_suffix: Option<String>,
+    pub(super) unencrypted_regex: Option<String>,
+    pub(super) encrypted_regex: Option<String>,
+    pub(super) mac_only_encrypted: bool,
+}
+
+impl EncryptionRules {
+    pub(super) const DEFAULT_UNENCRYPTED_SUFFIX: &str = "_unencrypted";
+
+    /// Reads the encryption rules from a mapping (either a creation rule or SOPS metadata).
+    pub(super) fn from_mapping(mapping: &Value) -> miette::Result<Self> {
+        let get_string = |key: &str| match mapping.get(&[PathElement::Key(key.into())]) {
+            Some(Value::String(s)) => Some(s.clone()),
+            _ => None,
+        };
+
+        let rules = Self {
+            unencrypted_suffix: get_string("unencrypted_suffix"),
+            encrypted_suffix: get_string("encrypted_suffix"),
+            unencrypted_regex: get_string("unencrypted_regex"),
+            encrypted_regex: get_string("encrypted_regex"),
+            mac_only_encrypted: matches!(
+                mapping.get(&[PathElement::Key("mac_only_encrypted".into())]),
+                Some(Value::Boolean(true))
+            ),
+        };
+
+        if mapping
+            .get(&[PathElement::Key("encrypted_comment_regex".into())])
+            .is_some()
+            || mapping
+                .get(&[PathElement::Key("unencrypted_comment_regex".into())])
+                .is_some()
+        {
+            return Err(miette::Report::new(Unsupported(
+                "comment encryption rules".into(),
+            )));
+        }
+
+        Ok(rules.with_default())
+    }
+
+    /// Applies the SOPS default of an unencrypted suffix if no other rule is set.
+    fn with_default(mut self) -> Self {
+        if self.unencrypted_suffix.is_none()
+            && self.encrypted_suffix.is_none()
+            && self.unencrypted_regex.is_none()
+            && self.encrypted_regex.is_none()
+        {
+            self.unencrypted_suffix = Some(Self::DEFAULT_UNENCRYPTED_SUFFIX.into());
+        }
+        self
+    }
+
+    /// Determines if the value at a given path (of mapping keys) should be encrypted.
+    pub(super) fn should_encrypt(&self, path: &[String]) -> miette::Result<bool> {
+        if let Some(suffix) = &self.unencrypted_suffix {
+            return Ok(!path.iter().any(|k| k.ends_with(suffix)));
+        }
+
+        if let Some(suffix) = &self.encrypted_suffix {
+            return Ok(path.iter().any(|k| k.ends_with(suffix)));
+        }
+
+        if let Some(regex) = &self.unencrypted_regex {
+            let regex = Regex::new(regex).into_diagnostic()?;
+            return Ok(!path.iter().any(|k| regex.is_match(k)));
+        }
+
+        if let Some(regex) = &self.encrypted_regex {
+            let regex = Regex::new(regex).into_diagnostic()?;
+            return Ok(path.iter().any(|k| regex.is_match(k)));
+        }
+
+        Ok(true)
+    }
+
+    /// Writes the encryption rules into SOPS metadata.
+    pub(super) fn write_to(&self, metadata: &mut Vec<(String, Value)>) {
+        for (key, value) in [
+            ("unencrypted_suffix", &self.unencrypted_suffix),
+            ("encrypted_suffix", &self.encrypted_suffix),
+            ("unencrypted_regex", &self.unencrypted_regex),
+            ("encrypted_regex", &self.encrypted_regex),
+        ] {
+            if let Some(value) = value {
+                metadata.push((key.into(), Value::String(value.clone())));
+            }
+        }
+
+        if self.mac_only_encrypted {
+            metadata.push(("mac_only_encrypted".into(), Value::Boolean(true)));
+        }
+    }
+}
+
+/// The parts of a SOPS creation rule that are relevant to encrypting a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct CreationRule {
+    pub(super) age_recipients: Vec<String>,
+    pub(super) encryption: EncryptionRules,
+}
+
+/// Finds the creation rule that applies to a file in the store.
+pub(super) fn creation_rule(workdir: &Path, file: &Path) -> miette::Result<CreationRule> {
+    let config_filename = workdir.join(SOPS_CONFIG_FILENAME);
+    let config = std::fs::read(&config_filename)
+        .into_diagnostic()
+        .wrap_err(format!(
+            "Failed to read SOPS config from `{}`",
+            config_filename.display()
+        ))?;
+    let config = Value::parse(Format::Yaml, &config).wrap_err("Failed to parse SOPS config")?;
+
+    // Rules are matched against the path of the file relative to the config file
+    let relative = file.strip_prefix(workdir).unwrap_or(file);
+    let relative = relative.to_string_lossy();
+
+    let rules = match config.get(&[PathElement::Key("creation_rules".into())]) {
+        Some(Value::Sequence(rules)) => rules,
+        _ => return Err(miette!("SOPS config does not contain any creation rules")),
+    };
+
+    for rule in rules {
+        let matches = match rule.get(&[PathElement::Key("path_regex".into())]) {
+            Some(Value::String(regex)) => Regex::new(regex)
+                .into_diagnostic()
+                .wrap_err(format!("Invalid path_regex `{regex}`"))?
+                .is_match(&relative),
+            _ => true,
+        };
+
+        if matches {
+            return parse_creation_rule(rule);
+        }
+    }
+
+    Err(miette!(
+        "No matching creation rule found for `{relative}` in SOPS config"
+    ))
+}
+
+fn parse_creation_rule(rule: &Value) -> miette::Result<CreationRule> {
+    for key in [
+        "pgp",
+        "kms",
+        "gcp_kms",
+        "azure_keyvault",
+        "hc_vault_transit_uri",
+    ] {
+        if rule.get(&[PathElement::Key(key.into())]).is_some() {
+            return Err(miette::Report::new(Unsupported(format!(
+                "`{key}` keys in creation rule"
+            ))));
+        }
+    }
+
+    let mut age_recipients = match rule.get(&[PathElement::Key("age".into())]) {
+        Some(value) => parse_recipient_list(value)?,
+        None => Vec::new(),
+    };
+
+    if let Some(groups) = rule.get(&[PathElement::Key("key_groups".into())]) {
+        let groups = match groups {
+            Value::Sequence(groups) => groups,
+            _ => return Err(miette!("`key_groups` must be a list")),
+        };
+
+        if groups.len() > 1 {
+            return Err(miette::Report::new(Unsupported(
+                "multiple key groups (Shamir secret sharing)".into(),
+            )));
+        }
+
+        for group in groups {
+            if let Value::Mapping(items) = group {
+                for (key, value) in items {
+                    if key == "age" {
+                        age_recipients.extend(parse_recipient_list(value)?);
+                    } else {
+                        return Err(miette::Report::new(Unsupported(format!(
+                            "`{key}` keys in key group"
+                        ))));
+                    }
+                }
+            }
+        }
+    }
+
+    if age_recipients.is_empty() {
+        return Err(miette!("Creation rule does not specify any age recipients"));
+    }
+
+    Ok(CreationRule {
+        age_recipients,
+        encryption: EncryptionRules::from_mapping(rule)?,
+    })
+}
+
+/// Parses recipients given either as a comma separated string or as a list.
+fn parse_recipient_list(value: &Value) -> miette::Result<Vec<String>> {
+    match value {
+        Value::String(s) => Ok(s
+            .split(',')
+            .map(|r| r.trim().to_owned())
+            .filter(|r| !r.is_empty())
+            .collect()),
+        Value::Sequence(items) => items
+            .iter()
+            .map(|i| match i {
+                Value::String(s) => Ok(s.trim().to_owned()),
+                _ => Err(miette!("Age recipients must be strings")),
+            })
+            .collect(),
+        _ => Err(miette!("Age recipients must be a string or a list")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn rule_matching() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(SOPS_CONFIG_FILENAME),
+            r#"
+creation_rules:
+  - path_regex: ^team/
+    age: age1team1, age1team2
+  - key_groups:
+      - age:
+          - age1personal
+"#,
+        )
+        .unwrap();
+
+        let rule = creation_rule(dir.path(), &dir.path().join("team/db.yaml")).unwrap();
+        assert_eq!(rule.age_recipients, vec!["age1team1", "age1team2"]);
+
+        let rule = creation_rule(dir.path(), Path::new("personal/email.yaml")).unwrap();
+        assert_eq!(rule.age_recipients, vec!["age1personal"]);
+        assert_eq!(
+            rule.encryption.unencrypted_suffix.as_deref(),
+            Some(EncryptionRules::DEFAULT_UNENCRYPTED_SUFFIX)
+        );
+    }
+
+    #[test]
+    fn unsupported_key_types() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(SOPS_CONFIG_FILENAME),
+            "creation_rules:\n  - pgp: ABCDEF\n",
+        )
+        .unwrap();
+
+        let err = creation_rule(dir.path(), Path::new("a.yaml")).unwrap_err();
+        assert!(err.downcast_ref::<Unsupported>().is_some());
+    }
+
+    #[test]
+    fn should_encrypt() {
+        let default = EncryptionRules::default().with_default();
+        assert!(default.should_encrypt(&["password".into()]).unwrap());
+        assert!(!default.should_encrypt(&["url_unencrypted".into()]).unwrap());
+
+        let regex = EncryptionRules {
+            encrypted_regex: Some("^(password|otp)$".into()),
+            ..Default::default()
+        };
+        assert!(regex.should_encrypt(&["password".into()]).unwrap());
+        assert!(!regex.should_encrypt(&["username".into()]).unwrap());
+    }
+}
@@ -0,0 +1,165 @@
+use super::cipher::{DATA_KEY_SIZE, DataKey};
+use age::{
+    Identity, IdentityFile, Recipient,
+    armor::{ArmoredReader, ArmoredWriter, Format},
+};
+use miette::{Context, IntoDiagnostic, miette};
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+use zeroize::Zeroizing;
+
+/// Loads age identities from the same locations as SOPS does.
+///
+/// In order of precedence: the `SOPS_AGE_KEY` environment variable, the file given by
+/// `SOPS_AGE_KEY_FILE`, the output of the command given by `SOPS_AGE_KEY_CMD` and finally the
+/// default SOPS keys file in the user's config directory.
+pub(super) fn load_identities() -> miette::Result<Vec<Box<dyn Identity + Send + Sync>>> {
+    let mut identities = Vec::new();
+
+    if let Ok(key) = std::env::var("SOPS_AGE_KEY") {
+        identities.extend(parse_identities(key.as_bytes())?);
+    }
+
+    if let Ok(filename) = std::env::var("SOPS_AGE_KEY_FILE") {
+        identities.extend(read_identities_file(PathBuf::from(filename))?);
+    }
+
+    if let Ok(cmd) = std::env::var("SOPS_AGE_KEY_CMD") {
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&cmd)
+            .output()
+            .into_diagnostic()
+            .wrap_err("Failed to run SOPS_AGE_KEY_CMD")?;
+        let output = Zeroizing::new(output.stdout);
+        identities.extend(parse_identities(&output)?);
+    }
+
+    if let Some(filename) = default_keys_file() {
+        if filename.is_file() {
+            identities.extend(read_identities_file(filename)?);
+        }
+    }
+
+    if identities.is_empty() {
+        Err(miette!("No age identities found"))
+    } else {
+        Ok(identities)
+    }
+}
+
+fn default_keys_file() -> Option<PathBuf> {
+    let config_dir = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var("HOME").ok()?).join(".config"),
+    };
+
+    Some(config_dir.join("sops").join("age").join("keys.txt"))
+}
+
+fn read_identities_file(filename: PathBuf) -> miette::Result<Vec<Box<dyn Identity + Send + Sync>>> {
+    let contents = Zeroizing::new(
+        std::fs::read(&filename)
+            .into_diagnostic()
+            .wrap_err(format!(
+                "Failed to read age keys from `{}`",
+                filename.display()
+            ))?,
+    );
+    parse_identities(&contents)
+}
+
+fn parse_identities(data: &[u8]) -> miette::Result<Vec<Box<dyn Identity + Send + Sync>>> {
+    IdentityFile::from_buffer(data)
+        .into_diagnostic()?
+        .into_identities()
+        .into_diagnostic()
+        .wrap_err("Failed to parse age identities")
+}
+
+/// Parses an age recipient (public key).
+pub(super) fn parse_recipient(recipient: &str) -> miette::Result<age::x25519::Recipient> {
+    age::x25519::Recipient::from_str(recipient.trim())
+        .map_err(|e| miette!("Invalid age recipient `{recipient}`: {e}"))
+}
+
+/// Encrypts the data key for a single recipient, producing an ASCII armored age file.
+pub(super) fn wrap_data_key(key: &DataKey, recipient: &str) -> miette::Result<String> {
+    let recipient = parse_recipient(recipient)?;
+
+    let encryptor = age::Encryptor::with_recipients(std::iter::once(&recipient as &dyn Recipient))
+        .into_diagnostic()?;
+
+    let mut out = Vec::new();
+    let armor = ArmoredWriter::wrap_output(&mut out, Format::AsciiArmor).into_diagnostic()?;
+    let mut writer = encryptor.wrap_output(armor).into_diagnostic()?;
+    writer.write_all(key.as_slice()).into_diagnostic()?;
+    let _ = writer
+        .finish()
+        .and_then(|armor| armor.finish())
+        .into_diagnostic()?;
+
+    String::from_utf8(out).into_diagnostic()
+}
+
+/// Decrypts a data key that was encrypted with age, using any of the given identities.
+pub(super) fn unwrap_data_key(
+    encrypted: &str,
+    identities: &[Box<dyn Identity + Send + Sync>],
+) -> miette::Result<DataKey> {
+    let decryptor =
+        age::Decryptor::new(ArmoredReader::new(encrypted.as_bytes())).into_diagnostic()?;
+
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|i| i.as_ref() as &dyn Identity))
+        .into_diagnostic()?;
+
+    let mut plaintext = Zeroizing::new(Vec::new());
+    let _ = reader.read_to_end(&mut plaintext).into_diagnostic()?;
+
+    let key: [u8; DATA_KEY_SIZE] = plaintext
+        .as_slice()
+        .try_into()
+        .map_err(|_| miette!("Data key has an invalid length"))?;
+
+    Ok(Zeroizing::new(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::secrecy::ExposeSecret;
+
+    #[test]
+    fn wrap_and_unwrap() {
+        let identity = age::x25519::Identity::generate();
+        let recipient = identity.to_public().to_string();
+
+        let key = super::super::cipher::generate_data_key();
+        let wrapped = wrap_data_key(&key, &recipient).unwrap();
+        assert!(wrapped.starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+
+        let identities = parse_identities(identity.to_string().expose_secret().as_bytes()).unwrap();
+        let unwrapped = unwrap_data_key(&wrapped, &identities).unwrap();
+        assert_eq!(*unwrapped, *key);
+    }
+
+    #[test]
+    fn unwrap_with_wrong_identity() {
+        let recipient = age::x25519::Identity::generate().to_public().to_string();
+        let wrapped =
+            wrap_data_key(&super::super::cipher::generate_data_key(), &recipient).unwrap();
+
+        let other = age::x25519::Identity::generate();
+        let identities = parse_identities(other.to_string().expose_secret().as_bytes()).unwrap();
+        assert!(unwrap_data_key(&wrapped, &identities).is_err());
+    }
+
+    #[test]
+    fn invalid_recipient() {
+        assert!(parse_recipient("not a key").is_err());
+    }
+}
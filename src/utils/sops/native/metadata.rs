@@ -0,0 +1,197 @@
+use super::{
+    super::Unsupported,
+    cipher::{self, DataKey},
+    config::{CreationRule, EncryptionRules},
+    keys,
+};
+use crate::utils::document::{PathElement, Value};
+use miette::miette;
+
+/// Version of SOPS whose file format is produced.
+const SOPS_VERSION: &str = "3.9.0";
+
+/// Metadata keys that hold encrypted data keys for key types other than age.
+const OTHER_KEY_TYPES: &[&str] = &["kms", "gcp_kms", "azure_kv", "hc_vault", "pgp"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct AgeKey {
+    pub(super) recipient: String,
+    pub(super) enc: String,
+}
+
+/// The `sops` metadata block of an encrypted file.
+#[derive(Debug, Clone)]
+pub(super) struct Metadata {
+    pub(super) age: Vec<AgeKey>,
+    pub(super) last_modified: String,
+    pub(super) mac: String,
+    pub(super) encryption: EncryptionRules,
+    /// Data keys encrypted with key types that are not supported natively, kept as they are
+    other_keys: Vec<(String, Value)>,
+}
+
+impl Metadata {
+    /// Creates metadata for a newly encrypted file.
+    pub(super) fn new(key: &DataKey, rule: &CreationRule, mac: &str) -> miette::Result<Self> {
+        let mut metadata = Self {
+            age: Vec::new(),
+            last_modified: String::new(),
+            mac: String::new(),
+            encryption: rule.encryption.clone(),
+            other_keys: Vec::new(),
+        };
+
+        metadata.set_recipients(key, &rule.age_recipients)?;
+        metadata.update_mac(key, mac)?;
+
+        Ok(metadata)
+    }
+
+    pub(super) fn parse(value: &Value) -> miette::Result<Self> {
+        let get = |key: &str| value.get(&[PathElement::Key(key.into())]);
+
+        if get("key_groups").is_some() {
+            return Err(miette::Report::new(Unsupported(
+                "multiple key groups (Shamir secret sharing)".into(),
+            )));
+        }
+
+        let age = match get("age") {
+            Some(Value::Sequence(items)) => items
+                .iter()
+                .map(|i| {
+                    match (
+                        i.get(&[PathElement::Key("recipient".into())]),
+                        i.get(&[PathElement::Key("enc".into())]),
+                    ) {
+                        (Some(Value::String(recipient)), Some(Value::String(enc))) => Ok(AgeKey {
+                            recipient: recipient.clone(),
+                            enc: enc.clone(),
+                        }),
+                        _ => Err(miette!("Invalid age key in SOPS metadata")),
+                    }
+                })
+                .collect::<miette::Result<_>>()?,
+            _ => Vec::new(),
+        };
+
+        let other_keys = OTHER_KEY_TYPES
+            .iter()
+            .filter_map(|key| match get(key) {
+                Some(Value::Sequence(items)) if !items.is_empty() => {
+                    Some((key.to_string(), Value::Sequence(items.clone())))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let get_string = |key: &str| match get(key) {
+            Some(Value::String(s)) => Ok(s.clone()),
+            _ => Err(miette!("SOPS metadata is missing `{key}`")),
+        };
+
+        Ok(Self {
+            age,
+            last_modified: get_string("lastmodified")?,
+            mac: get_string("mac")?,
+            encryption: EncryptionRules::from_mapping(value)?,
+            other_keys,
+        })
+    }
+
+    pub(super) fn to_value(&self) -> Value {
+        let mut items = self.other_keys.clone();
+
+        if !self.age.is_empty() {
+            items.push((
+                "age".into(),
+                Value::Sequence(
+                    self.age
+                        .iter()
+                        .map(|k| {
+                            Value::Mapping(vec![
+                                ("recipient".into(), Value::String(k.recipient.clone())),
+                                ("enc".into(), Value::String(k.enc.clone())),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ));
+        }
+
+        items.push((
+            "lastmodified".into(),
+            Value::String(self.last_modified.clone()),
+        ));
+        items.push(("mac".into(), Value::String(self.mac.clone())));
+        self.encryption.write_to(&mut items);
+        items.push(("version".into(), Value::String(SOPS_VERSION.into())));
+
+        Value::Mapping(items)
+    }
+
+    /// Checks if the data key is also encrypted with key types that cannot be handled natively.
+    pub(super) fn has_other_keys(&self) -> bool {
+        !self.other_keys.is_empty()
+    }
+
+    pub(super) fn recipients(&self) -> Vec<String> {
+        self.age.iter().map(|k| k.recipient.clone()).collect()
+    }
+
+    /// Encrypts the data key for a new set of age recipients.
+    pub(super) fn set_recipients(
+        &mut self,
+        key: &DataKey,
+        recipients: &[String],
+    ) -> miette::Result<()> {
+        self.age = recipients
+            .iter()
+            .map(|r| {
+                Ok(AgeKey {
+                    recipient: r.clone(),
+                    enc: keys::wrap_data_key(key, r)?,
+                })
+            })
+            .collect::<miette::Result<_>>()?;
+        Ok(())
+    }
+
+    /// Stores a newly computed MAC, updating the last modified time.
+    pub(super) fn update_mac(&mut self, key: &DataKey, mac: &str) -> miette::Result<()> {
+        self.last_modified = jiff::Timestamp::now()
+            .strftime("%Y-%m-%dT%H:%M:%SZ")
+            .to_string();
+        self.mac = cipher::encrypt(&Value::String(mac.into()), key, &self.last_modified)?;
+        Ok(())
+    }
+
+    /// Decrypts the data key using the available age identities.
+    pub(super) fn data_key(&self) -> miette::Result<DataKey> {
+        let identities = match keys::load_identities() {
+            Ok(identities) => identities,
+            Err(_) if self.has_other_keys() => {
+                return Err(miette::Report::new(Unsupported(
+                    "no age identities available for decryption".into(),
+                )));
+            }
+            Err(e) => return Err(e),
+        };
+
+        for key in &self.age {
+            if let Ok(data_key) = keys::unwrap_data_key(&key.enc, &identities) {
+                return Ok(data_key);
+            }
+        }
+
+        if self.has_other_keys() {
+            Err(miette::Report::new(Unsupported(
+                "data key is not decryptable with available age identities".into(),
+            )))
+        } else {
+            Err(miette!(
+                "Failed to decrypt data key with any available age identity"
+            ))
+        }
+    }
+}
@@ -0,0 +1,714 @@
+mod cipher;
+mod config;
+mod keys;
+mod metadata;
+mod tree;
+
+use super::{Backend, Unsupported};
+use crate::utils::document::{Format, PathElement, Value};
+use metadata::Metadata;
+use miette::{Context, IntoDiagnostic, miette};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// In-process backend supporting YAML, JSON and binary files encrypted with age keys.
+pub(super) struct Native;
+
+/// A SOPS encrypted file, split into its (still encrypted) values and metadata.
+struct EncryptedFile {
+    format: Format,
+    tree: Value,
+    metadata: Metadata,
+    /// Whether the file has YAML comments, which SOPS keeps but are lost when it is parsed
+    has_comments: bool,
+}
+
+impl EncryptedFile {
+    fn read(filename: &Path) -> miette::Result<Self> {
+        let format = supported_format(filename)?;
+
+        let contents = std::fs::read(filename)
+            .into_diagnostic()
+            .wrap_err(format!("Failed to read `{}`", filename.display()))?;
+
+        let mut tree = Value::parse(storage_format(format), &contents)
+            .wrap_err(format!("Failed to parse `{}`", filename.display()))?;
+
+        let metadata = tree
+            .remove(&[PathElement::Key("sops".into())])
+            .ok_or_else(|| miette!("`{}` is not encrypted with SOPS", filename.display()))?;
+
+        let has_comments = format == Format::Yaml
+            && contents
+                .split(|b| *b == b'\n')
+                .any(|line| line.trim_ascii_start().starts_with(b"#"));
+
+        Ok(Self {
+            format,
+            tree,
+            metadata: Metadata::parse(&metadata)?,
+            has_comments,
+        })
+    }
+
+    /// Checks that the file can be written back without losing anything that is not parsed.
+    fn check_rewritable(&self) -> miette::Result<()> {
+        if self.has_comments {
+            Err(miette::Report::new(Unsupported(
+                "rewriting YAML files that contain comments".into(),
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn write(&self, filename: &Path) -> miette::Result<()> {
+        let mut document = self.tree.clone();
+        if let Value::Mapping(items) = &mut document {
+            items.push(("sops".into(), self.metadata.to_value()));
+        }
+
+        std::fs::write(filename, document.emit(storage_format(self.format))?)
+            .into_diagnostic()
+            .wrap_err(format!("Failed to write `{}`", filename.display()))
+    }
+
+    /// Decrypts all values in the file, verifying the MAC.
+    fn decrypt(&self, key: &cipher::DataKey) -> miette::Result<(Zeroizing<Value>, tree::Stash)> {
+        let mut stash = tree::Stash::default();
+        let (plaintext, mac) =
+            tree::decrypt(&self.tree, key, &self.metadata.encryption, &mut stash)?;
+        tree::verify_mac(&mac, &self.metadata.mac, key, &self.metadata.last_modified)?;
+        Ok((plaintext, stash))
+    }
+
+    /// Replaces all values in the file, reusing ciphertext of unchanged values.
+    fn update(
+        &mut self,
+        plaintext: &Value,
+        key: &cipher::DataKey,
+        stash: &tree::Stash,
+    ) -> miette::Result<()> {
+        let (tree, mac) = tree::encrypt(plaintext, key, &self.metadata.encryption, Some(stash))?;
+        self.tree = tree;
+        self.metadata.update_mac(key, &mac)
+    }
+}
+
+fn supported_format(filename: &Path) -> miette::Result<Format> {
+    match Format::from_path(filename) {
+        format @ (Format::Dotenv | Format::Ini) => Err(miette::Report::new(Unsupported(format!(
+            "{format:?} files"
+        )))),
+        format => Ok(format),
+    }
+}
+
+/// Parses plaintext to be encrypted.
+///
+/// Binary data that is not valid UTF-8 can't be stored as a string value, so is left to SOPS.
+fn parse_plaintext(format: Format, contents: &[u8]) -> miette::Result<Zeroizing<Value>> {
+    if format == Format::Binary && std::str::from_utf8(contents).is_err() {
+        return Err(miette::Report::new(Unsupported(
+            "binary data that is not valid UTF-8".into(),
+        )));
+    }
+
+    Ok(Zeroizing::new(Value::parse(format, contents)?))
+}
+
+/// The format encrypted files are stored in; binary files are stored as JSON.
+fn storage_format(format: Format) -> Format {
+    match format {
+        Format::Binary => Format::Json,
+        format => format,
+    }
+}
+
+/// Encrypts a plaintext document as a new file, using a new data key.
+fn encrypt_new(
+    workdir: &Path,
+    filename: &Path,
+    format: Format,
+    plaintext: &Value,
+) -> miette::Result<()> {
+    if !matches!(plaintext, Value::Mapping(_)) {
+        return Err(miette!("Document to encrypt must be a mapping"));
+    }
+
+    if plaintext.get(&[PathElement::Key("sops".into())]).is_some() {
+        return Err(miette!(
+            "Document to encrypt is already encrypted with SOPS"
+        ));
+    }
+
+    let rule = config::creation_rule(workdir, filename)?;
+
+    let key = cipher::generate_data_key();
+    let (tree, mac) = tree::encrypt(plaintext, &key, &rule.encryption, None)?;
+    let metadata = Metadata::new(&key, &rule, &mac)?;
+
+    EncryptedFile {
+        format,
+        tree,
+        metadata,
+        has_comments: false,
+    }
+    .write(filename)
+}
+
+impl Backend for Native {
+    fn edit(&self, workdir: &Path, file: &Path) -> miette::Result<()> {
+        let filename = workdir.join(file);
+        let format = supported_format(&filename)?;
+
+        let existing = if filename.exists() {
+            let encrypted = EncryptedFile::read(&filename)?;
+            encrypted.check_rewritable()?;
+            let key = encrypted.metadata.data_key()?;
+            let (plaintext, stash) = encrypted.decrypt(&key)?;
+            Some((encrypted, key, plaintext, stash))
+        } else {
+            None
+        };
+
+        let original = Zeroizing::new(match &existing {
+            Some((_, _, plaintext, _)) => plaintext.emit(format)?,
+            None => String::new(),
+        });
+
+        // Edit the plaintext in a private temporary directory, keeping the file name so that
+        // editors can detect the file type
+        let temp_dir = tempfile::Builder::new()
+            .prefix("koishi-")
+            .tempdir()
+            .into_diagnostic()?;
+        let temp_filename = temp_dir
+            .path()
+            .join(filename.file_name().unwrap_or("record".as_ref()));
+
+        std::fs::write(&temp_filename, original.as_bytes()).into_diagnostic()?;
+        let edit_result = crate::utils::file::edit_file_interactive(&temp_filename);
+        let edited = Zeroizing::new(std::fs::read(&temp_filename).into_diagnostic()?);
+
+        // Overwrite the plaintext before the temporary directory is removed
+        std::fs::write(&temp_filename, vec![0u8; edited.len()]).into_diagnostic()?;
+        edit_result?;
+
+        if edited.as_slice() == original.as_bytes() || edited.iter().all(u8::is_ascii_whitespace) {
+            return Ok(());
+        }
+
+        // Not `parse_plaintext`, falling back to SOPS here would throw away what was just edited
+        let plaintext = Zeroizing::new(Value::parse(format, &edited)?);
+
+        match existing {
+            Some((mut encrypted, key, _, stash)) => {
+                encrypted.update(&plaintext, &key, &stash)?;
+                encrypted.write(&filename)
+            }
+            None => encrypt_new(workdir, &filename, format, &plaintext),
+        }
+    }
+
+    fn decrypt(
+        &self,
+        workdir: &Path,
+        file: &Path,
+        extract: Option<&str>,
+    ) -> miette::Result<Zeroizing<Vec<u8>>> {
+        let encrypted = EncryptedFile::read(&workdir.join(file))?;
+        let key = encrypted.metadata.data_key()?;
+        let (plaintext, _) = encrypted.decrypt(&key)?;
+
+        let output = match extract {
+            None => plaintext.emit(encrypted.format)?,
+            Some(selector) => {
+                let path = crate::utils::document::parse_selector(selector)?;
+                let value = plaintext
+                    .get(&path)
+                    .ok_or_else(|| miette!("Selector `{selector}` not found in file"))?;
+
+                match value.scalar_to_string() {
+                    Some(s) => s,
+                    None => value.emit(storage_format(encrypted.format))?,
+                }
+            }
+        };
+
+        Ok(Zeroizing::new(output.into_bytes()))
+    }
+
+    fn encrypt(
+        &self,
+        workdir: &Path,
+        file: &Path,
+        contents: Zeroizing<Vec<u8>>,
+    ) -> miette::Result<()> {
+        let filename = workdir.join(file);
+        let format = supported_format(&filename)?;
+
+        let plaintext = parse_plaintext(format, &contents)?;
+
+        encrypt_new(workdir, &filename, format, &plaintext)
+    }
+
+    fn update(
+        &self,
+        workdir: &Path,
+        file: &Path,
+        contents: Zeroizing<Vec<u8>>,
+    ) -> miette::Result<()> {
+        let filename = workdir.join(file);
+        let format = supported_format(&filename)?;
+
+        let plaintext = parse_plaintext(format, &contents)?;
+
+        let mut encrypted = EncryptedFile::read(&filename)?;
+        encrypted.check_rewritable()?;
+
+        // The data key can only be kept if nobody else can read it than the creation rule says,
+        // otherwise a recipient that was removed could still read the new values
+        let rule = config::creation_rule(workdir, &filename)?;
+        if encrypted.metadata.has_other_keys()
+            || encrypted.metadata.recipients() != rule.age_recipients
+            || encrypted.metadata.encryption != rule.encryption
+        {
+            return encrypt_new(workdir, &filename, format, &plaintext);
+        }
+
+        let key = encrypted.metadata.data_key()?;
+        let (_, stash) = encrypted.decrypt(&key)?;
+
+        encrypted.update(&plaintext, &key, &stash)?;
+        encrypted.write(&filename)
+    }
+
+    fn set(
+        &self,
+        workdir: &Path,
+        file: &Path,
+        selector: &str,
+        contents: Zeroizing<String>,
+    ) -> miette::Result<()> {
+        let filename = workdir.join(file);
+        let path = crate::utils::document::parse_selector(selector)?;
+
+        let mut encrypted = EncryptedFile::read(&filename)?;
+        encrypted.check_rewritable()?;
+        let key = encrypted.metadata.data_key()?;
+        let (mut plaintext, stash) = encrypted.decrypt(&key)?;
+
+        plaintext.set(&path, Value::String(contents.to_string()))?;
+
+        encrypted.update(&plaintext, &key, &stash)?;
+        encrypted.write(&filename)
+    }
+
+    fn update_keys(&self, workdir: &Path, file: &Path, yes: bool) -> miette::Result<()> {
+        let filename = workdir.join(file);
+
+        let mut encrypted = EncryptedFile::read(&filename)?;
+        encrypted.check_rewritable()?;
+        if encrypted.metadata.has_other_keys() {
+            return Err(miette::Report::new(Unsupported(
+                "updating keys of files that use key types other than age".into(),
+            )));
+        }
+
+        let rule = config::creation_rule(workdir, &filename)?;
+
+        let current = encrypted.metadata.recipients();
+        if current == rule.age_recipients {
+            eprintln!("`{}` already up to date", file.display());
+            return Ok(());
+        }
+
+        eprintln!(
+            "The following changes will be made to `{}`:",
+            file.display()
+        );
+        for recipient in &current {
+            if !rule.age_recipients.contains(recipient) {
+                eprintln!("--- {recipient}");
+            }
+        }
+        for recipient in &rule.age_recipients {
+            if !current.contains(recipient) {
+                eprintln!("+++ {recipient}");
+            }
+        }
+
+        if !yes
+            && !inquire::Confirm::new("Is this okay?")
+                .with_default(false)
+                .prompt()
+                .into_diagnostic()?
+        {
+            return Ok(());
+        }
+
+        let key = encrypted.metadata.data_key()?;
+        encrypted
+            .metadata
+            .set_recipients(&key, &rule.age_recipients)?;
+        encrypted.write(&filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn setup() -> (tempfile::TempDir, String) {
+        let recipient = crate::utils::test::set_age_key();
+
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".sops.yaml"),
+            format!("creation_rules:\n  - age: {recipient}\n"),
+        )
+        .unwrap();
+
+        (dir, recipient)
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_yaml() {
+        let (dir, recipient) = setup();
+        let file = Path::new("test.yaml");
+
+        Native
+            .encrypt(
+                dir.path(),
+                file,
+                Zeroizing::new(b"username: alice\npassword: hunter2\n".to_vec()),
+            )
+            .unwrap();
+
+        let encrypted = std::fs::read_to_string(dir.path().join(file)).unwrap();
+        assert!(!encrypted.contains("hunter2"));
+        assert!(encrypted.contains(&recipient));
+
+        let decrypted = Native.decrypt(dir.path(), file, None).unwrap();
+        assert_eq!(
+            decrypted.as_slice(),
+            b"username: alice\npassword: hunter2\n"
+        );
+
+        let password = Native
+            .decrypt(dir.path(), file, Some("[\"password\"]"))
+            .unwrap();
+        assert_eq!(password.as_slice(), b"hunter2");
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_binary() {
+        let (dir, _) = setup();
+        let file = Path::new("notes");
+
+        Native
+            .encrypt(dir.path(), file, Zeroizing::new(b"some notes\n".to_vec()))
+            .unwrap();
+
+        let decrypted = Native.decrypt(dir.path(), file, None).unwrap();
+        assert_eq!(decrypted.as_slice(), b"some notes\n");
+    }
+
+    #[test]
+    fn set_only_changes_one_value() {
+        let (dir, _) = setup();
+        let file = Path::new("test.json");
+
+        Native
+            .encrypt(
+                dir.path(),
+                file,
+                Zeroizing::new(br#"{"username": "alice", "password": "hunter2"}"#.to_vec()),
+            )
+            .unwrap();
+        let before = EncryptedFile::read(&dir.path().join(file)).unwrap();
+
+        Native
+            .set(
+                dir.path(),
+                file,
+                "[\"password\"]",
+                Zeroizing::new("correct horse".into()),
+            )
+            .unwrap();
+        let after = EncryptedFile::read(&dir.path().join(file)).unwrap();
+
+        let username = [PathElement::Key("username".into())];
+        let password = [PathElement::Key("password".into())];
+        assert_eq!(before.tree.get(&username), after.tree.get(&username));
+        assert_ne!(before.tree.get(&password), after.tree.get(&password));
+
+        let password = Native
+            .decrypt(dir.path(), file, Some("[\"password\"]"))
+            .unwrap();
+        assert_eq!(password.as_slice(), b"correct horse");
+    }
+
+    #[test]
+    fn update_keeps_unchanged_values() {
+        let (dir, _) = setup();
+        let file = Path::new("test.yaml");
+
+        Native
+            .encrypt(
+                dir.path(),
+                file,
+                Zeroizing::new(b"username: alice\npassword: hunter2\n".to_vec()),
+            )
+            .unwrap();
+        let before = EncryptedFile::read(&dir.path().join(file)).unwrap();
+
+        Native
+            .update(
+                dir.path(),
+                file,
+                Zeroizing::new(b"username: alice\npin: 1234\n".to_vec()),
+            )
+            .unwrap();
+        let after = EncryptedFile::read(&dir.path().join(file)).unwrap();
+
+        let username = [PathElement::Key("username".into())];
+        assert_eq!(before.tree.get(&username), after.tree.get(&username));
+        assert_eq!(after.tree.get(&[PathElement::Key("password".into())]), None);
+
+        let pin = Native.decrypt(dir.path(), file, Some("[\"pin\"]")).unwrap();
+        assert_eq!(pin.as_slice(), b"1234");
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let (dir, _) = setup();
+        let file = Path::new("test.yaml");
+
+        Native
+            .encrypt(
+                dir.path(),
+                file,
+                Zeroizing::new(b"a: one\nb_unencrypted: two\n".to_vec()),
+            )
+            .unwrap();
+
+        let filename = dir.path().join(file);
+        let contents = std::fs::read_to_string(&filename).unwrap();
+        std::fs::write(
+            &filename,
+            contents.replace("b_unencrypted: two", "b_unencrypted: 2x"),
+        )
+        .unwrap();
+
+        assert!(Native.decrypt(dir.path(), file, None).is_err());
+    }
+
+    #[test]
+    fn update_keys_adds_recipient() {
+        let (dir, recipient) = setup();
+        let file = Path::new("test.yaml");
+
+        Native
+            .encrypt(dir.path(), file, Zeroizing::new(b"a: one\n".to_vec()))
+            .unwrap();
+
+        let other = age::x25519::Identity::generate().to_public().to_string();
+        std::fs::write(
+            dir.path().join(".sops.yaml"),
+            format!("creation_rules:\n  - age: {recipient},{other}\n"),
+        )
+        .unwrap();
+
+        Native.update_keys(dir.path(), file, true).unwrap();
+
+        let encrypted = EncryptedFile::read(&dir.path().join(file)).unwrap();
+        assert_eq!(encrypted.metadata.recipients(), vec![recipient, other]);
+
+        let decrypted = Native.decrypt(dir.path(), file, Some("[\"a\"]")).unwrap();
+        assert_eq!(decrypted.as_slice(), b"one");
+    }
+
+    #[test]
+    fn update_uses_new_key_for_removed_recipient() {
+        let (dir, recipient) = setup();
+        let file = Path::new("test.yaml");
+        let filename = dir.path().join(file);
+
+        let removed = age::x25519::Identity::generate();
+        std::fs::write(
+            dir.path().join(".sops.yaml"),
+            format!(
+                "creation_rules:\n  - age: {recipient},{}\n",
+                removed.to_public()
+            ),
+        )
+        .unwrap();
+
+        Native
+            .encrypt(
+                dir.path(),
+                file,
+                Zeroizing::new(b"username: alice\npassword: hunter2\n".to_vec()),
+            )
+            .unwrap();
+
+        // The removed recipient has a copy of the data key from before
+        let before = EncryptedFile::read(&filename).unwrap();
+        let identities: Vec<Box<dyn age::Identity + Send + Sync>> = vec![Box::new(removed)];
+        let old_key = keys::unwrap_data_key(&before.metadata.age[1].enc, &identities).unwrap();
+
+        std::fs::write(
+            dir.path().join(".sops.yaml"),
+            format!("creation_rules:\n  - age: {recipient}\n"),
+        )
+        .unwrap();
+
+        Native
+            .update(
+                dir.path(),
+                file,
+                Zeroizing::new(b"username: alice\npassword: hunter3\n".to_vec()),
+            )
+            .unwrap();
+
+        let after = EncryptedFile::read(&filename).unwrap();
+        assert_eq!(after.metadata.recipients(), vec![recipient]);
+        assert!(after.decrypt(&old_key).is_err());
+
+        let username = [PathElement::Key("username".into())];
+        assert_ne!(before.tree.get(&username), after.tree.get(&username));
+
+        let password = Native
+            .decrypt(dir.path(), file, Some("[\"password\"]"))
+            .unwrap();
+        assert_eq!(password.as_slice(), b"hunter3");
+    }
+
+    /// A file encrypted by SOPS itself (from the parity tests of the `rops` crate), for the age
+    /// identity in `crate::utils::test::FIXTURE_AGE_KEY`.
+    const SOPS_FILE: &str = include_str!("testdata/age_example.yaml");
+
+    fn sops_file() -> (tempfile::TempDir, &'static Path) {
+        let (dir, _) = setup();
+        let file = Path::new("example.yaml");
+        std::fs::write(dir.path().join(file), SOPS_FILE).unwrap();
+        (dir, file)
+    }
+
+    #[test]
+    fn decrypt_sops_file() {
+        let (dir, file) = sops_file();
+
+        let decrypted = Native.decrypt(dir.path(), file, None).unwrap();
+        assert_eq!(
+            Value::parse(Format::Yaml, &decrypted).unwrap(),
+            Value::parse(
+                Format::Yaml,
+                b"hello: Welcome to SOPS! Edit this file as you please!
+example_key: example_value
+example_array:
+- example_value1
+- example_value2
+example_number: 1234.56789
+example_booleans:
+- true
+- false
+"
+            )
+            .unwrap()
+        );
+
+        let value = Native
+            .decrypt(dir.path(), file, Some("[\"example_array\"][1]"))
+            .unwrap();
+        assert_eq!(value.as_slice(), b"example_value2");
+    }
+
+    #[test]
+    fn update_sops_file() {
+        let (dir, file) = sops_file();
+        let before = EncryptedFile::read(&dir.path().join(file)).unwrap();
+
+        Native
+            .set(
+                dir.path(),
+                file,
+                "[\"example_key\"]",
+                Zeroizing::new("changed".into()),
+            )
+            .unwrap();
+        let after = EncryptedFile::read(&dir.path().join(file)).unwrap();
+
+        // Only the changed value is re-encrypted, with the data key that SOPS generated
+        let hello = [PathElement::Key("hello".into())];
+        let example_key = [PathElement::Key("example_key".into())];
+        assert_eq!(before.tree.get(&hello), after.tree.get(&hello));
+        assert_ne!(before.tree.get(&example_key), after.tree.get(&example_key));
+        assert_eq!(before.metadata.age, after.metadata.age);
+        assert_ne!(before.metadata.last_modified, after.metadata.last_modified);
+
+        // Decrypting again checks the new MAC
+        let value = Native
+            .decrypt(dir.path(), file, Some("[\"example_key\"]"))
+            .unwrap();
+        assert_eq!(value.as_slice(), b"changed");
+        let value = Native
+            .decrypt(dir.path(), file, Some("[\"example_number\"]"))
+            .unwrap();
+        assert_eq!(value.as_slice(), b"1234.56789");
+    }
+
+    #[test]
+    fn comments_are_unsupported_when_rewriting() {
+        let (dir, file) = sops_file();
+        let filename = dir.path().join(file);
+        std::fs::write(
+            &filename,
+            format!("#ENC[AES256_GCM,data:x,iv:y,tag:z,type:comment]\n{SOPS_FILE}"),
+        )
+        .unwrap();
+
+        assert!(Native.decrypt(dir.path(), file, None).is_ok());
+
+        let err = Native
+            .set(dir.path(), file, "[\"hello\"]", Zeroizing::new("hi".into()))
+            .unwrap_err();
+        assert!(err.downcast_ref::<Unsupported>().is_some());
+
+        let err = Native
+            .update(dir.path(), file, Zeroizing::new(b"hello: hi\n".to_vec()))
+            .unwrap_err();
+        assert!(err.downcast_ref::<Unsupported>().is_some());
+    }
+
+    #[test]
+    fn non_utf8_binary_is_unsupported() {
+        let (dir, _) = setup();
+
+        let err = Native
+            .encrypt(
+                dir.path(),
+                Path::new("blob"),
+                Zeroizing::new(vec![0xff, 0xfe, 0x00]),
+            )
+            .unwrap_err();
+        assert!(err.downcast_ref::<Unsupported>().is_some());
+    }
+
+    #[test]
+    fn dotenv_is_unsupported() {
+        let (dir, _) = setup();
+
+        let err = Native
+            .encrypt(
+                dir.path(),
+                Path::new("test.env"),
+                Zeroizing::new(b"A=b\n".to_vec()),
+            )
+            .unwrap_err();
+        assert!(err.downcast_ref::<Unsupported>().is_some());
+    }
+}
@@ -0,0 +1,259 @@
+use super::{
+    cipher::{self, DataKey},
+    config::EncryptionRules,
+};
+use crate::utils::document::Value;
+use miette::miette;
+use sha2::{Digest, Sha512};
+use zeroize::Zeroizing;
+
+/// Plaintext and ciphertext of values seen while decrypting a file.
+///
+/// When the file is re-encrypted, unchanged values reuse their previous ciphertext so that only
+/// values which actually changed differ between versions of the file (as is the case with SOPS).
+#[derive(Default)]
+pub(super) struct Stash(Vec<(String, Zeroizing<Value>, String)>);
+
+impl Stash {
+    fn insert(&mut self, additional_data: &str, plaintext: &Value, encrypted: &str) {
+        self.0.push((
+            additional_data.to_owned(),
+            Zeroizing::new(plaintext.clone()),
+            encrypted.to_owned(),
+        ));
+    }
+
+    fn get(&self, additional_data: &str, plaintext: &Value) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(ad, p, _)| ad == additional_data && **p == *plaintext)
+            .map(|(_, _, e)| e.as_str())
+    }
+}
+
+/// Formats a path of keys as the additional data SOPS uses when encrypting a value.
+fn additional_data(path: &[String]) -> String {
+    let mut ad = path.join(":");
+    ad.push(':');
+    ad
+}
+
+fn format_mac(hasher: Sha512) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect()
+}
+
+/// Encrypts every value in a document, returning the encrypted document and its MAC.
+pub(super) fn encrypt(
+    plaintext: &Value,
+    key: &DataKey,
+    rules: &EncryptionRules,
+    stash: Option<&Stash>,
+) -> miette::Result<(Value, String)> {
+    fn walk(
+        value: &Value,
+        path: &mut Vec<String>,
+        key: &DataKey,
+        rules: &EncryptionRules,
+        stash: Option<&Stash>,
+        hasher: &mut Sha512,
+    ) -> miette::Result<Value> {
+        match value {
+            Value::Mapping(items) => Ok(Value::Mapping(
+                items
+                    .iter()
+                    .map(|(k, v)| {
+                        path.push(k.clone());
+                        let v = walk(v, path, key, rules, stash, hasher);
+                        let _ = path.pop();
+                        Ok((k.clone(), v?))
+                    })
+                    .collect::<miette::Result<_>>()?,
+            )),
+            Value::Sequence(items) => Ok(Value::Sequence(
+                items
+                    .iter()
+                    .map(|v| walk(v, path, key, rules, stash, hasher))
+                    .collect::<miette::Result<_>>()?,
+            )),
+            Value::Null => Ok(Value::Null),
+            scalar => {
+                let encrypted = rules.should_encrypt(path)?;
+
+                if encrypted || !rules.mac_only_encrypted {
+                    if let Some(bytes) = cipher::mac_bytes(scalar) {
+                        hasher.update(bytes.as_slice());
+                    }
+                }
+
+                if encrypted {
+                    let ad = additional_data(path);
+                    let ciphertext = match stash.and_then(|s| s.get(&ad, scalar)) {
+                        Some(ciphertext) => ciphertext.to_owned(),
+                        None => cipher::encrypt(scalar, key, &ad)?,
+                    };
+                    Ok(Value::String(ciphertext))
+                } else {
+                    Ok(scalar.clone())
+                }
+            }
+        }
+    }
+
+    let mut hasher = Sha512::new();
+    let encrypted = walk(plaintext, &mut Vec::new(), key, rules, stash, &mut hasher)?;
+    Ok((encrypted, format_mac(hasher)))
+}
+
+/// Decrypts every value in a document, returning the plaintext document and its MAC.
+///
+/// Ciphertexts of the decrypted values are recorded in `stash`.
+pub(super) fn decrypt(
+    encrypted: &Value,
+    key: &DataKey,
+    rules: &EncryptionRules,
+    stash: &mut Stash,
+) -> miette::Result<(Zeroizing<Value>, String)> {
+    fn walk(
+        value: &Value,
+        path: &mut Vec<String>,
+        key: &DataKey,
+        rules: &EncryptionRules,
+        stash: &mut Stash,
+        hasher: &mut Sha512,
+    ) -> miette::Result<Value> {
+        match value {
+            Value::Mapping(items) => Ok(Value::Mapping(
+                items
+                    .iter()
+                    .map(|(k, v)| {
+                        path.push(k.clone());
+                        let v = walk(v, path, key, rules, stash, hasher);
+                        let _ = path.pop();
+                        Ok((k.clone(), v?))
+                    })
+                    .collect::<miette::Result<_>>()?,
+            )),
+            Value::Sequence(items) => Ok(Value::Sequence(
+                items
+                    .iter()
+                    .map(|v| walk(v, path, key, rules, stash, hasher))
+                    .collect::<miette::Result<_>>()?,
+            )),
+            Value::Null => Ok(Value::Null),
+            scalar => {
+                let encrypted = rules.should_encrypt(path)?;
+
+                let plaintext = match scalar {
+                    Value::String(ciphertext) if encrypted => {
+                        let ad = additional_data(path);
+                        let plaintext = cipher::decrypt(ciphertext, key, &ad)?;
+                        stash.insert(&ad, &plaintext, ciphertext);
+                        plaintext
+                    }
+                    _ => scalar.clone(),
+                };
+
+                if encrypted || !rules.mac_only_encrypted {
+                    if let Some(bytes) = cipher::mac_bytes(&plaintext) {
+                        hasher.update(bytes.as_slice());
+                    }
+                }
+
+                Ok(plaintext)
+            }
+        }
+    }
+
+    let mut hasher = Sha512::new();
+    let plaintext = Zeroizing::new(walk(
+        encrypted,
+        &mut Vec::new(),
+        key,
+        rules,
+        stash,
+        &mut hasher,
+    )?);
+    Ok((plaintext, format_mac(hasher)))
+}
+
+/// Checks that the MAC computed from the plaintext matches the one stored in the file.
+pub(super) fn verify_mac(
+    computed: &str,
+    stored: &str,
+    key: &DataKey,
+    last_modified: &str,
+) -> miette::Result<()> {
+    match cipher::decrypt(stored, key, last_modified)? {
+        Value::String(stored) if stored == computed => Ok(()),
+        _ => Err(miette!(
+            "MAC mismatch, the file has been modified without being re-encrypted"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::document::Format;
+
+    fn rules() -> EncryptionRules {
+        EncryptionRules {
+            unencrypted_suffix: Some(EncryptionRules::DEFAULT_UNENCRYPTED_SUFFIX.into()),
+            ..Default::default()
+        }
+    }
+
+    fn document() -> Value {
+        Value::parse(
+            Format::Yaml,
+            b"username: alice\npassword: hunter2\nurl_unencrypted: https://example.com\nlist:\n  - 1\n  - true\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let key = cipher::generate_data_key();
+
+        let (encrypted, mac) = encrypt(&document(), &key, &rules(), None).unwrap();
+        assert_eq!(
+            encrypted.get(&[crate::utils::document::PathElement::Key(
+                "url_unencrypted".into()
+            )]),
+            Some(&Value::String("https://example.com".into()))
+        );
+
+        let mut stash = Stash::default();
+        let (decrypted, decrypted_mac) = decrypt(&encrypted, &key, &rules(), &mut stash).unwrap();
+        assert_eq!(*decrypted, document());
+        assert_eq!(mac, decrypted_mac);
+    }
+
+    #[test]
+    fn stash_reuses_ciphertext() {
+        let key = cipher::generate_data_key();
+        let (encrypted, _) = encrypt(&document(), &key, &rules(), None).unwrap();
+
+        let mut stash = Stash::default();
+        let _ = decrypt(&encrypted, &key, &rules(), &mut stash).unwrap();
+
+        let (reencrypted, _) = encrypt(&document(), &key, &rules(), Some(&stash)).unwrap();
+        assert_eq!(encrypted, reencrypted);
+    }
+
+    #[test]
+    fn mac_verification() {
+        let key = cipher::generate_data_key();
+        let (_, mac) = encrypt(&document(), &key, &rules(), None).unwrap();
+
+        let last_modified = "2025-01-01T00:00:00Z";
+        let stored = cipher::encrypt(&Value::String(mac.clone()), &key, last_modified).unwrap();
+
+        assert!(verify_mac(&mac, &stored, &key, last_modified).is_ok());
+        assert!(verify_mac("0000", &stored, &key, last_modified).is_err());
+    }
+}
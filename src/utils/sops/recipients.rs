@@ -0,0 +1,146 @@
+use crate::utils::document::{Format, PathElement, Value};
+use miette::{Context, IntoDiagnostic, miette};
+use regex::Regex;
+use std::{path::Path, sync::LazyLock};
+
+/// Key types that may appear in SOPS metadata, along with the field that identifies the key.
+const KEY_TYPES: &[(&str, &str)] = &[
+    ("age", "recipient"),
+    ("pgp", "fp"),
+    ("kms", "arn"),
+    ("gcp_kms", "resource_id"),
+    ("azure_kv", "vault_url"),
+    ("hc_vault", "vault_address"),
+];
+
+/// Matches flattened metadata keys in dotenv and INI files, e.g. `sops_age__list_0__map_recipient`.
+static FLATTENED_KEY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\w+)__list_\d+__map_(\w+?)\s*=\s*(.*)$").unwrap());
+
+/// Lists the keys (age recipients, PGP fingerprints, KMS ARNs, etc.) that the data key of an
+/// encrypted file is encrypted for, as found in its SOPS metadata.
+///
+/// Nothing is decrypted, so this works without access to any of the keys.
+pub(crate) fn recipients(file: &Path) -> miette::Result<Vec<String>> {
+    let contents = std::fs::read(file)
+        .into_diagnostic()
+        .wrap_err(format!("Failed to read `{}`", file.display()))?;
+
+    match Format::from_path(file) {
+        Format::Dotenv | Format::Ini => Ok(from_flattened(&String::from_utf8_lossy(&contents))),
+        format => {
+            // Binary files are stored as JSON
+            let format = match format {
+                Format::Binary => Format::Json,
+                format => format,
+            };
+
+            let document = Value::parse(format, &contents)
+                .wrap_err(format!("Failed to parse `{}`", file.display()))?;
+
+            let metadata = document
+                .get(&[PathElement::Key("sops".into())])
+                .ok_or_else(|| miette!("`{}` is not encrypted with SOPS", file.display()))?;
+
+            Ok(from_metadata(metadata))
+        }
+    }
+}
+
+fn from_metadata(metadata: &Value) -> Vec<String> {
+    let groups = match metadata.get(&[PathElement::Key("key_groups".into())]) {
+        Some(Value::Sequence(groups)) => groups.iter().collect(),
+        _ => vec![metadata],
+    };
+
+    let mut recipients = Vec::new();
+
+    for group in groups {
+        for (key_type, field) in KEY_TYPES {
+            if let Some(Value::Sequence(keys)) = group.get(&[PathElement::Key((*key_type).into())])
+            {
+                for key in keys {
+                    if let Some(Value::String(id)) = key.get(&[PathElement::Key((*field).into())]) {
+                        recipients.push(id.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    recipients
+}
+
+fn from_flattened(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| FLATTENED_KEY.captures(line))
+        .filter(|captures| {
+            KEY_TYPES
+                .iter()
+                .any(|(key_type, field)| captures[1].ends_with(key_type) && &captures[2] == *field)
+        })
+        .map(|captures| captures[3].trim().to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn yaml_recipients() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("record.yaml");
+        std::fs::write(
+            &file,
+            r#"password: ENC[AES256_GCM,data:abc=,iv:abc=,tag:abc=,type:str]
+sops:
+  age:
+    - recipient: age1one
+      enc: secret
+    - recipient: age1two
+      enc: secret
+  pgp:
+    - fp: ABCDEF
+      enc: secret
+  lastmodified: "2025-01-01T00:00:00Z"
+  mac: ENC[AES256_GCM,data:abc=,iv:abc=,tag:abc=,type:str]
+  version: 3.9.0
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            recipients(&file).unwrap(),
+            vec!["age1one", "age1two", "ABCDEF"]
+        );
+    }
+
+    #[test]
+    fn dotenv_recipients() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("record.env");
+        std::fs::write(
+            &file,
+            "password=ENC[AES256_GCM,data:abc=,iv:abc=,tag:abc=,type:str]\n\
+             sops_age__list_0__map_enc=secret\n\
+             sops_age__list_0__map_recipient=age1one\n\
+             sops_key_groups__list_0__map_age__list_0__map_recipient=age1two\n\
+             sops_version=3.9.0\n",
+        )
+        .unwrap();
+
+        assert_eq!(recipients(&file).unwrap(), vec!["age1one", "age1two"]);
+    }
+
+    #[test]
+    fn unencrypted_file() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("record.yaml");
+        std::fs::write(&file, "password: hunter2\n").unwrap();
+
+        assert!(recipients(&file).is_err());
+    }
+}
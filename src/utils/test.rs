@@ -1,4 +1,4 @@
-use std::{path::Path, process::Command};
+use std::path::Path;
 
 pub(crate) fn set_git_config() {
     #[allow(unsafe_code)]
@@ -12,9 +12,60 @@ pub(crate) fn set_git_config() {
 
 /// Initialize a git repo for testing.
 pub(crate) fn init_git_repo(path: &Path) {
-    let _ = Command::new("git")
-        .arg("init")
-        .arg(path)
-        .output()
-        .expect("failed to init git");
+    let _ = gix::init(path).expect("failed to init git");
+}
+
+/// Age identity that the SOPS files in test fixtures are encrypted for.
+pub(crate) const FIXTURE_AGE_KEY: &str =
+    "AGE-SECRET-KEY-1EQUCGFZH8UZKSZ0Z5N5T234YRNDT4U9H7QNYXWRRNJYDDVXE6FWSCPGNJ7";
+
+/// Sets a single age identity (shared by all tests in the process) for SOPS to use, returning
+/// its recipient.
+///
+/// The identity for test fixtures is also made available, so that they can be decrypted.
+pub(crate) fn set_age_key() -> String {
+    use age::secrecy::ExposeSecret;
+    use std::sync::OnceLock;
+
+    static IDENTITY: OnceLock<age::x25519::Identity> = OnceLock::new();
+
+    let identity = IDENTITY.get_or_init(age::x25519::Identity::generate);
+
+    #[allow(unsafe_code)]
+    unsafe {
+        std::env::set_var(
+            "SOPS_AGE_KEY",
+            format!(
+                "{}\n{FIXTURE_AGE_KEY}\n",
+                identity.to_string().expose_secret()
+            ),
+        );
+        std::env::remove_var("SOPS_AGE_KEY_FILE");
+        std::env::remove_var("SOPS_AGE_KEY_CMD");
+        std::env::set_var("XDG_CONFIG_HOME", "/nonexistent");
+    }
+
+    identity.to_public().to_string()
+}
+
+/// Initialises a store whose SOPS config encrypts everything for the test age identity.
+pub(crate) fn init_store(root: &Path) -> crate::secret_store::Store {
+    use miette::IntoDiagnostic;
+
+    set_git_config();
+    let recipient = set_age_key();
+
+    let store = crate::secret_store::Store::init(root).unwrap();
+    let _ = store
+        .git_operation("Use test key", || {
+            std::fs::write(
+                root.join(".sops.yaml"),
+                format!("creation_rules:\n  - age: {recipient}\n"),
+            )
+            .into_diagnostic()?;
+            Ok(vec![".sops.yaml".into()])
+        })
+        .unwrap();
+
+    store
 }
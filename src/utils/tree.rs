@@ -0,0 +1,168 @@
+use std::path::{Component, Path};
+
+/// A tree of records, grouped by directory, that can be rendered as text.
+#[derive(Debug, Default)]
+pub(crate) struct Tree {
+    entries: Vec<(String, Entry)>,
+}
+
+#[derive(Debug)]
+enum Entry {
+    /// A record, with annotations to show alongside its name
+    Record(Vec<String>),
+    Directory(Tree),
+}
+
+impl Tree {
+    /// Adds a record to the tree.
+    ///
+    /// Entries are rendered in the order they are inserted.
+    pub(crate) fn insert(&mut self, path: &Path, annotations: Vec<String>) {
+        let components: Vec<String> = path
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(c) => Some(c.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+
+        if let Some((name, directories)) = components.split_last() {
+            let mut tree = self;
+            for directory in directories {
+                tree = tree.directory(directory);
+            }
+            tree.entries
+                .push((name.clone(), Entry::Record(annotations)));
+        }
+    }
+
+    fn directory(&mut self, name: &str) -> &mut Tree {
+        let idx = match self
+            .entries
+            .iter()
+            .position(|(n, e)| n == name && matches!(e, Entry::Directory(_)))
+        {
+            Some(idx) => idx,
+            None => {
+                self.entries
+                    .push((name.to_owned(), Entry::Directory(Tree::default())));
+                self.entries.len() - 1
+            }
+        };
+
+        match &mut self.entries[idx].1 {
+            Entry::Directory(tree) => tree,
+            Entry::Record(_) => unreachable!(),
+        }
+    }
+
+    /// Counts the records in this tree, including those in subdirectories.
+    fn record_count(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|(_, e)| match e {
+                Entry::Record(_) => 1,
+                Entry::Directory(tree) => tree.record_count(),
+            })
+            .sum()
+    }
+
+    /// Renders the tree under a root label.
+    ///
+    /// Directories deeper than `max_depth` are collapsed, showing only the number of records they
+    /// contain.
+    pub(crate) fn render(&self, label: &str, max_depth: Option<usize>) -> String {
+        let mut out = format!("{label}\n");
+        self.render_entries(&mut out, "", 1, max_depth);
+        out
+    }
+
+    fn render_entries(
+        &self,
+        out: &mut String,
+        prefix: &str,
+        depth: usize,
+        max_depth: Option<usize>,
+    ) {
+        for (idx, (name, entry)) in self.entries.iter().enumerate() {
+            let last = idx == self.entries.len() - 1;
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+
+            out.push_str(prefix);
+            out.push_str(branch);
+            out.push_str(name);
+
+            match entry {
+                Entry::Record(annotations) => {
+                    if !annotations.is_empty() {
+                        out.push_str(&format!(" ({})", annotations.join(", ")));
+                    }
+                    out.push('\n');
+                }
+                Entry::Directory(tree) => {
+                    if max_depth.is_some_and(|max| depth >= max) {
+                        let count = tree.record_count();
+                        let noun = if count == 1 { "record" } else { "records" };
+                        out.push_str(&format!(" ({count} {noun})\n"));
+                    } else {
+                        out.push('\n');
+                        tree.render_entries(
+                            out,
+                            &format!("{prefix}{indent}"),
+                            depth + 1,
+                            max_depth,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Tree {
+        let mut tree = Tree::default();
+        tree.insert(Path::new("email.yaml"), vec!["2 attributes".into()]);
+        tree.insert(Path::new("web/a.yaml"), vec![]);
+        tree.insert(Path::new("web/b.yaml"), vec![]);
+        tree.insert(Path::new("web/shop/c.yaml"), vec![]);
+        tree.insert(Path::new("wifi/home.yaml"), vec![]);
+        tree
+    }
+
+    #[test]
+    fn render() {
+        assert_eq!(
+            tree().render("store", None),
+            "store
+├── email.yaml (2 attributes)
+├── web
+│   ├── a.yaml
+│   ├── b.yaml
+│   └── shop
+│       └── c.yaml
+└── wifi
+    └── home.yaml
+"
+        );
+    }
+
+    #[test]
+    fn render_with_depth() {
+        assert_eq!(
+            tree().render("store", Some(1)),
+            "store
+├── email.yaml (2 attributes)
+├── web (3 records)
+└── wifi (1 record)
+"
+        );
+    }
+}
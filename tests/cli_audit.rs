@@ -0,0 +1,68 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+#[test]
+fn audit_reports_issues() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set(
+        "web/site.yaml",
+        "username: alice\npassword: hunter2\notp: JBSWY3DPEHPK3PXP\n",
+    );
+    store.set("mail/work.yaml", "username: alice\npassword: hunter2\n");
+    store.set(
+        "bank.yaml",
+        "username: bob\npassword: correct-horse-battery-staple-1984\notp: JBSWY3DPEHPK3PXP\n",
+    );
+
+    let _ = store
+        .koishi()
+        .arg("audit")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Reused values:\n  mail/work.yaml:password, web/site.yaml:password\n",
+        ))
+        .stdout(predicate::str::contains("mail/work.yaml:password (~"))
+        .stdout(predicate::str::contains("Missing OTP:\n  mail/work.yaml\n"))
+        // Shared usernames and OTP secrets aren't secrets that should be unique
+        .stdout(predicate::str::contains("username").not())
+        .stdout(predicate::str::contains("otp").not())
+        .stdout(predicate::str::contains("hunter2").not());
+
+    Ok(())
+}
+
+#[test]
+fn audit_json() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set(
+        "a.yaml",
+        "password: correct-horse-battery-staple-1984\notp: x\n",
+    );
+    store.set("b.yaml", "token: abc\n");
+    store.set("c", "abc");
+
+    let output = store
+        .koishi()
+        .arg("--output")
+        .arg("json")
+        .arg("audit")
+        .output()?;
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "reused": [["b.yaml:token", "c"]],
+            "weak": [],
+            "missing_otp": [],
+            "skipped": [],
+            "errors": [],
+        })
+    );
+
+    Ok(())
+}
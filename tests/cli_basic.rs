@@ -15,3 +15,49 @@ fn help_root() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn unknown_store() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "[stores.personal]\npath = \"/nonexistent\"\n")?;
+
+    let mut cmd = Command::new(cargo_bin!("koishi"));
+
+    let _ = cmd
+        .env("KOISHI_CONFIG", &config)
+        .arg("--store")
+        .arg("team")
+        .arg("ls");
+
+    let _ = cmd
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No store named `team`"));
+
+    Ok(())
+}
+
+#[test]
+fn json_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "")?;
+
+    let mut cmd = Command::new(cargo_bin!("koishi"));
+
+    let _ = cmd
+        .env("KOISHI_CONFIG", &config)
+        .arg("--output")
+        .arg("json")
+        .arg("--store")
+        .arg("team")
+        .arg("ls");
+
+    let output = cmd.assert().failure().get_output().clone();
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr)?;
+    assert_eq!(error["message"], "Failed to determine store location");
+    assert_eq!(error["severity"], "error");
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+#[test]
+fn cp_directory() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set("web/site.yaml", "user: alice\npassword: hunter2\n");
+    store.set("web/pin", "1234");
+
+    let _ = store
+        .koishi()
+        .arg("cp")
+        .arg("web")
+        .arg("backup/web")
+        .assert()
+        .success();
+
+    let _ = store
+        .koishi()
+        .arg("get")
+        .arg("backup/web/site.yaml")
+        .arg("password")
+        .assert()
+        .success()
+        .stdout("hunter2");
+    let _ = store
+        .koishi()
+        .arg("get")
+        .arg("backup/web/pin")
+        .assert()
+        .success()
+        .stdout("1234");
+
+    // The source is kept, and all of the copies are made in a single commit
+    assert!(store.root().join("web/site.yaml").exists());
+    assert_eq!(
+        store.git(&["log", "-1", "--format=%s"]),
+        "Copy `web` => `backup/web`\n"
+    );
+    assert_eq!(store.git(&["status", "--porcelain"]), "");
+
+    let _ = store
+        .koishi()
+        .arg("cp")
+        .arg("web/pin")
+        .arg("backup/web/pin")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Destination `backup/web/pin` already exists",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn cp_to_another_store() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set("pin", "1234");
+
+    // The other store shares the age identity, so that the copy can be read back
+    let other = store.clone_through_remote();
+    std::fs::write(
+        store.scratch().join("config.toml"),
+        format!(
+            "[stores.other]\npath = \"{}\"\n",
+            other.root().to_str().unwrap()
+        ),
+    )?;
+
+    let _ = store
+        .koishi()
+        .arg("cp")
+        .arg("pin")
+        .arg("@other:shared/pin")
+        .assert()
+        .success();
+
+    let _ = other
+        .koishi()
+        .arg("get")
+        .arg("shared/pin")
+        .assert()
+        .success()
+        .stdout("1234");
+    assert!(store.root().join("pin").exists());
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+#[test]
+fn diff_revisions() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set(
+        "web.yaml",
+        "user: alice\npassword: hunter2\nurl: example.com\n",
+    );
+    store.set("web.yaml", "user: alice\npassword: hunter3\notp: abc\n");
+
+    let _ = store
+        .koishi()
+        .arg("diff")
+        .arg("web.yaml")
+        .arg("HEAD~1")
+        .arg("HEAD")
+        .assert()
+        .success()
+        .stdout("~ password: ******** -> ********\n+ otp: ********\n- url: ********\n");
+
+    let _ = store
+        .koishi()
+        .arg("diff")
+        .arg("--show")
+        .arg("web.yaml")
+        .arg("HEAD~1")
+        .assert()
+        .success()
+        .stdout("~ password: hunter2 -> hunter3\n+ otp: abc\n- url: example.com\n");
+
+    // The working tree matches the last commit
+    let _ = store
+        .koishi()
+        .arg("diff")
+        .arg("web.yaml")
+        .assert()
+        .success()
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn diff_added_record() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set("pin", "1234");
+
+    let _ = store
+        .koishi()
+        .arg("diff")
+        .arg("--show")
+        .arg("pin")
+        .arg("HEAD~1")
+        .arg("HEAD")
+        .assert()
+        .success()
+        .stdout("+ pin: 1234\n");
+
+    let _ = store
+        .koishi()
+        .arg("diff")
+        .arg("missing")
+        .arg("HEAD~1")
+        .arg("HEAD")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No secret found at `missing`"));
+
+    Ok(())
+}
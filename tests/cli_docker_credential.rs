@@ -0,0 +1,91 @@
+mod common;
+
+use assert_cmd::cargo_bin;
+use common::TestStore;
+use predicates::prelude::*;
+
+#[test]
+fn docker_credential_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set("registries/notes", "not credentials");
+
+    let _ = store
+        .koishi()
+        .arg("docker-credential")
+        .arg("store")
+        .write_stdin(r#"{"ServerURL":"https://ghcr.io","Username":"alice","Secret":"hunter2"}"#)
+        .assert()
+        .success()
+        .stdout("");
+
+    let _ = store
+        .koishi()
+        .arg("docker-credential")
+        .arg("get")
+        .write_stdin("https://ghcr.io\n")
+        .assert()
+        .success()
+        .stdout(r#"{"ServerURL":"https://ghcr.io","Username":"alice","Secret":"hunter2"}"#);
+
+    // Records that aren't credentials are skipped with a warning
+    let _ = store
+        .koishi()
+        .arg("docker-credential")
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(r#"{"https://ghcr.io":"alice"}"#)
+        .stderr(predicate::str::contains("Skipping `registries/notes`"));
+
+    let _ = store
+        .koishi()
+        .arg("docker-credential")
+        .arg("erase")
+        .write_stdin("https://ghcr.io\n")
+        .assert()
+        .success();
+    assert!(!store.root().join("registries/ghcr.io.yaml").exists());
+
+    Ok(())
+}
+
+#[test]
+fn docker_credential_not_found() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+
+    // Docker looks for this exact message
+    let _ = store
+        .koishi()
+        .arg("docker-credential")
+        .arg("get")
+        .write_stdin("https://ghcr.io\n")
+        .assert()
+        .code(1)
+        .stdout("credentials not found in native keychain\n");
+
+    Ok(())
+}
+
+#[test]
+fn run_as_docker_credential_helper() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set(
+        "registries/ghcr.io.yaml",
+        "server_url: ghcr.io\nusername: alice\nsecret: hunter2\n",
+    );
+
+    let helper = store
+        .scratch()
+        .join("docker-credential-koishi")
+        .with_extension(std::env::consts::EXE_EXTENSION);
+    let _ = std::fs::copy(cargo_bin!("koishi"), &helper)?;
+
+    let _ = store
+        .command(&helper)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(r#"{"ghcr.io":"alice"}"#);
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+#[test]
+fn exec_exports_secrets() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set(
+        "db.yaml",
+        "user: alice\npassword: hunter2\napi:\n  key: abc123\n",
+    );
+    store.set("admin.yaml", "user: root\n");
+    store.set("pin", "1234");
+
+    // `--map` overrides the value of `USER` from the record, whichever order they are given in
+    let _ = store
+        .koishi()
+        .arg("exec")
+        .arg("--map")
+        .arg("USER=admin.yaml:user")
+        .arg("--map-record")
+        .arg("db.yaml")
+        .arg("--map")
+        .arg("PIN=pin")
+        .arg("--")
+        .arg("sh")
+        .arg("-c")
+        .arg("echo \"$USER $PASSWORD $API_KEY $PIN\"")
+        .assert()
+        .success()
+        .stdout("root hunter2 abc123 1234\n");
+
+    Ok(())
+}
+
+#[test]
+fn exec_passes_exit_code_on() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set("pin", "1234");
+
+    let _ = store
+        .koishi()
+        .arg("exec")
+        .arg("--map")
+        .arg("PIN=pin")
+        .arg("--")
+        .arg("sh")
+        .arg("-c")
+        .arg("echo $PIN; exit 3")
+        .assert()
+        .code(3)
+        .stdout("1234\n");
+
+    Ok(())
+}
+
+#[test]
+fn exec_rejects_invalid_map() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set("db.yaml", "user: alice\npassword: hunter2\n");
+
+    let _ = store
+        .koishi()
+        .arg("exec")
+        .arg("--map")
+        .arg("db/prod:password")
+        .arg("--")
+        .arg("true")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected VAR=PATH[:SELECTOR]"));
+
+    // Nothing is run if a value can't be exported
+    let _ = store
+        .koishi()
+        .arg("exec")
+        .arg("--map")
+        .arg("DB=db.yaml")
+        .arg("--")
+        .arg("echo")
+        .arg("ran")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains("is not a single value"));
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+#[test]
+fn find_paths_and_attributes() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set(
+        "web/site.yaml",
+        "url: https://example.com\npassword: hunter2\n",
+    );
+    store.set("mail/work.yaml", "password: hunter3\n");
+    store.set("notes.txt", "password: not an attribute\n");
+
+    let _ = store
+        .koishi()
+        .arg("find")
+        .arg("password")
+        .assert()
+        .success()
+        .stdout("mail/work.yaml:password\nweb/site.yaml:password\n");
+
+    let _ = store
+        .koishi()
+        .arg("find")
+        .arg("*.txt")
+        .assert()
+        .success()
+        .stdout("notes.txt\n");
+
+    let _ = store
+        .koishi()
+        .arg("find")
+        .arg("--regex")
+        .arg("^web/|url")
+        .assert()
+        .success()
+        .stdout("web/site.yaml\nweb/site.yaml:url\n")
+        // Values are never decrypted
+        .stdout(predicate::str::contains("example.com").not());
+
+    Ok(())
+}
+
+#[test]
+fn find_under_path() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set("web/site.yaml", "password: hunter2\n");
+    store.set("mail/work.yaml", "password: hunter3\n");
+
+    let _ = store
+        .koishi()
+        .arg("find")
+        .arg("password")
+        .arg("mail")
+        .assert()
+        .success()
+        .stdout("mail/work.yaml:password\n");
+
+    Ok(())
+}
+
+#[test]
+fn find_skips_binary_records() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set("pin", "1234");
+    store.set("notes.txt", "some notes");
+    store.set("backup.yaml", "data: abc\n");
+
+    // Binary records are stored under a `data` key, which isn't one of their attributes
+    let _ = store
+        .koishi()
+        .arg("find")
+        .arg("data")
+        .assert()
+        .success()
+        .stdout("backup.yaml:data\n");
+
+    Ok(())
+}
+
+#[test]
+fn find_rejects_invalid_pattern() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+
+    let _ = store
+        .koishi()
+        .arg("find")
+        .arg("--regex")
+        .arg("(")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid regex pattern"));
+
+    Ok(())
+}
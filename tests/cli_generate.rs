@@ -0,0 +1,80 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+fn get(store: &TestStore, args: &[&str]) -> String {
+    let output = store.koishi().arg("get").args(args).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn generate_password() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+
+    // The secret is only written to the record
+    let _ = store
+        .koishi()
+        .arg("generate")
+        .arg("--length")
+        .arg("16")
+        .arg("--no-lowercase")
+        .arg("--no-uppercase")
+        .arg("--no-symbols")
+        .arg("pin")
+        .assert()
+        .success()
+        .stdout("");
+
+    let pin = get(&store, &["pin"]);
+    assert_eq!(pin.len(), 16);
+    assert!(pin.chars().all(|c| c.is_ascii_digit()));
+
+    Ok(())
+}
+
+#[test]
+fn generate_passphrase_into_attribute() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set("web.yaml", "user: alice\npassword: hunter2\n");
+
+    let _ = store
+        .koishi()
+        .arg("generate")
+        .arg("--passphrase")
+        .arg("--words")
+        .arg("4")
+        .arg("--separator")
+        .arg(" ")
+        .arg("web.yaml")
+        .arg("password")
+        .assert()
+        .success()
+        .stdout("");
+
+    let password = get(&store, &["web.yaml", "password"]);
+    assert_eq!(password.split(' ').count(), 4);
+    assert_ne!(password, "hunter2");
+    assert_eq!(get(&store, &["web.yaml", "user"]), "alice");
+
+    Ok(())
+}
+
+#[test]
+fn generate_length_conflicts_with_passphrase() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+
+    let _ = store
+        .koishi()
+        .arg("generate")
+        .arg("--passphrase")
+        .arg("--length")
+        .arg("12")
+        .arg("pin")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
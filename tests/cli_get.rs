@@ -47,3 +47,17 @@ fn get_with_nonexistent_store() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn get_timeout_requires_copy() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::new(cargo_bin!("koishi"));
+
+    let _ = cmd.arg("get").arg("--timeout").arg("10").arg("some/path");
+
+    let _ = cmd
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--copy"));
+
+    Ok(())
+}
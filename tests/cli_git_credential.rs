@@ -0,0 +1,96 @@
+mod common;
+
+use common::TestStore;
+
+#[test]
+fn git_credential_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+
+    let _ = store
+        .koishi()
+        .arg("git-credential")
+        .arg("store")
+        .write_stdin("protocol=https\nhost=example.com\nusername=alice\npassword=hunter2\n\n")
+        .assert()
+        .success()
+        .stdout("");
+
+    let _ = store
+        .koishi()
+        .arg("get")
+        .arg("git/https/example.com/alice.yaml")
+        .arg("password")
+        .assert()
+        .success()
+        .stdout("hunter2");
+
+    let _ = store
+        .koishi()
+        .arg("git-credential")
+        .arg("get")
+        .write_stdin("protocol=https\nhost=example.com\n\n")
+        .assert()
+        .success()
+        .stdout("username=alice\npassword=hunter2\n");
+
+    let _ = store
+        .koishi()
+        .arg("git-credential")
+        .arg("erase")
+        .write_stdin("protocol=https\nhost=example.com\nusername=alice\npassword=hunter2\n\n")
+        .assert()
+        .success();
+
+    let _ = store
+        .koishi()
+        .arg("git-credential")
+        .arg("get")
+        .write_stdin("protocol=https\nhost=example.com\nusername=alice\n\n")
+        .assert()
+        .success()
+        .stdout("");
+    assert!(
+        !store
+            .root()
+            .join("git/https/example.com/alice.yaml")
+            .exists()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn git_credential_custom_prefix() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set("forges/https/example.com/bob.yaml", "password: abc\n");
+
+    let _ = store
+        .koishi()
+        .arg("git-credential")
+        .arg("--prefix")
+        .arg("forges")
+        .arg("get")
+        .write_stdin("protocol=https\nhost=example.com\nusername=bob\n\n")
+        .assert()
+        .success()
+        .stdout("username=bob\npassword=abc\n");
+
+    Ok(())
+}
+
+#[test]
+fn git_credential_unknown_operation() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+
+    // Git may add operations in future, which helpers must ignore
+    let _ = store
+        .koishi()
+        .arg("git-credential")
+        .arg("capability")
+        .write_stdin("protocol=https\nhost=example.com\n\n")
+        .assert()
+        .success()
+        .stdout("");
+
+    Ok(())
+}
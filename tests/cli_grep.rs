@@ -0,0 +1,95 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+fn store_with_records() -> TestStore {
+    let store = TestStore::new();
+    store.set("web/site.yaml", "user: alice\npassword: hunter2\n");
+    store.set("mail/work.yaml", "user: bob\npassword: correct horse\n");
+    store.set("notes.txt", "the wifi password is Hunter3");
+    store
+}
+
+#[test]
+fn grep_masks_matches() -> Result<(), Box<dyn std::error::Error>> {
+    let store = store_with_records();
+
+    let _ = store
+        .koishi()
+        .arg("grep")
+        .arg("hunter")
+        .assert()
+        .success()
+        .stdout("web/site.yaml:password: ********\n");
+
+    Ok(())
+}
+
+#[test]
+fn grep_show() -> Result<(), Box<dyn std::error::Error>> {
+    let store = store_with_records();
+
+    let _ = store
+        .koishi()
+        .arg("grep")
+        .arg("--show")
+        .arg("--ignore-case")
+        .arg("hunter")
+        .assert()
+        .success()
+        .stdout("notes.txt: the wifi password is Hunter3\nweb/site.yaml:password: hunter2\n");
+
+    let _ = store
+        .koishi()
+        .arg("grep")
+        .arg("--show")
+        .arg("^(alice|bob)$")
+        .arg("mail")
+        .assert()
+        .success()
+        .stdout("mail/work.yaml:user: bob\n");
+
+    Ok(())
+}
+
+#[test]
+fn grep_json() -> Result<(), Box<dyn std::error::Error>> {
+    let store = store_with_records();
+
+    let output = store
+        .koishi()
+        .arg("--output")
+        .arg("json")
+        .arg("grep")
+        .arg("horse")
+        .output()?;
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        json,
+        serde_json::json!([{
+            "record": "mail/work.yaml",
+            "attribute": "password",
+            "value": null,
+        }])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn grep_rejects_invalid_regex() -> Result<(), Box<dyn std::error::Error>> {
+    let store = store_with_records();
+
+    let _ = store
+        .koishi()
+        .arg("grep")
+        .arg("(")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid regex pattern"));
+
+    Ok(())
+}
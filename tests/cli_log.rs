@@ -0,0 +1,89 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+/// IDs of the commits that changed a record, newest first.
+fn log_ids(store: &TestStore, path: &str) -> Vec<String> {
+    let output = store
+        .koishi()
+        .arg("--output")
+        .arg("json")
+        .arg("log")
+        .arg(path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    entries
+        .iter()
+        .map(|entry| entry["id"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[test]
+fn log_and_restore() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.write_config("default_format = \"yaml\"\n");
+    store.set("web/site.yaml", "password: hunter2\n");
+    store.set("web/site.yaml", "password: hunter3\n");
+    store.set("mail/work.yaml", "password: hunter4\n");
+
+    // The path is resolved the same way as for `get`
+    let _ = store
+        .koishi()
+        .arg("log")
+        .arg("web/site")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Update contents of record `web/site.yaml`").count(2));
+
+    let ids = log_ids(&store, "web/site");
+    assert_eq!(ids.len(), 2);
+
+    let _ = store
+        .koishi()
+        .arg("rm")
+        .arg("web/site.yaml")
+        .assert()
+        .success();
+
+    // Deleted records still have a history and can be restored
+    assert_eq!(log_ids(&store, "web/site").len(), 3);
+
+    let _ = store
+        .koishi()
+        .arg("restore")
+        .arg("--rev")
+        .arg(&ids[1])
+        .arg("web/site")
+        .assert()
+        .success();
+
+    let _ = store
+        .koishi()
+        .arg("get")
+        .arg("web/site")
+        .arg("password")
+        .assert()
+        .success()
+        .stdout("hunter2");
+
+    Ok(())
+}
+
+#[test]
+fn log_without_history() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+
+    let _ = store
+        .koishi()
+        .arg("log")
+        .arg("missing")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No history for `missing`"));
+
+    Ok(())
+}
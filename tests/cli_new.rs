@@ -0,0 +1,113 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+fn store_with_template() -> TestStore {
+    let store = TestStore::new();
+    let templates = store.root().join(".koishi/templates");
+    std::fs::create_dir_all(&templates).unwrap();
+    // Only generated fields, since the others are prompted for
+    std::fs::write(
+        templates.join("login.yaml"),
+        "password:\n  generate: password\n  length: 24\nrecovery:\n  generate: passphrase\n  words: 3\n",
+    )
+    .unwrap();
+    store
+}
+
+fn get(store: &TestStore, args: &[&str]) -> String {
+    let output = store.koishi().arg("get").args(args).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn new_from_template() -> Result<(), Box<dyn std::error::Error>> {
+    let store = store_with_template();
+
+    let _ = store
+        .koishi()
+        .arg("new")
+        .arg("--template")
+        .arg("login")
+        .arg("web/site")
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(predicate::str::contains("Created `web/site.yaml`"));
+
+    assert_eq!(get(&store, &["web/site.yaml", "password"]).len(), 24);
+    assert_eq!(
+        get(&store, &["web/site.yaml", "recovery"])
+            .split('-')
+            .count(),
+        3
+    );
+
+    Ok(())
+}
+
+#[test]
+fn new_uses_default_format() -> Result<(), Box<dyn std::error::Error>> {
+    let store = store_with_template();
+    store.write_config("default_format = \"json\"\n");
+
+    let _ = store
+        .koishi()
+        .arg("new")
+        .arg("--template")
+        .arg("login")
+        .arg("web/site")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Created `web/site.json`"));
+
+    assert_eq!(get(&store, &["web/site.json", "password"]).len(), 24);
+
+    Ok(())
+}
+
+#[test]
+fn new_rejects_unknown_template_and_format() -> Result<(), Box<dyn std::error::Error>> {
+    let store = store_with_template();
+
+    let _ = store
+        .koishi()
+        .arg("new")
+        .arg("--template")
+        .arg("missing")
+        .arg("web/site")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No template named `missing`"));
+
+    let _ = store
+        .koishi()
+        .arg("new")
+        .arg("--template")
+        .arg("login")
+        .arg("web/site.txt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("must be YAML or JSON"));
+
+    // Templates can't be loaded from outside the templates directory
+    std::fs::write(
+        store.root().join("outside.yaml"),
+        "password:\n  generate: password\n",
+    )?;
+    let _ = store
+        .koishi()
+        .arg("new")
+        .arg("--template")
+        .arg("../../outside")
+        .arg("web/site")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("isn't a valid template name"));
+
+    assert!(!store.root().join("web").exists());
+
+    Ok(())
+}
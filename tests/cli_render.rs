@@ -0,0 +1,103 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+fn store_with_records() -> TestStore {
+    let store = TestStore::new();
+    store.set("db/prod.yaml", "user: alice\npassword: hunter2\n");
+    store.set("api/token", "abc123");
+    store
+}
+
+#[test]
+fn render_to_stdout() -> Result<(), Box<dyn std::error::Error>> {
+    let store = store_with_records();
+    let template = store.scratch().join("app.env.tpl");
+    std::fs::write(
+        &template,
+        "DB_USER={{ koishi \"db/prod.yaml\" \"user\" }}\nDB_PASS={{ koishi \"db/prod.yaml\" \"password\" }}\nTOKEN={{koishi \"api/token\"}}\nNAME={{ .Values.name }}\n",
+    )?;
+
+    let _ = store
+        .koishi()
+        .arg("render")
+        .arg(&template)
+        .assert()
+        .success()
+        .stdout("DB_USER=alice\nDB_PASS=hunter2\nTOKEN=abc123\nNAME={{ .Values.name }}\n");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn render_to_private_file() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let store = store_with_records();
+    let template = store.scratch().join("token.tpl");
+    std::fs::write(&template, "token: {{ koishi \"api/token\" }}\n")?;
+    let out = store.scratch().join("token.yaml");
+
+    let _ = store
+        .koishi()
+        .arg("render")
+        .arg("--out")
+        .arg(&out)
+        .arg(&template)
+        .assert()
+        .success()
+        .stdout("");
+
+    assert_eq!(std::fs::read_to_string(&out)?, "token: abc123\n");
+    assert_eq!(std::fs::metadata(&out)?.permissions().mode() & 0o777, 0o600);
+
+    Ok(())
+}
+
+#[test]
+fn render_fails_without_writing() -> Result<(), Box<dyn std::error::Error>> {
+    let store = store_with_records();
+    let out = store.scratch().join("out");
+
+    // A YAML record can't be used without a selector
+    let template = store.scratch().join("whole.tpl");
+    std::fs::write(&template, "a\n{{ koishi \"db/prod.yaml\" }}\n")?;
+
+    let _ = store
+        .koishi()
+        .arg("render")
+        .arg("--out")
+        .arg(&out)
+        .arg(&template)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("On line 2"))
+        .stderr(predicate::str::contains("is not a single value"));
+
+    let template = store.scratch().join("missing.tpl");
+    std::fs::write(&template, "{{ koishi \"db/prod.yaml\" \"otp\" }}\n")?;
+
+    let _ = store
+        .koishi()
+        .arg("render")
+        .arg("--out")
+        .arg(&out)
+        .arg(&template)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("in record `db/prod.yaml`"));
+
+    assert!(!out.exists());
+
+    let _ = store
+        .koishi()
+        .arg("render")
+        .arg(store.scratch().join("nonexistent.tpl"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to read"));
+
+    Ok(())
+}
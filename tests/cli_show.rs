@@ -0,0 +1,111 @@
+mod common;
+
+use common::TestStore;
+
+fn store_with_records() -> TestStore {
+    let store = TestStore::new();
+    store.set("web/site.yaml", "user: alice\npassword: hunter2\n");
+    store.set("mail/work.yaml", "password: hunter3\n");
+    store.set("mail/old/home.yaml", "password: hunter4\n");
+    store.set("pin", "1234");
+    store
+}
+
+#[test]
+fn show_tree() -> Result<(), Box<dyn std::error::Error>> {
+    let store = store_with_records();
+
+    let _ = store
+        .koishi()
+        .arg("show")
+        .arg("--attributes")
+        .arg("mail")
+        .assert()
+        .success()
+        .stdout(concat!(
+            "mail\n",
+            "├── work.yaml (1 attribute)\n",
+            "└── old\n",
+            "    └── home.yaml (1 attribute)\n",
+        ));
+
+    // Binary records have no attributes to count
+    let _ = store
+        .koishi()
+        .arg("show")
+        .arg("--attributes")
+        .arg("--depth")
+        .arg("1")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}\n{}",
+            store.root().display(),
+            concat!(
+                "├── pin\n",
+                "├── mail (2 records)\n",
+                "└── web (1 record)\n",
+            )
+        ));
+
+    let _ = store
+        .koishi()
+        .arg("show")
+        .arg("--depth")
+        .arg("1")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}\n{}",
+            store.root().display(),
+            concat!(
+                "├── pin\n",
+                "├── mail (2 records)\n",
+                "└── web (1 record)\n",
+            )
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn show_json() -> Result<(), Box<dyn std::error::Error>> {
+    let store = store_with_records();
+
+    let output = store
+        .koishi()
+        .arg("--output")
+        .arg("json")
+        .arg("show")
+        .arg("--attributes")
+        .arg("--modified")
+        .arg("--recipients")
+        .arg("web")
+        .output()?;
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(json[0]["record"], "web/site.yaml");
+    assert_eq!(json[0]["attributes"], 2);
+    assert!(json[0]["modified"].is_string());
+    assert_eq!(
+        json[0]["recipients"],
+        serde_json::json!([store.recipient()])
+    );
+    assert_eq!(json.as_array().map(Vec::len), Some(1));
+
+    let output = store
+        .koishi()
+        .arg("--output")
+        .arg("json")
+        .arg("show")
+        .arg("--attributes")
+        .output()?;
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(json[0]["record"], "pin");
+    assert_eq!(json[0]["attributes"], 0);
+
+    Ok(())
+}
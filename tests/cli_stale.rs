@@ -0,0 +1,59 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+#[test]
+fn stale_attributes() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+
+    let _ = store
+        .koishi()
+        .env("GIT_AUTHOR_DATE", "2020-01-15T12:00:00Z")
+        .env("GIT_COMMITTER_DATE", "2020-01-15T12:00:00Z")
+        .arg("set")
+        .arg("web/site.yaml")
+        .write_stdin("user: alice\npassword: hunter2\n")
+        .assert()
+        .success();
+
+    // Only the password is changed, so the user keeps its age
+    store.set("web/site.yaml", "user: alice\npassword: hunter3\n");
+
+    let _ = store
+        .koishi()
+        .arg("stale")
+        .arg("--older-than")
+        .arg("1y")
+        .assert()
+        .success()
+        .stdout("web/site.yaml:user (last changed 2020-01-15)\n");
+
+    let _ = store
+        .koishi()
+        .arg("stale")
+        .arg("--older-than")
+        .arg("1y")
+        .arg("mail")
+        .assert()
+        .success()
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn stale_rejects_invalid_duration() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+
+    let _ = store
+        .koishi()
+        .arg("stale")
+        .arg("--older-than")
+        .arg("soon")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--older-than"));
+
+    Ok(())
+}
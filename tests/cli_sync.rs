@@ -0,0 +1,135 @@
+mod common;
+
+use common::TestStore;
+use predicates::prelude::*;
+
+#[test]
+fn sync_merges_records() -> Result<(), Box<dyn std::error::Error>> {
+    let a = TestStore::new();
+    a.set("web.yaml", "user: alice\npassword: one\n");
+    let b = a.clone_through_remote();
+
+    // Both sides change a different attribute of the same record
+    a.set("web.yaml", "user: alice\npassword: two\n");
+    b.set("web.yaml", "user: bob\npassword: one\n");
+
+    let _ = a
+        .koishi()
+        .arg("sync")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Pulled 0 commit(s), pushed 1 commit(s).",
+        ));
+
+    let _ = b
+        .koishi()
+        .arg("sync")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Merged changes to `web.yaml`"))
+        .stderr(predicate::str::contains(
+            "Pulled 1 commit(s), pushed 2 commit(s).",
+        ));
+
+    let _ = a
+        .koishi()
+        .arg("sync")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Pulled 2 commit(s), pushed 0 commit(s).",
+        ));
+
+    for store in [&a, &b] {
+        let _ = store
+            .koishi()
+            .arg("get")
+            .arg("web.yaml")
+            .assert()
+            .success()
+            .stdout("user: bob\npassword: two\n");
+
+        let _ = store
+            .koishi()
+            .arg("sync")
+            .assert()
+            .success()
+            .stderr("Already up to date.\n");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn sync_without_git() -> Result<(), Box<dyn std::error::Error>> {
+    let store = TestStore::new();
+    store.set("web.yaml", "password: one\n");
+
+    let _ = store
+        .koishi()
+        .env("PATH", store.scratch())
+        .arg("sync")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "The `git` executable was not found on the PATH",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn auto_push_warnings_as_json() -> Result<(), Box<dyn std::error::Error>> {
+    let a = TestStore::new();
+    a.write_config("auto_push = true\n");
+    let _ = a.git(&["add", ".koishi.toml"]);
+    let _ = a.git(&["commit", "--quiet", "--message", "Enable auto-push"]);
+    let _b = a.clone_through_remote();
+    std::fs::remove_dir_all(a.scratch().join("remote.git"))?;
+
+    // Running offline doesn't contact the remote
+    let _ = a
+        .koishi()
+        .arg("--offline")
+        .arg("--output")
+        .arg("json")
+        .arg("ls")
+        .assert()
+        .success()
+        .stderr("");
+
+    let output = a
+        .koishi()
+        .arg("--output")
+        .arg("json")
+        .arg("set")
+        .arg("web.yaml")
+        .write_stdin("password: one\n")
+        .output()?;
+    assert!(output.status.success());
+
+    let warnings = String::from_utf8(output.stderr)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    let messages: Vec<_> = warnings
+        .iter()
+        .map(|w| (w["severity"].as_str(), w["message"].as_str()))
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            (
+                Some("warning"),
+                Some("Failed to pull changes from the remote")
+            ),
+            (
+                Some("warning"),
+                Some("Failed to push changes, they have been committed locally")
+            ),
+        ]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+// Each test binary only uses some of these
+#![allow(dead_code)]
+
+use age::secrecy::ExposeSecret;
+use assert_cmd::{Command, cargo_bin};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
+
+/// Identity for commits, so that tests don't depend on the user's Git config.
+const GIT_IDENTITY: [(&str, &str); 4] = [
+    ("GIT_AUTHOR_NAME", "Test"),
+    ("GIT_AUTHOR_EMAIL", "test@example.com"),
+    ("GIT_COMMITTER_NAME", "Test"),
+    ("GIT_COMMITTER_EMAIL", "test@example.com"),
+];
+
+/// A store in a temporary directory, with its records encrypted for an age identity that only
+/// exists for the test.
+pub struct TestStore {
+    dir: TempDir,
+    age_key: String,
+}
+
+impl TestStore {
+    pub fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let identity = age::x25519::Identity::generate();
+
+        let store = Self {
+            dir,
+            age_key: identity.to_string().expose_secret().to_owned(),
+        };
+
+        let _ = git(store.scratch(), &["init", "--quiet", "store"]);
+        std::fs::write(
+            store.root().join(".sops.yaml"),
+            format!("creation_rules:\n  - age: {}\n", identity.to_public()),
+        )
+        .unwrap();
+        let _ = store.git(&["add", ".sops.yaml"]);
+        let _ = store.git(&["commit", "--quiet", "--message", "Add SOPS config"]);
+
+        store
+    }
+
+    /// Pushes the store to a new bare repository next to it, which becomes its upstream, and
+    /// returns a second store cloned from there that uses the same age identity.
+    pub fn clone_through_remote(&self) -> Self {
+        let _ = git(self.scratch(), &["init", "--quiet", "--bare", "remote.git"]);
+        let _ = self.git(&["remote", "add", "origin", "../remote.git"]);
+        let _ = self.git(&["push", "--quiet", "--set-upstream", "origin", "HEAD"]);
+
+        let clone = Self {
+            dir: TempDir::new().unwrap(),
+            age_key: self.age_key.clone(),
+        };
+        let _ = git(
+            clone.scratch(),
+            &[
+                "clone",
+                "--quiet",
+                self.scratch().join("remote.git").to_str().unwrap(),
+                "store",
+            ],
+        );
+
+        clone
+    }
+
+    /// The age recipient that records are encrypted for.
+    pub fn recipient(&self) -> String {
+        self.age_key
+            .parse::<age::x25519::Identity>()
+            .unwrap()
+            .to_public()
+            .to_string()
+    }
+
+    /// Runs a Git command in the store.
+    pub fn git(&self, args: &[&str]) -> String {
+        git(&self.root(), args)
+    }
+
+    pub fn root(&self) -> PathBuf {
+        self.dir.path().join("store")
+    }
+
+    /// A directory next to the store for anything else a test needs.
+    pub fn scratch(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Builds a koishi command that uses this store, and nothing from the user's environment.
+    pub fn koishi(&self) -> Command {
+        self.command(cargo_bin!("koishi"))
+    }
+
+    /// Builds a command like [`Self::koishi`] for a copy of koishi run by another name.
+    pub fn command(&self, program: impl AsRef<OsStr>) -> Command {
+        let mut cmd = Command::new(program);
+
+        let _ = cmd
+            .env("KOISHI_STORE", self.root())
+            .env("KOISHI_CONFIG", self.dir.path().join("config.toml"))
+            .env("XDG_CONFIG_HOME", self.dir.path().join("config"))
+            .env("SOPS_AGE_KEY", &self.age_key)
+            .env_remove("SOPS_AGE_KEY_FILE")
+            .env_remove("SOPS_AGE_KEY_CMD")
+            .env_remove("KOISHI_OFFLINE")
+            .envs(GIT_IDENTITY);
+
+        cmd
+    }
+
+    /// Writes the store config, e.g. to make YAML the default format of new records.
+    pub fn write_config(&self, contents: &str) {
+        std::fs::write(self.root().join(".koishi.toml"), contents).unwrap();
+    }
+
+    /// Sets the entire contents of a record.
+    pub fn set(&self, path: &str, contents: &str) {
+        let output = self
+            .koishi()
+            .arg("set")
+            .arg(path)
+            .write_stdin(contents)
+            .output()
+            .unwrap();
+
+        assert!(
+            output.status.success(),
+            "failed to set `{path}`: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .envs(GIT_IDENTITY)
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "git {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout).unwrap()
+}